@@ -135,13 +135,18 @@
 //! [`Builder`]: struct.Builder.html
 //! [`Error`]: ../struct.Error.html
 
+mod pool;
+
+pub use self::pool::{Pool, PoolConnection};
+
 use crate::codec::{Codec, RecvError, SendError, UserError};
-use crate::frame::{Headers, Pseudo, Reason, Settings, StreamId};
+use crate::ext::{ExtensionFrames, Origin, Protocol};
+use crate::frame::{self, Headers, Pseudo, Reason, Settings, StreamId};
 use crate::proto;
-use crate::{FlowControl, PingPong, RecvStream, SendStream};
+use crate::{ConnectionStats, FlowControl, PingPong, RecvStream, SendStream};
 
 use bytes::{Buf, Bytes};
-use http::{uri, HeaderMap, Method, Request, Response, Version};
+use http::{uri, HeaderMap, HeaderValue, Method, Request, Response, Version};
 use std::fmt;
 use std::future::Future;
 use std::pin::Pin;
@@ -269,6 +274,23 @@ pub struct PushPromises {
     inner: proto::OpaqueStreamRef,
 }
 
+/// An alternative service advertisement received in an [ALTSVC frame].
+///
+/// [ALTSVC frame]: https://www.rfc-editor.org/rfc/rfc7838.html#section-4
+#[derive(Debug, Clone)]
+pub struct AltSvc {
+    origin: Option<Origin>,
+    stream_id: Option<crate::StreamId>,
+    field_value: http::HeaderValue,
+}
+
+/// A stream of alternative service advertisements received from the server
+#[derive(Debug)]
+#[must_use = "streams do nothing unless polled"]
+pub struct AltSvcs {
+    inner: proto::UserAltSvcs,
+}
+
 /// Builds client connections with custom configuration values.
 ///
 /// Methods can be chained in order to set the configuration values.
@@ -322,12 +344,55 @@ pub struct Builder {
     /// Maximum number of locally reset streams to keep at a time.
     reset_stream_max: usize,
 
+    /// Maximum amount of data each stream may buffer before it is sent.
+    max_send_buffer_size: usize,
+
     /// Initial `Settings` frame to send as part of the handshake.
     settings: Settings,
 
     /// The stream ID of the first (lowest) stream. Subsequent streams will use
     /// monotonically increasing stream IDs.
     stream_id: StreamId,
+
+    /// Interval at which keep-alive PINGs are sent, if any.
+    keep_alive_interval: Option<Duration>,
+
+    /// How long to wait for a keep-alive PING to be acknowledged.
+    keep_alive_timeout: Duration,
+
+    /// Whether keep-alive PINGs are sent without any active streams.
+    keep_alive_while_idle: bool,
+
+    /// How long the connection may go without active streams, if limited.
+    idle_timeout: Option<Duration>,
+
+    /// Whether receive windows grow with the estimated bandwidth-delay
+    /// product.
+    adaptive_window: bool,
+
+    /// Whether received data releases its capacity automatically.
+    auto_release_capacity: bool,
+
+    /// Extension frame types to hand to the user.
+    extension_frame_types: Vec<u8>,
+
+    /// Maximum number of extension frames queued in each direction.
+    max_queued_extension_frames: usize,
+
+    /// Maximum number of CONTINUATION frames in a received header block.
+    max_continuation_frames: Option<usize>,
+
+    /// Maximum number of encoded bytes in a received header block.
+    max_header_block_size: Option<usize>,
+
+    /// Maximum size of the header table used to encode header blocks.
+    max_send_header_table_size: Option<usize>,
+
+    /// Maximum number of each type of control frame received per second.
+    max_control_frames_per_second: usize,
+
+    /// Maximum number of empty DATA frames received per second.
+    max_empty_data_frames_per_second: usize,
 }
 
 #[derive(Debug)]
@@ -501,7 +566,6 @@ where
     ) -> Result<(ResponseFuture, SendStream<B>), crate::Error> {
         self.inner
             .send_request(request, end_of_stream, self.pending.as_ref())
-            .map_err(Into::into)
             .map(|stream| {
                 if stream.is_pending_open() {
                     self.pending = Some(stream.clone_to_opaque());
@@ -517,6 +581,39 @@ where
                 (response, stream)
             })
     }
+
+    /// Returns whether the [extended CONNECT protocol][1] is enabled or not.
+    ///
+    /// This setting is configured by the server peer by sending the
+    /// [`SETTINGS_ENABLE_CONNECT_PROTOCOL` parameter][2] in a `SETTINGS` frame.
+    /// This method returns the currently acknowledged value received from the
+    /// remote.
+    ///
+    /// When enabled, a request using the `CONNECT` method may carry a
+    /// [`Protocol`] in its extensions, which is sent as the `:protocol`
+    /// pseudo-header.
+    ///
+    /// [1]: https://datatracker.ietf.org/doc/html/rfc8441#section-4
+    /// [2]: https://datatracker.ietf.org/doc/html/rfc8441#section-3
+    /// [`Protocol`]: ../ext/struct.Protocol.html
+    pub fn is_extended_connect_protocol_enabled(&self) -> bool {
+        self.inner.is_extended_connect_protocol_enabled()
+    }
+
+    /// Returns the origins received from the server in [ORIGIN frames].
+    ///
+    /// Returns `None` if the server has not sent an ORIGIN frame. Otherwise,
+    /// the server claims to be authoritative for the returned origins, in
+    /// addition to the origin this connection was opened for, so that
+    /// requests to any of them may be sent on this connection.
+    ///
+    /// Origins that are not valid are left out, and at most 256 origins are
+    /// kept; any more that the server sends are ignored.
+    ///
+    /// [ORIGIN frames]: https://www.rfc-editor.org/rfc/rfc8336.html
+    pub fn origin_set(&self) -> Option<Vec<Origin>> {
+        self.inner.origin_set()
+    }
 }
 
 impl<B> fmt::Debug for SendRequest<B>
@@ -616,10 +713,24 @@ impl Builder {
         Builder {
             reset_stream_duration: Duration::from_secs(proto::DEFAULT_RESET_STREAM_SECS),
             reset_stream_max: proto::DEFAULT_RESET_STREAM_MAX,
+            max_send_buffer_size: proto::DEFAULT_MAX_SEND_BUFFER_SIZE,
             initial_target_connection_window_size: None,
             initial_max_send_streams: usize::MAX,
             settings: Default::default(),
             stream_id: 1.into(),
+            keep_alive_interval: None,
+            keep_alive_timeout: Duration::from_secs(proto::DEFAULT_KEEP_ALIVE_TIMEOUT_SECS),
+            keep_alive_while_idle: false,
+            idle_timeout: None,
+            adaptive_window: false,
+            auto_release_capacity: false,
+            extension_frame_types: Vec::new(),
+            max_queued_extension_frames: proto::DEFAULT_MAX_QUEUED_EXTENSION_FRAMES,
+            max_continuation_frames: None,
+            max_header_block_size: None,
+            max_send_header_table_size: None,
+            max_control_frames_per_second: proto::DEFAULT_MAX_CONTROL_FRAMES_PER_SECOND,
+            max_empty_data_frames_per_second: proto::DEFAULT_MAX_EMPTY_DATA_FRAMES_PER_SECOND,
         }
     }
 
@@ -767,6 +878,231 @@ impl Builder {
         self
     }
 
+    /// Sets the header table size.
+    ///
+    /// This setting informs the peer of the maximum size of the header
+    /// compression table used to decode header blocks, in octets. A smaller
+    /// table uses less memory per connection, at the cost of less effective
+    /// header compression.
+    ///
+    /// The new size is enforced once the peer has acknowledged the setting
+    /// and shrunk its table with a dynamic table size update. Header blocks
+    /// that don't do so are treated as a connection error.
+    ///
+    /// The default value is 4,096.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::client::*;
+    /// # use bytes::Bytes;
+    /// #
+    /// # async fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Result<((SendRequest<Bytes>, Connection<T, Bytes>)), h2::Error>
+    /// # {
+    /// // `client_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let client_fut = Builder::new()
+    ///     .header_table_size(1024)
+    ///     .handshake(my_io);
+    /// # client_fut.await
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn header_table_size(&mut self, size: u32) -> &mut Self {
+        self.settings.set_header_table_size(Some(size));
+        self
+    }
+
+    /// Sets the maximum size of the header compression table used to encode
+    /// header blocks, in octets.
+    ///
+    /// The peer's `SETTINGS_HEADER_TABLE_SIZE` is the largest table it lets
+    /// this endpoint use. This setting caps the table below that, whatever
+    /// the peer advertises. Any change to the table size is signalled to the
+    /// peer with a dynamic table size update at the start of the next header
+    /// block.
+    ///
+    /// By default, the table may grow to the peer's header table size.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::client::*;
+    /// # use bytes::Bytes;
+    /// #
+    /// # async fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Result<((SendRequest<Bytes>, Connection<T, Bytes>)), h2::Error>
+    /// # {
+    /// // `client_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let client_fut = Builder::new()
+    ///     .max_send_header_table_size(0)
+    ///     .handshake(my_io);
+    /// # client_fut.await
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn max_send_header_table_size(&mut self, max: usize) -> &mut Self {
+        self.max_send_header_table_size = Some(max);
+        self
+    }
+
+    /// Sets the maximum number of CONTINUATION frames in a received header
+    /// block.
+    ///
+    /// A header block is sent as a HEADERS or PUSH_PROMISE frame followed by
+    /// any number of CONTINUATION frames. A peer sending endless tiny
+    /// CONTINUATION frames keeps the connection busy without ever completing
+    /// the block, so once a header block has more CONTINUATION frames than
+    /// this, the connection is closed with the `ENHANCE_YOUR_CALM` error code.
+    ///
+    /// By default, the limit allows enough frames to carry a header list of
+    /// the [`max_header_list_size`] in frames of the max frame size, plus
+    /// 25%%, and at least 5 frames.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::client::*;
+    /// # use bytes::Bytes;
+    /// #
+    /// # async fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Result<((SendRequest<Bytes>, Connection<T, Bytes>)), h2::Error>
+    /// # {
+    /// // `client_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let client_fut = Builder::new()
+    ///     .max_continuation_frames(16)
+    ///     .handshake(my_io);
+    /// # client_fut.await
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    ///
+    /// [`max_header_list_size`]: #method.max_header_list_size
+    pub fn max_continuation_frames(&mut self, max: usize) -> &mut Self {
+        self.max_continuation_frames = Some(max);
+        self
+    }
+
+    /// Sets the maximum number of encoded bytes in a received header block.
+    ///
+    /// The HPACK encoded header block is counted over the HEADERS or
+    /// PUSH_PROMISE frame and all of its CONTINUATION frames. Once a header
+    /// block is larger than this, the connection is closed with the
+    /// `ENHANCE_YOUR_CALM` error code.
+    ///
+    /// By default, header blocks are only limited by the number of
+    /// CONTINUATION frames they span; see [`max_continuation_frames`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::client::*;
+    /// # use bytes::Bytes;
+    /// #
+    /// # async fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Result<((SendRequest<Bytes>, Connection<T, Bytes>)), h2::Error>
+    /// # {
+    /// // `client_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let client_fut = Builder::new()
+    ///     .max_header_block_size(64 * 1024)
+    ///     .handshake(my_io);
+    /// # client_fut.await
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    ///
+    /// [`max_continuation_frames`]: #method.max_continuation_frames
+    pub fn max_header_block_size(&mut self, max: usize) -> &mut Self {
+        self.max_header_block_size = Some(max);
+        self
+    }
+
+    /// Sets the maximum number of control frames of each type that the peer
+    /// may send per second.
+    ///
+    /// PING and SETTINGS frames must be acknowledged, and PRIORITY and
+    /// WINDOW_UPDATE frames update connection state, yet they cost the peer
+    /// almost nothing to send. This setting limits, separately for each of
+    /// these frame types, how many may be received in any one second.
+    /// Acknowledgements of frames sent by this endpoint are not counted.
+    ///
+    /// When the limit is exceeded, the connection is closed with a GOAWAY
+    /// frame with the `ENHANCE_YOUR_CALM` error code.
+    ///
+    /// The default value is 10,000.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::client::*;
+    /// # use bytes::Bytes;
+    /// #
+    /// # async fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Result<((SendRequest<Bytes>, Connection<T, Bytes>)), h2::Error>
+    /// # {
+    /// // `client_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let client_fut = Builder::new()
+    ///     .max_control_frames_per_second(1000)
+    ///     .handshake(my_io);
+    /// # client_fut.await
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn max_control_frames_per_second(&mut self, max: usize) -> &mut Self {
+        self.max_control_frames_per_second = max;
+        self
+    }
+
+    /// Sets the maximum number of empty DATA frames that the peer may send
+    /// per second.
+    ///
+    /// A DATA frame without any payload that doesn't end its stream has no
+    /// effect, but it still has to be processed. When more than this many
+    /// are received in any one second, the connection is closed with a
+    /// GOAWAY frame with the `ENHANCE_YOUR_CALM` error code.
+    ///
+    /// The default value is 100.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::client::*;
+    /// # use bytes::Bytes;
+    /// #
+    /// # async fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Result<((SendRequest<Bytes>, Connection<T, Bytes>)), h2::Error>
+    /// # {
+    /// // `client_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let client_fut = Builder::new()
+    ///     .max_empty_data_frames_per_second(10)
+    ///     .handshake(my_io);
+    /// # client_fut.await
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn max_empty_data_frames_per_second(&mut self, max: usize) -> &mut Self {
+        self.max_empty_data_frames_per_second = max;
+        self
+    }
+
     /// Sets the maximum number of concurrent streams.
     ///
     /// The maximum concurrent streams setting only controls the maximum number
@@ -902,6 +1238,49 @@ impl Builder {
         self
     }
 
+    /// Sets the maximum send buffer size per stream.
+    ///
+    /// Data passed to [`SendStream::send_data`] is buffered until the peer's
+    /// flow control window allows it to be written. Once a stream has
+    /// buffered this much data, [`SendStream::poll_capacity`] stops reporting
+    /// additional capacity, whatever the size of the window. Capacity is
+    /// reported again as the buffered data is written to the connection.
+    ///
+    /// The default value is currently 400KB, but may change.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `max` is larger than `u32::MAX`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::client::*;
+    /// # use bytes::Bytes;
+    /// #
+    /// # async fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Result<((SendRequest<Bytes>, Connection<T, Bytes>)), h2::Error>
+    /// # {
+    /// // `client_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let client_fut = Builder::new()
+    ///     .max_send_buffer_size(64 * 1024)
+    ///     .handshake(my_io);
+    /// # client_fut.await
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    ///
+    /// [`SendStream::send_data`]: ../struct.SendStream.html#method.send_data
+    /// [`SendStream::poll_capacity`]: ../struct.SendStream.html#method.poll_capacity
+    pub fn max_send_buffer_size(&mut self, max: usize) -> &mut Self {
+        assert!(max <= u32::MAX as usize);
+        self.max_send_buffer_size = max;
+        self
+    }
+
     /// Sets the duration to remember locally reset streams.
     ///
     /// When a stream is explicitly reset, the HTTP/2.0 specification requires
@@ -986,63 +1365,428 @@ impl Builder {
         self
     }
 
-    /// Sets the first stream ID to something other than 1.
-    #[cfg(feature = "unstable")]
-    pub fn initial_stream_id(&mut self, stream_id: u32) -> &mut Self {
-        self.stream_id = stream_id.into();
-        assert!(
-            self.stream_id.is_client_initiated(),
-            "stream id must be odd"
-        );
-        self
-    }
-
-    /// Creates a new configured HTTP/2.0 client backed by `io`.
-    ///
-    /// It is expected that `io` already be in an appropriate state to commence
-    /// the [HTTP/2.0 handshake]. The handshake is completed once both the connection
-    /// preface and the initial settings frame is sent by the client.
-    ///
-    /// The handshake future does not wait for the initial settings frame from the
-    /// server.
+    /// Sets an interval for HTTP/2.0 PING frames used to keep the connection
+    /// alive.
     ///
-    /// Returns a future which resolves to the [`Connection`] / [`SendRequest`]
-    /// tuple once the HTTP/2.0 handshake has been completed.
+    /// When the connection has not received a frame for `interval`, a PING is
+    /// sent to the remote. If the PING is not acknowledged within the
+    /// [`keep_alive_timeout`], the connection is closed and resolves with an
+    /// error for which [`Error::is_keep_alive_timeout`] returns `true`.
     ///
-    /// This function also allows the caller to configure the send payload data
-    /// type. See [Outbound data type] for more details.
+    /// Pass `None` to disable keep-alive PINGs, which is the default.
     ///
-    /// [HTTP/2.0 handshake]: http://httpwg.org/specs/rfc7540.html#ConnectionHeader
-    /// [`Connection`]: struct.Connection.html
-    /// [`SendRequest`]: struct.SendRequest.html
-    /// [Outbound data type]: ../index.html#outbound-data-type.
+    /// [`keep_alive_timeout`]: #method.keep_alive_timeout
+    /// [`Error::is_keep_alive_timeout`]: ../struct.Error.html#method.is_keep_alive_timeout
     ///
     /// # Examples
     ///
-    /// Basic usage:
-    ///
     /// ```
     /// # use tokio::io::{AsyncRead, AsyncWrite};
     /// # use h2::client::*;
+    /// # use std::time::Duration;
     /// # use bytes::Bytes;
     /// #
     /// # async fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
-    ///     -> Result<((SendRequest<Bytes>, Connection<T, Bytes>)), h2::Error>
+    /// # -> Result<((SendRequest<Bytes>, Connection<T, Bytes>)), h2::Error>
     /// # {
     /// // `client_fut` is a future representing the completion of the HTTP/2.0
     /// // handshake.
     /// let client_fut = Builder::new()
+    ///     .keep_alive_interval(Duration::from_secs(30))
     ///     .handshake(my_io);
     /// # client_fut.await
     /// # }
     /// #
     /// # pub fn main() {}
     /// ```
+    pub fn keep_alive_interval<D: Into<Option<Duration>>>(&mut self, interval: D) -> &mut Self {
+        self.keep_alive_interval = interval.into();
+        self
+    }
+
+    /// Sets how long to wait for the acknowledgement of a keep-alive PING
+    /// before closing the connection.
     ///
-    /// Configures the send-payload data type. In this case, the outbound data
-    /// type will be `&'static [u8]`.
+    /// This has no effect unless [`keep_alive_interval`] is set.
     ///
-    /// ```
+    /// The default value is 20 seconds.
+    ///
+    /// [`keep_alive_interval`]: #method.keep_alive_interval
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::client::*;
+    /// # use std::time::Duration;
+    /// # use bytes::Bytes;
+    /// #
+    /// # async fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Result<((SendRequest<Bytes>, Connection<T, Bytes>)), h2::Error>
+    /// # {
+    /// // `client_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let client_fut = Builder::new()
+    ///     .keep_alive_interval(Duration::from_secs(30))
+    ///     .keep_alive_timeout(Duration::from_secs(10))
+    ///     .handshake(my_io);
+    /// # client_fut.await
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn keep_alive_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.keep_alive_timeout = timeout;
+        self
+    }
+
+    /// Sets whether keep-alive PINGs are sent while the connection has no
+    /// active streams.
+    ///
+    /// When disabled, keep-alive PINGs are only sent while there are open
+    /// streams on the connection.
+    ///
+    /// This has no effect unless [`keep_alive_interval`] is set.
+    ///
+    /// The default value is `false`.
+    ///
+    /// [`keep_alive_interval`]: #method.keep_alive_interval
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::client::*;
+    /// # use std::time::Duration;
+    /// # use bytes::Bytes;
+    /// #
+    /// # async fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Result<((SendRequest<Bytes>, Connection<T, Bytes>)), h2::Error>
+    /// # {
+    /// // `client_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let client_fut = Builder::new()
+    ///     .keep_alive_interval(Duration::from_secs(30))
+    ///     .keep_alive_while_idle(true)
+    ///     .handshake(my_io);
+    /// # client_fut.await
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn keep_alive_while_idle(&mut self, enabled: bool) -> &mut Self {
+        self.keep_alive_while_idle = enabled;
+        self
+    }
+
+    /// Sets how long the connection may go without any active streams before
+    /// it is closed.
+    ///
+    /// The timer starts whenever the connection has no open streams, which
+    /// includes right after the handshake, and is cancelled when a stream is
+    /// opened. When it expires, a GOAWAY frame with the `NO_ERROR` code is
+    /// sent and the connection is closed cleanly.
+    ///
+    /// Pass `None` to keep idle connections open, which is the default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::client::*;
+    /// # use std::time::Duration;
+    /// # use bytes::Bytes;
+    /// #
+    /// # async fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Result<((SendRequest<Bytes>, Connection<T, Bytes>)), h2::Error>
+    /// # {
+    /// // `client_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let client_fut = Builder::new()
+    ///     .idle_timeout(Duration::from_secs(90))
+    ///     .handshake(my_io);
+    /// # client_fut.await
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn idle_timeout<D: Into<Option<Duration>>>(&mut self, timeout: D) -> &mut Self {
+        self.idle_timeout = timeout.into();
+        self
+    }
+
+    /// Enables adaptive flow control.
+    ///
+    /// When enabled, the connection periodically sends PING frames while DATA
+    /// is being received in order to estimate the bandwidth-delay product of
+    /// the link. Whenever the peer comes close to filling the receive window
+    /// within a round trip, both the connection window and the initial stream
+    /// window are grown to match the estimate, up to 16MB.
+    ///
+    /// The windows configured with [`initial_window_size`] and
+    /// [`initial_connection_window_size`] are used as the starting point.
+    /// The estimate only ever raises a window: a connection window set with
+    /// `set_target_window_size` that is larger than the estimate is kept.
+    ///
+    /// The default value is `false`.
+    ///
+    /// [`initial_window_size`]: #method.initial_window_size
+    /// [`initial_connection_window_size`]: #method.initial_connection_window_size
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::client::*;
+    /// # use bytes::Bytes;
+    /// #
+    /// # async fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Result<((SendRequest<Bytes>, Connection<T, Bytes>)), h2::Error>
+    /// # {
+    /// // `client_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let client_fut = Builder::new()
+    ///     .adaptive_window(true)
+    ///     .handshake(my_io);
+    /// # client_fut.await
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn adaptive_window(&mut self, enabled: bool) -> &mut Self {
+        self.adaptive_window = enabled;
+        self
+    }
+
+    /// Sets whether received data releases its flow control capacity
+    /// automatically.
+    ///
+    /// By default, data returned by [`RecvStream::poll_data`] keeps counting
+    /// against the stream and connection receive windows until the caller
+    /// passes its size to [`FlowControl::release_capacity`]. If the caller
+    /// forgets to, the windows are never replenished, and the peer eventually
+    /// stops sending data on the whole connection.
+    ///
+    /// When enabled, the capacity of each chunk of data is released as soon
+    /// as it is returned by `poll_data`. The setting can be changed for a
+    /// single stream with [`RecvStream::set_auto_release_capacity`].
+    ///
+    /// The default value is `false`.
+    ///
+    /// [`RecvStream::poll_data`]: ../struct.RecvStream.html#method.poll_data
+    /// [`FlowControl::release_capacity`]: ../struct.FlowControl.html#method.release_capacity
+    /// [`RecvStream::set_auto_release_capacity`]: ../struct.RecvStream.html#method.set_auto_release_capacity
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::client::*;
+    /// # use bytes::Bytes;
+    /// #
+    /// # async fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Result<((SendRequest<Bytes>, Connection<T, Bytes>)), h2::Error>
+    /// # {
+    /// // `client_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let client_fut = Builder::new()
+    ///     .auto_release_capacity(true)
+    ///     .handshake(my_io);
+    /// # client_fut.await
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn auto_release_capacity(&mut self, enabled: bool) -> &mut Self {
+        self.auto_release_capacity = enabled;
+        self
+    }
+
+    /// Tells the server that this client does not use RFC 7540 priorities.
+    ///
+    /// This sends `SETTINGS_NO_RFC7540_PRIORITIES` so that the server knows
+    /// the priority of requests is only signalled using [Extensible
+    /// Priorities]: the `priority` header and PRIORITY_UPDATE frames, which
+    /// are sent by [`SendStream::set_priority`].
+    ///
+    /// [Extensible Priorities]: https://www.rfc-editor.org/rfc/rfc9218.html
+    /// [`SendStream::set_priority`]: ../struct.SendStream.html#method.set_priority
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::client::*;
+    /// # use bytes::Bytes;
+    /// #
+    /// # async fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Result<((SendRequest<Bytes>, Connection<T, Bytes>)), h2::Error>
+    /// # {
+    /// // `client_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let client_fut = Builder::new()
+    ///     .disable_rfc7540_priorities()
+    ///     .handshake(my_io);
+    /// # client_fut.await
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn disable_rfc7540_priorities(&mut self) -> &mut Self {
+        self.settings.set_no_rfc7540_priorities(Some(1));
+        self
+    }
+
+    /// Registers an extension frame type to receive.
+    ///
+    /// Frames of this type sent by the peer are handed to the
+    /// [`ExtensionFrames`] handle of the connection. Frames of unknown types
+    /// that were not registered are ignored, as required by RFC 7540.
+    ///
+    /// Extension frames may be sent without registering their type.
+    ///
+    /// [`ExtensionFrames`]: ../ext/struct.ExtensionFrames.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frame_type` is the type of a frame that is implemented by
+    /// this library.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::client::*;
+    /// # use bytes::Bytes;
+    /// #
+    /// # async fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Result<((SendRequest<Bytes>, Connection<T, Bytes>)), h2::Error>
+    /// # {
+    /// // `client_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let client_fut = Builder::new()
+    ///     .extension_frame_type(0xb0)
+    ///     .handshake(my_io);
+    /// # client_fut.await
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn extension_frame_type(&mut self, frame_type: u8) -> &mut Self {
+        crate::ext::assert_extension_type(frame_type);
+        if !self.extension_frame_types.contains(&frame_type) {
+            self.extension_frame_types.push(frame_type);
+        }
+        self
+    }
+
+    /// Sets the maximum number of extension frames queued in each direction.
+    ///
+    /// Frames received from the peer wait in a queue until they are taken
+    /// with [`ExtensionFrames::recv_frame`]. If the peer sends more frames
+    /// than this while the queue is full, it is sending them faster than they
+    /// are handled, and the connection is closed with `ENHANCE_YOUR_CALM`.
+    ///
+    /// Frames queued with [`ExtensionFrames::send_frame`] wait until the
+    /// connection writes them. Once this many are waiting, `send_frame` fails
+    /// until [`ExtensionFrames::poll_ready`] returns `Ready` again.
+    ///
+    /// The default value is 64.
+    ///
+    /// [`ExtensionFrames::recv_frame`]: ../ext/struct.ExtensionFrames.html#method.recv_frame
+    /// [`ExtensionFrames::send_frame`]: ../ext/struct.ExtensionFrames.html#method.send_frame
+    /// [`ExtensionFrames::poll_ready`]: ../ext/struct.ExtensionFrames.html#method.poll_ready
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::client::*;
+    /// # use bytes::Bytes;
+    /// #
+    /// # async fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Result<((SendRequest<Bytes>, Connection<T, Bytes>)), h2::Error>
+    /// # {
+    /// // `client_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let client_fut = Builder::new()
+    ///     .max_queued_extension_frames(16)
+    ///     .handshake(my_io);
+    /// # client_fut.await
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn max_queued_extension_frames(&mut self, max: usize) -> &mut Self {
+        assert!(
+            max > 0,
+            "max_queued_extension_frames must be greater than 0"
+        );
+        self.max_queued_extension_frames = max;
+        self
+    }
+
+    /// Sets the first stream ID to something other than 1.
+    #[cfg(feature = "unstable")]
+    pub fn initial_stream_id(&mut self, stream_id: u32) -> &mut Self {
+        self.stream_id = stream_id.into();
+        assert!(
+            self.stream_id.is_client_initiated(),
+            "stream id must be odd"
+        );
+        self
+    }
+
+    /// Creates a new configured HTTP/2.0 client backed by `io`.
+    ///
+    /// It is expected that `io` already be in an appropriate state to commence
+    /// the [HTTP/2.0 handshake]. The handshake is completed once both the connection
+    /// preface and the initial settings frame is sent by the client.
+    ///
+    /// The handshake future does not wait for the initial settings frame from the
+    /// server.
+    ///
+    /// Returns a future which resolves to the [`Connection`] / [`SendRequest`]
+    /// tuple once the HTTP/2.0 handshake has been completed.
+    ///
+    /// This function also allows the caller to configure the send payload data
+    /// type. See [Outbound data type] for more details.
+    ///
+    /// [HTTP/2.0 handshake]: http://httpwg.org/specs/rfc7540.html#ConnectionHeader
+    /// [`Connection`]: struct.Connection.html
+    /// [`SendRequest`]: struct.SendRequest.html
+    /// [Outbound data type]: ../index.html#outbound-data-type.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::client::*;
+    /// # use bytes::Bytes;
+    /// #
+    /// # async fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    ///     -> Result<((SendRequest<Bytes>, Connection<T, Bytes>)), h2::Error>
+    /// # {
+    /// // `client_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let client_fut = Builder::new()
+    ///     .handshake(my_io);
+    /// # client_fut.await
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    ///
+    /// Configures the send-payload data type. In this case, the outbound data
+    /// type will be `&'static [u8]`.
+    ///
+    /// ```
     /// # use tokio::io::{AsyncRead, AsyncWrite};
     /// # use h2::client::*;
     /// #
@@ -1068,6 +1812,100 @@ impl Builder {
     {
         Connection::handshake2(io, self.clone())
     }
+
+    /// Returns the value of the `HTTP2-Settings` header to send with an
+    /// HTTP/1.1 `Upgrade: h2c` request.
+    ///
+    /// The value encodes the settings configured on this builder, which are
+    /// sent again in the connection preface after the upgrade. See
+    /// [`handshake_upgrade`] for details.
+    ///
+    /// [`handshake_upgrade`]: #method.handshake_upgrade
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use h2::client::*;
+    /// let http2_settings = Builder::new()
+    ///     .initial_window_size(1_000_000)
+    ///     .http2_settings();
+    /// assert_eq!(http2_settings, "AAQAD0JA");
+    /// ```
+    pub fn http2_settings(&self) -> HeaderValue {
+        HeaderValue::from_str(&self.settings.encode_http2_settings())
+            .expect("base64url is a valid header value")
+    }
+
+    /// Creates a new configured HTTP/2.0 client backed by `io`, continuing an
+    /// HTTP/1.1 connection that was [upgraded][1] with `Upgrade: h2c`.
+    ///
+    /// The caller is responsible for the HTTP/1.1 part of the upgrade: the
+    /// request must have been sent with `Connection: Upgrade, HTTP2-Settings`,
+    /// `Upgrade: h2c` and an `HTTP2-Settings` header with the value returned
+    /// by [`http2_settings`], and the server must have answered with `101
+    /// Switching Protocols`. No bytes sent by the server after that response
+    /// may have been read from `io`.
+    ///
+    /// The upgrade request is stream 1, which is half-closed (local). The
+    /// returned [`ResponseFuture`] resolves to the server's response on that
+    /// stream. `request` is only used to know how to interpret the response,
+    /// e.g. if it is the response to a `HEAD` request.
+    ///
+    /// Stream 1 counts against [`initial_max_send_streams`], so the handshake
+    /// fails if that is set to 0.
+    ///
+    /// [1]: https://httpwg.org/specs/rfc7540.html#discover-http
+    /// [`initial_max_send_streams`]: #method.initial_max_send_streams
+    /// [`http2_settings`]: #method.http2_settings
+    /// [`ResponseFuture`]: struct.ResponseFuture.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::client::*;
+    /// # use bytes::Bytes;
+    /// # use http::Request;
+    /// #
+    /// # async fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T, upgrade_request: Request<()>)
+    /// # -> Result<(), h2::Error>
+    /// # {
+    /// let (send_request, connection, response) = Builder::new()
+    ///     .handshake_upgrade::<_, Bytes>(my_io, &upgrade_request)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub async fn handshake_upgrade<T, B>(
+        &self,
+        io: T,
+        request: &Request<()>,
+    ) -> Result<(SendRequest<B>, Connection<T, B>, ResponseFuture), crate::Error>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+        B: Buf + 'static,
+    {
+        let mut builder = self.clone();
+        // The upgrade request is always stream 1, so new requests start at 3.
+        builder.stream_id = 1.into();
+
+        let (send_request, connection) = Connection::handshake2(io, builder).await?;
+
+        let inner = connection
+            .inner
+            .streams()
+            .clone()
+            .send_upgrade_request(request.method())?;
+
+        let response = ResponseFuture {
+            inner,
+            push_promise_consumed: false,
+        };
+
+        Ok((send_request, connection, response))
+    }
 }
 
 impl Default for Builder {
@@ -1160,6 +1998,18 @@ where
             codec.set_max_recv_header_list_size(max as usize);
         }
 
+        if let Some(max) = builder.max_continuation_frames {
+            codec.set_max_recv_continuation_frames(max);
+        }
+
+        if let Some(max) = builder.max_header_block_size {
+            codec.set_max_recv_header_block_size(max);
+        }
+
+        if let Some(max) = builder.max_send_header_table_size {
+            codec.set_max_send_header_table_size(max);
+        }
+
         // Send initial settings frame
         codec
             .buffer(builder.settings.clone().into())
@@ -1172,7 +2022,19 @@ where
                 initial_max_send_streams: builder.initial_max_send_streams,
                 reset_stream_duration: builder.reset_stream_duration,
                 reset_stream_max: builder.reset_stream_max,
+                max_send_buffer_size: builder.max_send_buffer_size,
+                remote_reset_stream_max: proto::DEFAULT_REMOTE_RESET_STREAM_MAX,
                 settings: builder.settings.clone(),
+                keep_alive_interval: builder.keep_alive_interval,
+                keep_alive_timeout: builder.keep_alive_timeout,
+                keep_alive_while_idle: builder.keep_alive_while_idle,
+                idle_timeout: builder.idle_timeout,
+                adaptive_window: builder.adaptive_window,
+                auto_release_capacity: builder.auto_release_capacity,
+                extension_frame_types: builder.extension_frame_types.clone(),
+                max_queued_extension_frames: builder.max_queued_extension_frames,
+                max_control_frames_per_second: builder.max_control_frames_per_second,
+                max_empty_data_frames_per_second: builder.max_empty_data_frames_per_second,
             },
         );
         let send_request = SendRequest {
@@ -1229,6 +2091,34 @@ where
         Ok(())
     }
 
+    /// Sets the connection to a GOAWAY state.
+    ///
+    /// Does not terminate the connection. Must continue being polled to close
+    /// connection.
+    ///
+    /// After flushing the GOAWAY frame, the connection is closed. Any
+    /// outstanding requests do not prevent the connection from closing, and
+    /// are failed with `reason`.
+    ///
+    /// For graceful shutdowns, see [`graceful_shutdown`](Connection::graceful_shutdown).
+    pub fn abrupt_shutdown(&mut self, reason: Reason) {
+        self.inner.go_away_from_user(reason, Bytes::new());
+    }
+
+    /// Starts a [graceful shutdown][1] process.
+    ///
+    /// Must continue being polled to close connection.
+    ///
+    /// A GOAWAY frame is sent to the server, after which no more pushed
+    /// streams are accepted. Requests that are already in flight are allowed
+    /// to complete, while sending new requests fails. Once all active streams
+    /// have completed, the connection is closed.
+    ///
+    /// [1]: http://httpwg.org/specs/rfc7540.html#GOAWAY
+    pub fn graceful_shutdown(&mut self) {
+        self.inner.go_away_gracefully();
+    }
+
     /// Takes a `PingPong` instance from the connection.
     ///
     /// # Note
@@ -1238,6 +2128,53 @@ where
         self.inner.take_user_pings().map(PingPong::new)
     }
 
+    /// Returns a snapshot of the connection's statistics.
+    ///
+    /// This includes the number of bytes and frames sent and received, the
+    /// number of streams opened, closed and reset by each side, the current
+    /// connection-level flow control windows, and the size of the HPACK
+    /// dynamic tables.
+    pub fn stats(&self) -> ConnectionStats {
+        self.inner.stats()
+    }
+
+    /// Returns the origins received from the server in ORIGIN frames.
+    ///
+    /// See [`SendRequest::origin_set`] for details.
+    ///
+    /// [`SendRequest::origin_set`]: struct.SendRequest.html#method.origin_set
+    pub fn origin_set(&self) -> Option<Vec<Origin>> {
+        self.inner.streams().origin_set()
+    }
+
+    /// Takes an `ExtensionFrames` handle from the connection, used to send
+    /// extension frames and receive those of the registered types.
+    ///
+    /// # Note
+    ///
+    /// This may only be called once. Calling multiple times will return `None`.
+    pub fn extension_frames(&mut self) -> Option<ExtensionFrames> {
+        self.inner.take_user_extensions().map(ExtensionFrames::new)
+    }
+
+    /// Takes an `AltSvcs` stream from the connection, yielding the alternative
+    /// services advertised by the server in [ALTSVC frames].
+    ///
+    /// Advertisements are only kept once this has been called. At most 64
+    /// advertisements wait to be taken from the stream; any more that arrive
+    /// in the meantime are dropped, as they are only hints.
+    ///
+    /// # Note
+    ///
+    /// This may only be called once. Calling multiple times will return `None`.
+    ///
+    /// [ALTSVC frames]: https://www.rfc-editor.org/rfc/rfc7838.html#section-4
+    pub fn alt_svc(&mut self) -> Option<AltSvcs> {
+        self.inner
+            .take_user_alt_svc()
+            .map(|inner| AltSvcs { inner })
+    }
+
     /// Returns the maximum number of concurrent streams that may be initiated
     /// by this client.
     ///
@@ -1369,6 +2306,96 @@ impl futures_core::Stream for PushPromises {
     }
 }
 
+// ===== impl AltSvcs =====
+
+impl AltSvcs {
+    /// Get the next alternative service advertisement.
+    ///
+    /// Returns `None` once the connection is gone.
+    pub async fn alt_svc(&mut self) -> Option<AltSvc> {
+        futures_util::future::poll_fn(move |cx| self.poll_alt_svc(cx)).await
+    }
+
+    #[doc(hidden)]
+    pub fn poll_alt_svc(&mut self, cx: &mut Context<'_>) -> Poll<Option<AltSvc>> {
+        loop {
+            match ready!(self.inner.poll_recv(cx)) {
+                Some(frame) => {
+                    if let Some(alt_svc) = AltSvc::from_frame(frame) {
+                        return Poll::Ready(Some(alt_svc));
+                    }
+                }
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "stream")]
+impl futures_core::Stream for AltSvcs {
+    type Item = AltSvc;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_alt_svc(cx)
+    }
+}
+
+// ===== impl AltSvc =====
+
+impl AltSvc {
+    /// Converts a received frame, returning `None` if it must be ignored.
+    fn from_frame(frame: frame::AltSvc) -> Option<Self> {
+        let id = frame.stream_id();
+        let field_value = match http::HeaderValue::from_maybe_shared(frame.field_value().clone()) {
+            Ok(field_value) => field_value,
+            Err(_) => {
+                tracing::debug!("ignoring ALTSVC frame with an invalid field value");
+                return None;
+            }
+        };
+
+        // A frame on stream 0 names the origin it is for, while a frame on
+        // any other stream is for the origin of that stream's request.
+        let (origin, stream_id) = if id.is_zero() {
+            match Origin::from_bytes(frame.origin().clone()) {
+                Some(origin) => (Some(origin), None),
+                None => {
+                    tracing::debug!("ignoring ALTSVC frame with an invalid origin");
+                    return None;
+                }
+            }
+        } else if frame.origin().is_empty() {
+            (None, Some(crate::StreamId::from_internal(id)))
+        } else {
+            tracing::debug!("ignoring stream-level ALTSVC frame with an origin");
+            return None;
+        };
+
+        Some(AltSvc {
+            origin,
+            stream_id,
+            field_value,
+        })
+    }
+
+    /// Returns the origin the advertisement is for, if it was received on
+    /// the connection.
+    pub fn origin(&self) -> Option<&Origin> {
+        self.origin.as_ref()
+    }
+
+    /// Returns the stream the advertisement was received on, if it is for
+    /// the origin of that stream's request.
+    pub fn stream_id(&self) -> Option<crate::StreamId> {
+        self.stream_id.clone()
+    }
+
+    /// Returns the `Alt-Svc` field value, listing the alternative services.
+    pub fn field_value(&self) -> &http::HeaderValue {
+        &self.field_value
+    }
+}
+
 // ===== impl PushPromise =====
 
 impl PushPromise {
@@ -1426,16 +2453,18 @@ impl Peer {
                 uri,
                 headers,
                 version,
+                mut extensions,
                 ..
             },
             _,
         ) = request.into_parts();
 
         let is_connect = method == Method::CONNECT;
+        let protocol = extensions.remove::<Protocol>();
 
         // Build the set pseudo header set. All requests will include `method`
         // and `path`.
-        let mut pseudo = Pseudo::request(method, uri);
+        let mut pseudo = Pseudo::request(method, uri, protocol);
 
         if pseudo.scheme.is_none() {
             // If the scheme is not set, then there are a two options.
@@ -0,0 +1,541 @@
+use super::{Builder, Connection, ResponseFuture, SendRequest};
+use crate::codec::UserError;
+use crate::SendStream;
+
+use bytes::{Buf, Bytes};
+use http::Request;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::{fmt, io};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+type Handshaking<T, B> =
+    Pin<Box<dyn Future<Output = Result<(SendRequest<B>, Connection<T, B>), crate::Error>> + Send>>;
+
+/// A pool of HTTP/2.0 connections to a single authority.
+///
+/// A single connection can only have as many requests in flight as the
+/// server's `SETTINGS_MAX_CONCURRENT_STREAMS` allows. A `Pool` spreads
+/// requests over several connections instead: each request is sent on the
+/// least loaded connection, and once all connections are saturated, a new
+/// one is opened.
+///
+/// Connections stop being used for new requests once they can't open new
+/// streams anymore, such as after receiving a GOAWAY frame or running out of
+/// stream IDs. They are shut down once their active streams complete.
+///
+/// `Pool` values are created by calling [`Builder::pool`], which also returns
+/// the [`PoolConnection`] future that drives the connections. `Pool` handles
+/// can be cloned to send requests from several tasks.
+///
+/// [`Builder::pool`]: struct.Builder.html#method.pool
+/// [`PoolConnection`]: struct.PoolConnection.html
+pub struct Pool<B: Buf = Bytes> {
+    shared: Arc<Mutex<Shared<B>>>,
+
+    /// The connection picked by `poll_ready` for the next request.
+    reserved: Option<Reservation<B>>,
+}
+
+/// Drives the connections of a [`Pool`].
+///
+/// This future must be polled for the requests sent through the [`Pool`] to
+/// make progress, usually by spawning it on an executor. It opens new
+/// connections through the connector given to [`Builder::pool`] when needed.
+///
+/// It completes once all [`Pool`] handles have been dropped and all the
+/// connections have been closed.
+///
+/// [`Pool`]: struct.Pool.html
+/// [`Builder::pool`]: struct.Builder.html#method.pool
+#[must_use = "futures do nothing unless polled"]
+pub struct PoolConnection<T, B: Buf, C> {
+    shared: Arc<Mutex<Shared<B>>>,
+
+    /// Configures new connections.
+    builder: Builder,
+
+    /// Opens the I/O for new connections.
+    connect: C,
+
+    /// A connection being opened, if any.
+    connecting: Option<Handshaking<T, B>>,
+
+    /// All open connections, including the retired ones.
+    connections: Vec<(usize, Connection<T, B>)>,
+}
+
+struct Shared<B: Buf> {
+    /// Connections that new requests may be sent on.
+    entries: Vec<Entry<B>>,
+
+    /// Identifies the next connection.
+    next_id: usize,
+
+    /// Set when all connections are saturated and a new one is needed.
+    want_connection: bool,
+
+    /// The error of the last failed attempt to open a connection.
+    connect_error: Option<crate::Error>,
+
+    /// Number of `Pool` handles.
+    num_handles: usize,
+
+    /// Set once the `PoolConnection` is dropped.
+    is_closed: bool,
+
+    /// Task driving the connections.
+    driver: Option<Waker>,
+
+    /// Tasks waiting for a connection with capacity.
+    waiters: Vec<Waker>,
+}
+
+struct Entry<B: Buf> {
+    id: usize,
+    send_request: SendRequest<B>,
+
+    /// Number of `Pool` handles that picked this connection for their next
+    /// request.
+    num_reserved: usize,
+}
+
+struct Reservation<B: Buf> {
+    /// Identifies the connection.
+    id: usize,
+
+    send_request: SendRequest<B>,
+}
+
+impl Builder {
+    /// Creates a pool of connections to a single authority, configured by
+    /// this builder.
+    ///
+    /// `connect` is called whenever the pool needs a new connection, and
+    /// returns a future resolving to the I/O to perform the HTTP/2.0
+    /// handshake on. No connection is opened until the first request.
+    ///
+    /// Returns the [`Pool`] used to send requests, and the
+    /// [`PoolConnection`] future that must be polled to drive the
+    /// connections.
+    ///
+    /// [`Pool`]: struct.Pool.html
+    /// [`PoolConnection`]: struct.PoolConnection.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use h2::client::*;
+    /// # use http::Request;
+    /// # use bytes::Bytes;
+    /// # use tokio::net::TcpStream;
+    /// #
+    /// # async fn doc() -> Result<(), h2::Error> {
+    /// let (mut pool, connection) = Builder::new()
+    ///     .pool::<_, _, _, Bytes>(|| TcpStream::connect("127.0.0.1:5928"));
+    ///
+    /// // Drive the pooled connections in the background.
+    /// tokio::spawn(connection);
+    ///
+    /// pool.ready().await?;
+    /// let request = Request::get("http://127.0.0.1:5928/").body(()).unwrap();
+    /// let (response, _) = pool.send_request(request, true)?;
+    /// let response = response.await?;
+    /// # Ok(())
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn pool<C, F, T, B>(&self, connect: C) -> (Pool<B>, PoolConnection<T, B, C>)
+    where
+        C: FnMut() -> F + Unpin,
+        F: Future<Output = io::Result<T>> + Send + 'static,
+        T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        B: Buf + Send + 'static,
+    {
+        let shared = Arc::new(Mutex::new(Shared {
+            entries: Vec::new(),
+            next_id: 0,
+            want_connection: false,
+            connect_error: None,
+            num_handles: 1,
+            is_closed: false,
+            driver: None,
+            waiters: Vec::new(),
+        }));
+
+        let pool = Pool {
+            shared: shared.clone(),
+            reserved: None,
+        };
+
+        let connection = PoolConnection {
+            shared,
+            builder: self.clone(),
+            connect,
+            connecting: None,
+            connections: Vec::new(),
+        };
+
+        (pool, connection)
+    }
+}
+
+// ===== impl Pool =====
+
+impl<B> Pool<B>
+where
+    B: Buf + 'static,
+{
+    /// Returns `Ready` when a connection of the pool can initialize a new
+    /// HTTP/2.0 stream.
+    ///
+    /// The least loaded connection is picked and reserved for the next call
+    /// to `send_request`, so requests sent from several handles don't pile up
+    /// on the same connection. If all connections are saturated, a new
+    /// connection is opened, and `Ready` is returned once it is established.
+    /// If opening it fails, the error is returned.
+    ///
+    /// This function must return `Ready` before `send_request` is called.
+    pub fn poll_ready(&mut self, cx: &mut Context) -> Poll<Result<(), crate::Error>> {
+        loop {
+            if let Some(ref mut reserved) = self.reserved {
+                match reserved.send_request.poll_ready(cx) {
+                    Poll::Ready(Ok(())) => return Poll::Ready(Ok(())),
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(Err(e)) => {
+                        // The connection can't open new streams, so it is
+                        // never picked again.
+                        tracing::debug!(id = reserved.id, error = ?e, "reserved connection failed");
+                        self.release();
+                    }
+                }
+            }
+
+            ready!(self.poll_reserve(cx))?;
+        }
+    }
+
+    /// Reserves the least loaded connection, opening a new one if all
+    /// connections are saturated.
+    fn poll_reserve(&mut self, cx: &mut Context) -> Poll<Result<(), crate::Error>> {
+        let mut shared = self.shared.lock().unwrap();
+
+        if let Some(entry) = shared.least_loaded() {
+            entry.num_reserved += 1;
+            self.reserved = Some(Reservation {
+                id: entry.id,
+                send_request: entry.send_request.clone(),
+            });
+            return Poll::Ready(Ok(()));
+        }
+
+        if let Some(err) = shared.connect_error.take() {
+            return Poll::Ready(Err(err));
+        }
+
+        if shared.is_closed {
+            return Poll::Ready(Err(crate::Error::from_io(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "connection pool closed",
+            ))));
+        }
+
+        if !shared.waiters.iter().any(|w| w.will_wake(cx.waker())) {
+            shared.waiters.push(cx.waker().clone());
+        }
+
+        shared.want_connection = true;
+        shared.wake_driver();
+
+        Poll::Pending
+    }
+
+    /// Waits until a connection of the pool can initialize a new HTTP/2.0
+    /// stream.
+    ///
+    /// See [`poll_ready`](Pool::poll_ready) for details.
+    pub async fn ready(&mut self) -> Result<(), crate::Error> {
+        futures_util::future::poll_fn(move |cx| self.poll_ready(cx)).await
+    }
+
+    /// Sends a HTTP/2.0 request to the server, on the connection reserved by
+    /// [`poll_ready`].
+    ///
+    /// This works like [`SendRequest::send_request`]. An error is returned if
+    /// no connection is reserved, so [`poll_ready`] must return `Ready`
+    /// before each request.
+    ///
+    /// [`SendRequest::send_request`]: struct.SendRequest.html#method.send_request
+    /// [`poll_ready`]: #method.poll_ready
+    pub fn send_request(
+        &mut self,
+        request: Request<()>,
+        end_of_stream: bool,
+    ) -> Result<(ResponseFuture, SendStream<B>), crate::Error> {
+        let mut reserved = match self.reserved.take() {
+            Some(reserved) => reserved,
+            None => return Err(UserError::Rejected.into()),
+        };
+
+        // Hold the lock while the stream is created, so the connection's load
+        // never looks lower than it is.
+        let mut shared = self.shared.lock().unwrap();
+        shared.unreserve(reserved.id);
+
+        reserved.send_request.send_request(request, end_of_stream)
+    }
+
+    /// Returns the number of connections that new requests may be sent on.
+    pub fn num_connections(&self) -> usize {
+        self.shared.lock().unwrap().entries.len()
+    }
+}
+
+impl<B: Buf> Pool<B> {
+    /// Gives up the reserved connection, if any.
+    fn release(&mut self) {
+        if let Some(reserved) = self.reserved.take() {
+            let mut shared = self.shared.lock().unwrap();
+            shared.unreserve(reserved.id);
+            shared.wake_waiters();
+        }
+    }
+}
+
+impl<B: Buf> Clone for Pool<B> {
+    fn clone(&self) -> Self {
+        self.shared.lock().unwrap().num_handles += 1;
+
+        Pool {
+            shared: self.shared.clone(),
+            reserved: None,
+        }
+    }
+}
+
+impl<B: Buf> Drop for Pool<B> {
+    fn drop(&mut self) {
+        self.release();
+
+        let mut shared = self.shared.lock().unwrap();
+        shared.num_handles -= 1;
+
+        if shared.num_handles == 0 {
+            shared.wake_driver();
+        }
+    }
+}
+
+impl<B: Buf> fmt::Debug for Pool<B> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Pool")
+            .field(
+                "num_connections",
+                &self.shared.lock().unwrap().entries.len(),
+            )
+            .finish()
+    }
+}
+
+// ===== impl PoolConnection =====
+
+impl<T, B, C, F> Future for PoolConnection<T, B, C>
+where
+    C: FnMut() -> F + Unpin,
+    F: Future<Output = io::Result<T>> + Send + 'static,
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    B: Buf + Send + 'static,
+{
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+
+        loop {
+            this.maybe_connect(cx);
+
+            if let Some(connecting) = this.connecting.as_mut() {
+                if let Poll::Ready(res) = connecting.as_mut().poll(cx) {
+                    this.connecting = None;
+
+                    let mut shared = this.shared.lock().unwrap();
+                    match res {
+                        Ok((send_request, connection)) => {
+                            let id = shared.next_id;
+                            shared.next_id += 1;
+                            tracing::debug!(id, "pooled connection established");
+
+                            shared.entries.push(Entry {
+                                id,
+                                send_request,
+                                num_reserved: 0,
+                            });
+                            shared.connect_error = None;
+                            this.connections.push((id, connection));
+                        }
+                        Err(e) => {
+                            tracing::debug!(error = ?e, "failed to open pooled connection");
+                            shared.connect_error = Some(e);
+                        }
+                    }
+                    shared.wake_waiters();
+                }
+            }
+
+            // Drive the connections, forgetting those that have closed.
+            let mut i = 0;
+            while i < this.connections.len() {
+                let (id, ref mut connection) = this.connections[i];
+                match Pin::new(connection).poll(cx) {
+                    Poll::Pending => i += 1,
+                    Poll::Ready(res) => {
+                        if let Err(e) = res {
+                            tracing::debug!(id, error = ?e, "pooled connection error");
+                        }
+                        tracing::debug!(id, "pooled connection closed");
+                        let _ = this.connections.swap_remove(i);
+                        let mut shared = this.shared.lock().unwrap();
+                        shared.entries.retain(|entry| entry.id != id);
+                    }
+                }
+            }
+
+            let mut shared = this.shared.lock().unwrap();
+            let mut is_progress = false;
+
+            // Stop sending requests on connections that can't open new
+            // streams, and close them once their active streams complete.
+            for (id, connection) in this.connections.iter_mut() {
+                let pos = shared.entries.iter().position(|entry| entry.id == *id);
+                if let Some(pos) = pos {
+                    if !shared.entries[pos].send_request.inner.can_open_streams() {
+                        tracing::debug!(id, "retiring pooled connection");
+                        shared.entries.swap_remove(pos);
+                        connection.graceful_shutdown();
+                        is_progress = true;
+                    }
+                }
+            }
+
+            if shared.least_loaded().is_some() {
+                shared.wake_waiters();
+            } else if !shared.waiters.is_empty() && this.connecting.is_none() {
+                shared.want_connection = true;
+                is_progress = true;
+            }
+
+            if shared.num_handles == 0 && this.connections.is_empty() && this.connecting.is_none() {
+                return Poll::Ready(());
+            }
+
+            if !is_progress {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+impl<T, B, C, F> PoolConnection<T, B, C>
+where
+    C: FnMut() -> F + Unpin,
+    F: Future<Output = io::Result<T>> + Send + 'static,
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    B: Buf + Send + 'static,
+{
+    /// Starts opening a new connection, if one is wanted.
+    fn maybe_connect(&mut self, cx: &Context) {
+        let mut shared = self.shared.lock().unwrap();
+
+        if !shared.driver.iter().any(|w| w.will_wake(cx.waker())) {
+            shared.driver = Some(cx.waker().clone());
+        }
+
+        if shared.num_handles == 0 {
+            // No more requests can be sent, so the connections close once
+            // their active streams complete.
+            shared.entries.clear();
+            shared.want_connection = false;
+            return;
+        }
+
+        if !shared.want_connection || self.connecting.is_some() {
+            return;
+        }
+
+        tracing::debug!("pooled connections are saturated; opening a new one");
+        shared.want_connection = false;
+
+        let connect = (self.connect)();
+        let builder = self.builder.clone();
+        self.connecting = Some(Box::pin(async move {
+            let io = connect.await.map_err(crate::Error::from_io)?;
+            builder.handshake(io).await
+        }));
+    }
+}
+
+impl<T, B: Buf, C> Drop for PoolConnection<T, B, C> {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.is_closed = true;
+        shared.entries.clear();
+        shared.wake_waiters();
+    }
+}
+
+impl<T, B: Buf, C> fmt::Debug for PoolConnection<T, B, C> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("PoolConnection")
+            .field("connections", &self.connections.len())
+            .field("connecting", &self.connecting.is_some())
+            .finish()
+    }
+}
+
+// ===== impl Shared =====
+
+impl<B: Buf> Shared<B> {
+    /// Returns the least loaded connection among those that can open a new
+    /// stream.
+    fn least_loaded(&mut self) -> Option<&mut Entry<B>> {
+        self.entries
+            .iter_mut()
+            .filter(|entry| entry.has_capacity())
+            .min_by_key(|entry| entry.load())
+    }
+
+    fn unreserve(&mut self, id: usize) {
+        // The connection is gone if it was retired or closed meanwhile.
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.id == id) {
+            entry.num_reserved -= 1;
+        }
+    }
+
+    fn wake_driver(&mut self) {
+        if let Some(driver) = self.driver.take() {
+            driver.wake();
+        }
+    }
+
+    fn wake_waiters(&mut self) {
+        for waiter in self.waiters.drain(..) {
+            waiter.wake();
+        }
+    }
+}
+
+impl<B: Buf> Entry<B> {
+    /// Returns the number of streams opened, waiting to be opened, or
+    /// reserved on this connection.
+    fn load(&self) -> usize {
+        self.send_request.inner.num_send_streams() + self.num_reserved
+    }
+
+    fn has_capacity(&self) -> bool {
+        let streams = &self.send_request.inner;
+        streams.can_open_streams() && self.load() < streams.max_send_streams()
+    }
+}
@@ -66,6 +66,13 @@ pub enum UserError {
 
     /// Tries to send push promise to peer who has disabled server push
     PeerDisabledServerPush,
+
+    /// Sends an extended CONNECT request to a peer that has not enabled the
+    /// extended CONNECT protocol.
+    ExtendedConnectProtocolNotSupported,
+
+    /// Opens a stream after the connection started shutting down.
+    GoingAway,
 }
 
 // ===== impl RecvError =====
@@ -140,6 +147,10 @@ impl fmt::Display for UserError {
             SendPingWhilePending => "send_ping before received previous pong",
             SendSettingsWhilePending => "sending SETTINGS before received previous ACK",
             PeerDisabledServerPush => "sending PUSH_PROMISE to peer who disabled server push",
+            ExtendedConnectProtocolNotSupported => {
+                "sending extended CONNECT to peer who has not enabled it"
+            }
+            GoingAway => "opening stream after the connection started shutting down",
         })
     }
 }
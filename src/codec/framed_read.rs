@@ -5,16 +5,19 @@ use crate::frame::{
 };
 
 use crate::hpack;
+use crate::FrameStats;
 
 use futures_core::Stream;
 
 use bytes::BytesMut;
 
 use std::io;
+use std::mem;
 
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use tokio::io::AsyncRead;
+use tokio_util::codec::Decoder;
 use tokio_util::codec::FramedRead as InnerFramedRead;
 use tokio_util::codec::{LengthDelimitedCodec, LengthDelimitedCodecError};
 
@@ -30,7 +33,29 @@ pub struct FramedRead<T> {
 
     max_header_list_size: usize,
 
+    /// Maximum number of CONTINUATION frames in a header block. Derived from
+    /// the max header list size and max frame size if not set.
+    max_continuation_frames: Option<usize>,
+
+    /// Maximum number of encoded bytes in a header block. If not set, the
+    /// block is only bounded by the CONTINUATION frame limit.
+    max_header_block_size: Option<usize>,
+
     partial: Option<Partial>,
+
+    /// Number of frames read, by type
+    frames_received: FrameStats,
+
+    /// Number of bytes of frames read, by type
+    frame_bytes_received: FrameStats,
+
+    /// Number of bytes of frames read
+    bytes_received: u64,
+
+    /// Set when bytes were added to the read buffer directly. They must be
+    /// decoded before reading from the I/O again, which might never become
+    /// readable otherwise.
+    is_buffered: bool,
 }
 
 /// Partially loaded headers frame
@@ -41,6 +66,12 @@ struct Partial {
 
     /// Partial header payload
     buf: BytesMut,
+
+    /// Number of CONTINUATION frames received so far
+    continuations: usize,
+
+    /// Number of encoded header block bytes received so far
+    block_size: usize,
 }
 
 #[derive(Debug)]
@@ -55,10 +86,36 @@ impl<T> FramedRead<T> {
             inner,
             hpack: hpack::Decoder::new(DEFAULT_SETTINGS_HEADER_TABLE_SIZE),
             max_header_list_size: DEFAULT_SETTINGS_MAX_HEADER_LIST_SIZE,
+            max_continuation_frames: None,
+            max_header_block_size: None,
             partial: None,
+            frames_received: FrameStats::default(),
+            frame_bytes_received: FrameStats::default(),
+            bytes_received: 0,
+            is_buffered: false,
         }
     }
 
+    /// Adds bytes that were already read from the I/O to the read buffer.
+    pub fn extend_read_buffer(&mut self, bytes: &[u8]) {
+        self.inner.read_buffer_mut().extend_from_slice(bytes);
+        self.is_buffered = true;
+    }
+
+    /// Decodes the next frame from the read buffer, without reading from the
+    /// I/O.
+    fn decode_buffered(&mut self) -> Result<Option<BytesMut>, RecvError> {
+        let mut buf = mem::take(self.inner.read_buffer_mut());
+        let res = self.inner.decoder_mut().decode(&mut buf);
+        *self.inner.read_buffer_mut() = buf;
+
+        let bytes = res.map_err(map_err)?;
+        if bytes.is_none() {
+            self.is_buffered = false;
+        }
+        Ok(bytes)
+    }
+
     pub fn get_ref(&self) -> &T {
         self.inner.get_ref()
     }
@@ -83,6 +140,58 @@ impl<T> FramedRead<T> {
         self.inner.decoder_mut().set_max_frame_length(val)
     }
 
+    /// Sets the maximum number of CONTINUATION frames in a header block.
+    #[inline]
+    pub fn set_max_continuation_frames(&mut self, val: usize) {
+        self.max_continuation_frames = Some(val);
+    }
+
+    /// Sets the maximum number of encoded bytes in a header block.
+    #[inline]
+    pub fn set_max_header_block_size(&mut self, val: usize) {
+        self.max_header_block_size = Some(val);
+    }
+
+    fn header_block_limits(&self) -> HeaderBlockLimits {
+        let max_frame_size = self.inner.decoder().max_frame_length();
+        let max_continuation_frames = self.max_continuation_frames.unwrap_or_else(|| {
+            calc_max_continuation_frames(self.max_header_list_size, max_frame_size)
+        });
+        HeaderBlockLimits {
+            max_continuation_frames,
+            max_header_block_size: self.max_header_block_size.unwrap_or(usize::MAX),
+        }
+    }
+
+    /// Returns the number of frames read, by type.
+    pub fn frames_received(&self) -> &FrameStats {
+        &self.frames_received
+    }
+
+    /// Returns the number of bytes of frames read, by type.
+    pub fn frame_bytes_received(&self) -> &FrameStats {
+        &self.frame_bytes_received
+    }
+
+    /// Returns the number of bytes of frames read.
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+
+    /// Returns the size of the entries in the HPACK decoder's dynamic table.
+    pub fn header_table_size(&self) -> usize {
+        self.hpack.table_size()
+    }
+
+    /// Update the header table size setting.
+    ///
+    /// The peer must acknowledge the new size with a dynamic table size
+    /// update before it is enforced.
+    #[inline]
+    pub fn set_header_table_size(&mut self, val: usize) {
+        self.hpack.queue_size_update(val);
+    }
+
     /// Update the max header list size setting.
     #[inline]
     pub fn set_max_header_list_size(&mut self, val: usize) {
@@ -90,12 +199,30 @@ impl<T> FramedRead<T> {
     }
 }
 
+/// Limits on the size of header blocks, protecting against peers that send
+/// endless CONTINUATION frames.
+#[derive(Debug, Clone, Copy)]
+struct HeaderBlockLimits {
+    max_continuation_frames: usize,
+    max_header_block_size: usize,
+}
+
+/// Returns the default maximum number of CONTINUATION frames in a header
+/// block: enough frames to carry a full header list, with some slack for
+/// frames that are not completely filled.
+fn calc_max_continuation_frames(max_header_list_size: usize, max_frame_size: usize) -> usize {
+    let min_frames = (max_header_list_size / max_frame_size).max(1);
+    let padding = min_frames / 4;
+    min_frames.saturating_add(padding).max(5)
+}
+
 /// Decodes a frame.
 ///
 /// This method is intentionally de-generified and outlined because it is very large.
 fn decode_frame(
     hpack: &mut hpack::Decoder,
     max_header_list_size: usize,
+    limits: HeaderBlockLimits,
     partial_inout: &mut Option<Partial>,
     mut bytes: BytesMut,
 ) -> Result<Option<Frame>, RecvError> {
@@ -144,6 +271,12 @@ fn decode_frame(
 
             let is_end_headers = frame.is_end_headers();
 
+            let block_size = payload.len();
+            if block_size > limits.max_header_block_size {
+                proto_err!(conn: "header block size over limit; size={}", block_size);
+                return Err(Connection(Reason::ENHANCE_YOUR_CALM));
+            }
+
             // Load the HPACK encoded headers
             match frame.load_hpack(&mut payload, max_header_list_size, hpack) {
                 Ok(_) => {},
@@ -170,6 +303,8 @@ fn decode_frame(
                 *partial_inout = Some(Partial {
                     frame: Continuable::$frame(frame),
                     buf: payload,
+                    continuations: 0,
+                    block_size,
                 });
 
                 return Ok(None);
@@ -260,6 +395,18 @@ fn decode_frame(
                 }
             }
         }
+        Kind::PriorityUpdate => {
+            let res = frame::PriorityUpdate::load(head, &bytes[frame::HEADER_LEN..]);
+
+            res.map_err(|e| {
+                proto_err!(conn: "failed to load PRIORITY_UPDATE frame; err={:?}", e);
+                match e {
+                    frame::Error::BadFrameSize => Connection(Reason::FRAME_SIZE_ERROR),
+                    _ => Connection(Reason::PROTOCOL_ERROR),
+                }
+            })?
+            .into()
+        }
         Kind::Continuation => {
             let is_end_headers = (head.flag() & 0x4) == 0x4;
 
@@ -277,6 +424,20 @@ fn decode_frame(
                 return Err(Connection(Reason::PROTOCOL_ERROR));
             }
 
+            // Fail before buffering anything if the peer keeps the header
+            // block going for longer than any legitimate one would.
+            partial.continuations += 1;
+            if partial.continuations > limits.max_continuation_frames {
+                proto_err!(conn: "too many CONTINUATION frames; limit={}", limits.max_continuation_frames);
+                return Err(Connection(Reason::ENHANCE_YOUR_CALM));
+            }
+
+            partial.block_size += bytes.len() - frame::HEADER_LEN;
+            if partial.block_size > limits.max_header_block_size {
+                proto_err!(conn: "header block size over limit; size={}", partial.block_size);
+                return Err(Connection(Reason::ENHANCE_YOUR_CALM));
+            }
+
             // Extend the buf
             if partial.buf.is_empty() {
                 partial.buf = bytes.split_off(frame::HEADER_LEN);
@@ -330,9 +491,32 @@ fn decode_frame(
                 return Ok(None);
             }
         }
+        Kind::Origin => {
+            match frame::Origin::load(head, &bytes[frame::HEADER_LEN..]) {
+                Ok(frame) => frame.into(),
+                Err(e) => {
+                    // ORIGIN is a non-critical extension, so invalid frames
+                    // are ignored rather than treated as errors.
+                    tracing::debug!("ignoring invalid ORIGIN frame; err={:?}", e);
+                    return Ok(None);
+                }
+            }
+        }
+        Kind::AltSvc => match frame::AltSvc::load(head, &bytes[frame::HEADER_LEN..]) {
+            Ok(frame) => frame.into(),
+            Err(e) => {
+                // ALTSVC is a non-critical extension, so invalid frames are
+                // ignored rather than treated as errors.
+                tracing::debug!("ignoring invalid ALTSVC frame; err={:?}", e);
+                return Ok(None);
+            }
+        },
         Kind::Unknown => {
-            // Unknown frames are ignored
-            return Ok(None);
+            // Unknown frames are passed on as extension frames. The
+            // connection ignores those that no extension registered for.
+            let kind = bytes[3];
+            let _ = bytes.split_to(frame::HEADER_LEN);
+            frame::Extension::load(head, kind, bytes.freeze()).into()
         }
     };
 
@@ -350,20 +534,36 @@ where
         let _e = span.enter();
         loop {
             tracing::trace!("poll");
-            let bytes = match ready!(Pin::new(&mut self.inner).poll_next(cx)) {
-                Some(Ok(bytes)) => bytes,
-                Some(Err(e)) => return Poll::Ready(Some(Err(map_err(e)))),
-                None => return Poll::Ready(None),
+            let buffered = if self.is_buffered {
+                self.decode_buffered()?
+            } else {
+                None
+            };
+
+            let bytes = match buffered {
+                Some(bytes) => bytes,
+                None => match ready!(Pin::new(&mut self.inner).poll_next(cx)) {
+                    Some(Ok(bytes)) => bytes,
+                    Some(Err(e)) => return Poll::Ready(Some(Err(map_err(e)))),
+                    None => return Poll::Ready(None),
+                },
             };
 
             tracing::trace!(read.bytes = bytes.len());
+            self.bytes_received += bytes.len() as u64;
+            let kind = frame::Head::parse(&bytes).kind();
+            self.frames_received.record(kind, 1);
+            self.frame_bytes_received.record(kind, bytes.len() as u64);
+
+            let limits = self.header_block_limits();
             let Self {
                 ref mut hpack,
                 max_header_list_size,
                 ref mut partial,
                 ..
             } = *self;
-            if let Some(frame) = decode_frame(hpack, max_header_list_size, partial, bytes)? {
+            if let Some(frame) = decode_frame(hpack, max_header_list_size, limits, partial, bytes)?
+            {
                 tracing::debug!(?frame, "received");
                 return Poll::Ready(Some(Ok(frame)));
             }
@@ -2,8 +2,10 @@ use crate::codec::UserError;
 use crate::codec::UserError::*;
 use crate::frame::{self, Frame, FrameSize};
 use crate::hpack;
+use crate::FrameStats;
 
 use bytes::{Buf, BufMut, BytesMut};
+use std::cmp;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
@@ -24,6 +26,9 @@ pub struct FramedWrite<T, B> {
     inner: T,
 
     encoder: Encoder<B>,
+
+    /// Number of bytes written to `inner`
+    bytes_sent: u64,
 }
 
 #[derive(Debug)]
@@ -45,8 +50,20 @@ struct Encoder<B> {
     /// Max frame size, this is specified by the peer
     max_frame_size: FrameSize,
 
+    /// Header table size, this is specified by the peer
+    header_table_size: usize,
+
+    /// Local cap on the HPACK encoder's table size, whatever the peer allows
+    max_header_table_size: Option<usize>,
+
     /// Whether or not the wrapped `AsyncWrite` supports vectored IO.
     is_write_vectored: bool,
+
+    /// Number of frames encoded, by type
+    frames_sent: FrameStats,
+
+    /// Number of bytes of frames encoded, by type
+    frame_bytes_sent: FrameStats,
 }
 
 #[derive(Debug)]
@@ -85,8 +102,13 @@ where
                 next: None,
                 last_data_frame: None,
                 max_frame_size: frame::DEFAULT_MAX_FRAME_SIZE,
+                header_table_size: frame::DEFAULT_SETTINGS_HEADER_TABLE_SIZE,
+                max_header_table_size: None,
                 is_write_vectored,
+                frames_sent: FrameStats::default(),
+                frame_bytes_sent: FrameStats::default(),
             },
+            bytes_sent: 0,
         }
     }
 
@@ -126,7 +148,7 @@ where
                     Some(Next::Data(ref mut frame)) => {
                         tracing::trace!(queued_data_frame = true);
                         let mut buf = (&mut self.encoder.buf).chain(frame.payload_mut());
-                        ready!(write(
+                        self.bytes_sent += ready!(write(
                             &mut self.inner,
                             self.encoder.is_write_vectored,
                             &mut buf,
@@ -135,7 +157,7 @@ where
                     }
                     _ => {
                         tracing::trace!(queued_data_frame = false);
-                        ready!(write(
+                        self.bytes_sent += ready!(write(
                             &mut self.inner,
                             self.encoder.is_write_vectored,
                             &mut self.encoder.buf,
@@ -176,7 +198,7 @@ fn write<T, B>(
     is_write_vectored: bool,
     buf: &mut B,
     cx: &mut Context<'_>,
-) -> Poll<io::Result<()>>
+) -> Poll<io::Result<u64>>
 where
     T: AsyncWrite + Unpin,
     B: Buf,
@@ -192,7 +214,7 @@ where
         ready!(Pin::new(writer).poll_write(cx, buf.chunk()))?
     };
     buf.advance(n);
-    Ok(()).into()
+    Ok(n as u64).into()
 }
 
 #[must_use]
@@ -220,8 +242,12 @@ where
             }
             Some(Next::Continuation(frame)) => {
                 // Buffer the continuation frame, then try to write again
+                self.frames_sent.record(frame::Kind::Continuation, 1);
                 let mut buf = limited_write_buf!(self);
-                if let Some(continuation) = frame.encode(&mut self.hpack, &mut buf) {
+                let continuation = frame.encode(&mut self.hpack, &mut buf);
+                self.frame_bytes_sent
+                    .record(frame::Kind::Continuation, self.buf.get_ref().len() as u64);
+                if let Some(continuation) = continuation {
                     // We previously had a CONTINUATION, and after encoding
                     // it, we got *another* one? Let's just double check
                     // that at least some progress is being made...
@@ -251,6 +277,14 @@ where
 
         tracing::debug!(frame = ?item, "send");
 
+        let kind = item.kind();
+        self.frames_sent.record(kind, 1);
+
+        // Bytes of the frame that are written without being copied into
+        // `buf`.
+        let start = self.buf.get_ref().len();
+        let mut chained = 0;
+
         match item {
             Frame::Data(mut v) => {
                 // Ensure that the payload is not greater than the max frame.
@@ -265,6 +299,7 @@ where
 
                     // Encode the frame head to the buffer
                     head.encode(len, self.buf.get_mut());
+                    chained = len;
 
                     // Save the data frame
                     self.next = Some(Next::Data(v));
@@ -308,19 +343,47 @@ where
                 tracing::trace!(rem = self.buf.remaining(), "encoded window_update");
             }
 
-            Frame::Priority(_) => {
-                /*
+            Frame::Priority(v) => {
                 v.encode(self.buf.get_mut());
-                tracing::trace!("encoded priority; rem={:?}", self.buf.remaining());
-                */
-                unimplemented!();
+                tracing::trace!(rem = self.buf.remaining(), "encoded priority");
+            }
+            Frame::PriorityUpdate(v) => {
+                v.encode(self.buf.get_mut());
+                tracing::trace!(rem = self.buf.remaining(), "encoded priority update");
             }
             Frame::Reset(v) => {
                 v.encode(self.buf.get_mut());
                 tracing::trace!(rem = self.buf.remaining(), "encoded reset");
             }
+            Frame::Origin(v) => {
+                if v.payload_len() > self.max_frame_size() {
+                    return Err(PayloadTooBig);
+                }
+
+                v.encode(self.buf.get_mut());
+                tracing::trace!(rem = self.buf.remaining(), "encoded origin");
+            }
+            Frame::AltSvc(v) => {
+                if v.payload_len() > self.max_frame_size() {
+                    return Err(PayloadTooBig);
+                }
+
+                v.encode(self.buf.get_mut());
+                tracing::trace!(rem = self.buf.remaining(), "encoded alt_svc");
+            }
+            Frame::Extension(v) => {
+                if v.payload().len() > self.max_frame_size() {
+                    return Err(PayloadTooBig);
+                }
+
+                v.encode(self.buf.get_mut());
+                tracing::trace!(rem = self.buf.remaining(), "encoded extension");
+            }
         }
 
+        let len = self.buf.get_ref().len() - start + chained;
+        self.frame_bytes_sent.record(kind, len as u64);
+
         Ok(())
     }
 
@@ -340,6 +403,16 @@ impl<B> Encoder<B> {
     fn max_frame_size(&self) -> usize {
         self.max_frame_size as usize
     }
+
+    /// Resizes the HPACK table, signalling the peer with a dynamic table
+    /// size update in the next header block if it changes.
+    fn update_header_table_size(&mut self) {
+        let size = match self.max_header_table_size {
+            Some(max) => cmp::min(self.header_table_size, max),
+            None => self.header_table_size,
+        };
+        self.hpack.update_max_size(size);
+    }
 }
 
 impl<T, B> FramedWrite<T, B> {
@@ -356,7 +429,35 @@ impl<T, B> FramedWrite<T, B> {
 
     /// Set the peer's header table size.
     pub fn set_header_table_size(&mut self, val: usize) {
-        self.encoder.hpack.update_max_size(val);
+        self.encoder.header_table_size = val;
+        self.encoder.update_header_table_size();
+    }
+
+    /// Set the max size the HPACK encoder's table may grow to, whatever the
+    /// peer's header table size.
+    pub fn set_max_header_table_size(&mut self, val: usize) {
+        self.encoder.max_header_table_size = Some(val);
+        self.encoder.update_header_table_size();
+    }
+
+    /// Returns the number of frames encoded, by type.
+    pub fn frames_sent(&self) -> &FrameStats {
+        &self.encoder.frames_sent
+    }
+
+    /// Returns the number of bytes of frames encoded, by type.
+    pub fn frame_bytes_sent(&self) -> &FrameStats {
+        &self.encoder.frame_bytes_sent
+    }
+
+    /// Returns the number of bytes written to the upstream `AsyncWrite`.
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    /// Returns the size of the entries in the HPACK encoder's dynamic table.
+    pub fn header_table_size(&self) -> usize {
+        self.encoder.hpack.table_size()
     }
 
     /// Retrieve the last data frame that has been sent
@@ -8,6 +8,7 @@ use self::framed_read::FramedRead;
 use self::framed_write::FramedWrite;
 
 use crate::frame::{self, Data, Frame};
+use crate::FrameStats;
 
 use bytes::Buf;
 use futures_core::Stream;
@@ -94,11 +95,74 @@ impl<T, B> Codec<T, B> {
         self.framed_write().set_header_table_size(val)
     }
 
+    /// Set the max header table size that the peer may use to encode headers.
+    pub fn set_recv_header_table_size(&mut self, val: usize) {
+        self.inner.set_header_table_size(val);
+    }
+
+    /// Set the max size the header table used to encode headers may grow to,
+    /// whatever the peer's header table size.
+    pub fn set_max_send_header_table_size(&mut self, val: usize) {
+        self.framed_write().set_max_header_table_size(val)
+    }
+
     /// Set the max header list size that can be received.
     pub fn set_max_recv_header_list_size(&mut self, val: usize) {
         self.inner.set_max_header_list_size(val);
     }
 
+    /// Returns the number of frames sent, by type.
+    pub fn frames_sent(&self) -> &FrameStats {
+        self.inner.get_ref().frames_sent()
+    }
+
+    /// Returns the number of frames received, by type.
+    pub fn frames_received(&self) -> &FrameStats {
+        self.inner.frames_received()
+    }
+
+    /// Returns the number of bytes of frames sent, by type.
+    pub fn frame_bytes_sent(&self) -> &FrameStats {
+        self.inner.get_ref().frame_bytes_sent()
+    }
+
+    /// Returns the number of bytes of frames received, by type.
+    pub fn frame_bytes_received(&self) -> &FrameStats {
+        self.inner.frame_bytes_received()
+    }
+
+    /// Returns the number of bytes written to the wire.
+    pub fn bytes_sent(&self) -> u64 {
+        self.inner.get_ref().bytes_sent()
+    }
+
+    /// Returns the number of bytes of frames read from the wire.
+    pub fn bytes_received(&self) -> u64 {
+        self.inner.bytes_received()
+    }
+
+    /// Returns the size of the entries in the HPACK encoder's dynamic table.
+    pub fn send_header_table_size(&self) -> usize {
+        self.inner.get_ref().header_table_size()
+    }
+
+    /// Returns the size of the entries in the HPACK decoder's dynamic table.
+    pub fn recv_header_table_size(&self) -> usize {
+        self.inner.header_table_size()
+    }
+
+    /// Set the max number of CONTINUATION frames that can be received in a
+    /// single header block.
+    pub fn set_max_recv_continuation_frames(&mut self, val: usize) {
+        self.inner.set_max_continuation_frames(val);
+    }
+
+    /// Set the max number of encoded bytes that can be received in a single
+    /// header block.
+    pub fn set_max_recv_header_block_size(&mut self, val: usize) {
+        self.inner.set_max_header_block_size(val);
+    }
+
     /// Get a reference to the inner stream.
     #[cfg(feature = "unstable")]
     pub fn get_ref(&self) -> &T {
@@ -110,6 +174,11 @@ impl<T, B> Codec<T, B> {
         self.inner.get_mut().get_mut()
     }
 
+    /// Adds bytes that were already read from the I/O to the read buffer.
+    pub fn extend_read_buffer(&mut self, bytes: &[u8]) {
+        self.inner.extend_read_buffer(bytes);
+    }
+
     /// Takes the data payload value that was fully written to the socket
     pub(crate) fn take_last_data_frame(&mut self) -> Option<Data<B>> {
         self.framed_write().take_last_data_frame()
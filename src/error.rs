@@ -1,4 +1,5 @@
 use crate::codec::{SendError, UserError};
+use crate::frame::GoAway;
 use crate::proto;
 
 use std::{error, fmt, io};
@@ -24,10 +25,19 @@ pub struct Error {
 enum Kind {
     /// An error caused by an action taken by the remote peer.
     ///
-    /// This is either an error received by the peer or caused by an invalid
-    /// action taken by the peer (i.e. a protocol error).
+    /// This is caused by an invalid action taken by the peer (i.e. a
+    /// protocol error), or a stream or connection reset by this library.
     Proto(Reason),
 
+    /// The remote peer sent a RST_STREAM frame.
+    Reset(Reason),
+
+    /// The remote peer sent a GOAWAY frame.
+    ///
+    /// `unprocessed` is set when the error is reported for a request that the
+    /// peer did not process.
+    GoAway { frame: GoAway, unprocessed: bool },
+
     /// An error resulting from an invalid action taken by the user of this
     /// library.
     User(UserError),
@@ -36,6 +46,22 @@ enum Kind {
     Io(io::Error),
 }
 
+/// The remote did not acknowledge a keep-alive PING in time.
+///
+/// This travels inside an `io::Error` of kind `TimedOut` so that it is
+/// reported to every stream on the connection like any other I/O failure.
+#[derive(Debug)]
+pub(crate) struct KeepAliveTimedOut;
+
+/// A request did not complete before its [`RequestTimeout`] elapsed.
+///
+/// Like `KeepAliveTimedOut`, this travels inside an `io::Error` of kind
+/// `TimedOut`.
+///
+/// [`RequestTimeout`]: ../ext/struct.RequestTimeout.html
+#[derive(Debug)]
+pub(crate) struct RequestTimedOut;
+
 // ===== impl Error =====
 
 impl Error {
@@ -45,11 +71,68 @@ impl Error {
     /// action taken by the peer (i.e. a protocol error).
     pub fn reason(&self) -> Option<Reason> {
         match self.kind {
-            Kind::Proto(reason) => Some(reason),
+            Kind::Proto(reason) | Kind::Reset(reason) => Some(reason),
+            Kind::GoAway { ref frame, .. } => Some(frame.reason()),
+            _ => None,
+        }
+    }
+
+    /// If the error was caused by a GOAWAY frame from the remote peer, the
+    /// opaque debug data that came with it.
+    ///
+    /// The debug data is meant for diagnostics and may be empty.
+    pub fn go_away_debug_data(&self) -> Option<&[u8]> {
+        match self.kind {
+            Kind::GoAway { ref frame, .. } => Some(frame.debug_data()),
+            _ => None,
+        }
+    }
+
+    /// If the error was caused by a GOAWAY frame from the remote peer, the
+    /// last stream ID that the peer might have processed.
+    ///
+    /// Requests on streams with a higher ID were not processed by the peer,
+    /// and can safely be retried on another connection.
+    pub fn go_away_last_stream_id(&self) -> Option<crate::StreamId> {
+        match self.kind {
+            Kind::GoAway { ref frame, .. } => {
+                Some(crate::StreamId::from_internal(frame.last_stream_id()))
+            }
             _ => None,
         }
     }
 
+    /// Returns true if the error was caused by a GOAWAY frame from the remote
+    /// peer.
+    pub fn is_go_away(&self) -> bool {
+        matches!(self.kind, Kind::GoAway { .. })
+    }
+
+    /// Returns true if the error was received from the remote peer, in either
+    /// a RST_STREAM or a GOAWAY frame.
+    ///
+    /// Errors detected by this library, including protocol errors caused by
+    /// the peer, return false.
+    pub fn is_remote(&self) -> bool {
+        matches!(self.kind, Kind::Reset(_) | Kind::GoAway { .. })
+    }
+
+    /// Returns true if the peer is known not to have processed the request,
+    /// so it can safely be retried, possibly on another connection.
+    ///
+    /// This is the case when the request's stream was reset by the peer with
+    /// `REFUSED_STREAM`, or when the peer sent a GOAWAY frame with a last
+    /// stream ID lower than the request's stream ID. Requests started after
+    /// a GOAWAY frame was received are never sent, and are safe to retry as
+    /// well.
+    pub fn is_safe_to_retry(&self) -> bool {
+        match self.kind {
+            Kind::Reset(reason) => reason == Reason::REFUSED_STREAM,
+            Kind::GoAway { unprocessed, .. } => unprocessed,
+            _ => false,
+        }
+    }
+
     /// Returns the true if the error is an io::Error
     pub fn is_io(&self) -> bool {
         match self.kind {
@@ -66,6 +149,26 @@ impl Error {
         }
     }
 
+    /// Returns true if the error was caused by the remote failing to
+    /// acknowledge a keep-alive PING within the configured timeout.
+    pub fn is_keep_alive_timeout(&self) -> bool {
+        match self.kind {
+            Kind::Io(ref e) => KeepAliveTimedOut::is(e),
+            _ => false,
+        }
+    }
+
+    /// Returns true if the error was caused by a request not completing
+    /// before its [`RequestTimeout`] elapsed.
+    ///
+    /// [`RequestTimeout`]: ext/struct.RequestTimeout.html
+    pub fn is_request_timeout(&self) -> bool {
+        match self.kind {
+            Kind::Io(ref e) => RequestTimedOut::is(e),
+            _ => false,
+        }
+    }
+
     /// Returns the error if the error is an io::Error
     pub fn into_io(self) -> Option<io::Error> {
         match self.kind {
@@ -88,6 +191,8 @@ impl From<proto::Error> for Error {
         Error {
             kind: match src {
                 Proto(reason) => Kind::Proto(reason),
+                Reset(reason) => Kind::Reset(reason),
+                GoAway { frame, unprocessed } => Kind::GoAway { frame, unprocessed },
                 Io(e) => Kind::Io(e),
             },
         }
@@ -125,7 +230,8 @@ impl fmt::Display for Error {
         use self::Kind::*;
 
         match self.kind {
-            Proto(ref reason) => write!(fmt, "protocol error: {}", reason),
+            Proto(ref reason) | Reset(ref reason) => write!(fmt, "protocol error: {}", reason),
+            GoAway { ref frame, .. } => write!(fmt, "protocol error: {}", frame.reason()),
             User(ref e) => write!(fmt, "user error: {}", e),
             Io(ref e) => fmt::Display::fmt(e, fmt),
         }
@@ -133,3 +239,61 @@ impl fmt::Display for Error {
 }
 
 impl error::Error for Error {}
+
+// ===== impl KeepAliveTimedOut =====
+
+impl KeepAliveTimedOut {
+    pub(crate) fn is(err: &io::Error) -> bool {
+        if err.kind() != io::ErrorKind::TimedOut {
+            return false;
+        }
+
+        match err.get_ref() {
+            Some(inner) => inner.is::<KeepAliveTimedOut>(),
+            None => false,
+        }
+    }
+}
+
+impl From<KeepAliveTimedOut> for io::Error {
+    fn from(src: KeepAliveTimedOut) -> io::Error {
+        io::Error::new(io::ErrorKind::TimedOut, src)
+    }
+}
+
+impl fmt::Display for KeepAliveTimedOut {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("keep-alive timed out")
+    }
+}
+
+impl error::Error for KeepAliveTimedOut {}
+
+// ===== impl RequestTimedOut =====
+
+impl RequestTimedOut {
+    pub(crate) fn is(err: &io::Error) -> bool {
+        if err.kind() != io::ErrorKind::TimedOut {
+            return false;
+        }
+
+        match err.get_ref() {
+            Some(inner) => inner.is::<RequestTimedOut>(),
+            None => false,
+        }
+    }
+}
+
+impl From<RequestTimedOut> for io::Error {
+    fn from(src: RequestTimedOut) -> io::Error {
+        io::Error::new(io::ErrorKind::TimedOut, src)
+    }
+}
+
+impl fmt::Display for RequestTimedOut {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("request timed out")
+    }
+}
+
+impl error::Error for RequestTimedOut {}
@@ -0,0 +1,497 @@
+//! Extensions specific to the HTTP/2.0 protocol.
+
+use crate::codec::UserError;
+use crate::frame::{self, Kind};
+use crate::hpack::BytesStr;
+use crate::proto;
+use crate::StreamId;
+
+use bytes::Bytes;
+use http::Uri;
+use std::convert::TryFrom;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use std::{error, fmt, io};
+
+/// Represents the `:protocol` pseudo-header used by
+/// the [Extended CONNECT Protocol].
+///
+/// When a server has enabled the extended CONNECT protocol, requests using the
+/// `CONNECT` method carry this value in their extensions. Clients set it by
+/// inserting a `Protocol` into the request's extensions before calling
+/// `send_request`.
+///
+/// [Extended CONNECT Protocol]: https://datatracker.ietf.org/doc/html/rfc8441#section-4
+#[derive(Clone, Eq, PartialEq)]
+pub struct Protocol {
+    value: BytesStr,
+}
+
+impl Protocol {
+    /// Converts a static string to a protocol name.
+    pub const fn from_static(value: &'static str) -> Self {
+        Self {
+            value: BytesStr::from_static(value),
+        }
+    }
+
+    /// Returns a str representation of the header.
+    pub fn as_str(&self) -> &str {
+        self.value.as_str()
+    }
+
+    pub(crate) fn try_from(bytes: Bytes) -> Result<Self, std::str::Utf8Error> {
+        Ok(Self {
+            value: BytesStr::try_from(bytes)?,
+        })
+    }
+}
+
+impl<'a> From<&'a str> for Protocol {
+    fn from(value: &'a str) -> Self {
+        Self {
+            value: BytesStr::from(value),
+        }
+    }
+}
+
+impl AsRef<[u8]> for Protocol {
+    fn as_ref(&self) -> &[u8] {
+        self.value.as_ref()
+    }
+}
+
+impl fmt::Debug for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+/// The priority of a stream, as defined by [Extensible Priorities].
+///
+/// Clients send the priority of a request in its `priority` header, or
+/// change it later using [`SendStream::set_priority`], which sends a
+/// PRIORITY_UPDATE frame. Servers use it to decide in which order responses
+/// are sent.
+///
+/// Streams with a lower urgency are served first. Streams with the same
+/// urgency that are not incremental are served one at a time, in the order
+/// of their stream IDs, while incremental streams share the connection.
+///
+/// [Extensible Priorities]: https://www.rfc-editor.org/rfc/rfc9218.html
+/// [`SendStream::set_priority`]: ../struct.SendStream.html#method.set_priority
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Priority {
+    urgency: u8,
+    incremental: bool,
+}
+
+impl Priority {
+    /// The lowest urgency a stream may have.
+    pub const MAX_URGENCY: u8 = 7;
+
+    /// Creates a new priority.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `urgency` is greater than `Priority::MAX_URGENCY`.
+    pub fn new(urgency: u8, incremental: bool) -> Self {
+        assert!(urgency <= Self::MAX_URGENCY, "invalid urgency");
+        Priority {
+            urgency,
+            incremental,
+        }
+    }
+
+    /// Returns the urgency, from 0 (most urgent) to 7.
+    pub fn urgency(&self) -> u8 {
+        self.urgency
+    }
+
+    /// Returns `true` if the response may be processed incrementally.
+    pub fn is_incremental(&self) -> bool {
+        self.incremental
+    }
+
+    /// Parses a priority field value, such as `u=1, i`.
+    ///
+    /// Parameters that are unknown or invalid are ignored, as required by
+    /// RFC 9218, section 4.
+    pub(crate) fn parse(src: &[u8]) -> Self {
+        let mut priority = Priority::default();
+
+        for member in src.split(|b| *b == b',') {
+            let member = trim(member);
+            let (key, value) = match member.iter().position(|b| *b == b'=') {
+                Some(i) => (&member[..i], Some(&member[i + 1..])),
+                None => (member, None),
+            };
+
+            match (key, value) {
+                (b"u", Some(&[digit])) if (b'0'..=b'7').contains(&digit) => {
+                    priority.urgency = digit - b'0';
+                }
+                (b"i", None) | (b"i", Some(b"?1")) => priority.incremental = true,
+                (b"i", Some(b"?0")) => priority.incremental = false,
+                _ => {}
+            }
+        }
+
+        priority
+    }
+
+    /// Encodes the priority as a field value.
+    pub(crate) fn to_field_value(self) -> Bytes {
+        let mut value = format!("u={}", self.urgency);
+        if self.incremental {
+            value.push_str(", i");
+        }
+        Bytes::from(value)
+    }
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Priority {
+            urgency: 3,
+            incremental: false,
+        }
+    }
+}
+
+/// Limits how long a client request may take.
+///
+/// Insert a `RequestTimeout` into the extensions of a request before passing
+/// it to `client::SendRequest::send_request`. If the response, including its
+/// body and trailers, has not been received once the duration elapses, the
+/// stream is reset with `CANCEL`, and both the `ResponseFuture` and the
+/// `RecvStream` of the response fail with an error for which
+/// [`Error::is_request_timeout`] returns `true`.
+///
+/// The timer starts when the request is sent, and is driven by the
+/// connection task.
+///
+/// # Examples
+///
+/// ```
+/// # use h2::ext::RequestTimeout;
+/// # use http::Request;
+/// # use std::time::Duration;
+/// let mut request = Request::get("https://example.com/").body(()).unwrap();
+/// request
+///     .extensions_mut()
+///     .insert(RequestTimeout::new(Duration::from_secs(10)));
+/// ```
+///
+/// [`Error::is_request_timeout`]: ../struct.Error.html#method.is_request_timeout
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct RequestTimeout {
+    duration: Duration,
+}
+
+impl RequestTimeout {
+    /// Creates a timeout of the given duration.
+    pub fn new(duration: Duration) -> Self {
+        RequestTimeout { duration }
+    }
+
+    /// Returns the duration of the timeout.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+/// An origin that a server is authoritative for, as sent in an [ORIGIN frame].
+///
+/// Servers send origins with `server::Connection::send_origin` so that
+/// clients may use the connection for requests to any of them, and clients
+/// report the origins they received with `client::SendRequest::origin_set`.
+///
+/// The value is the ASCII serialization of the origin, such as
+/// `https://example.com` or `https://example.com:8443`.
+///
+/// [ORIGIN frame]: https://www.rfc-editor.org/rfc/rfc8336.html
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct Origin {
+    value: BytesStr,
+}
+
+/// An error returned when parsing an invalid `Origin`.
+#[derive(Debug)]
+pub struct InvalidOrigin {
+    _p: (),
+}
+
+/// A frame of a type that is not defined by RFC 7540.
+///
+/// Extension frames are sent and received with [`ExtensionFrames`]. The
+/// library does not interpret them, except for making sure that they never
+/// end up between the frames of a header block.
+///
+/// [`ExtensionFrames`]: struct.ExtensionFrames.html
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ExtensionFrame {
+    inner: frame::Extension,
+}
+
+/// A handle to send and receive extension frames on a connection.
+///
+/// Only frames of the types registered with the `extension_frame_type`
+/// method of the client or server `Builder` are received; the peer's frames
+/// of any other unknown type are ignored.
+// NOT Clone on purpose
+pub struct ExtensionFrames {
+    inner: proto::UserExtensions,
+}
+
+// ===== impl Origin =====
+
+impl Origin {
+    /// The longest origin that fits in an ORIGIN frame.
+    const MAX_LEN: usize = frame::DEFAULT_MAX_FRAME_SIZE as usize - 2;
+
+    /// Converts a static string to an origin.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` is not the ASCII serialization of an origin.
+    pub fn from_static(src: &'static str) -> Self {
+        match Origin::try_from(src) {
+            Ok(origin) => origin,
+            Err(_) => panic!("invalid origin: {:?}", src),
+        }
+    }
+
+    /// Returns the ASCII serialization of the origin.
+    pub fn as_str(&self) -> &str {
+        self.value.as_str()
+    }
+
+    /// Parses an origin received in an ORIGIN frame.
+    pub(crate) fn from_bytes(src: Bytes) -> Option<Self> {
+        let value = BytesStr::try_from(src).ok()?;
+
+        if is_valid_origin(value.as_str()) {
+            Some(Origin { value })
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn into_bytes(self) -> Bytes {
+        self.value.into_inner()
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Origin {
+    type Error = InvalidOrigin;
+
+    fn try_from(src: &'a str) -> Result<Self, Self::Error> {
+        if is_valid_origin(src) {
+            Ok(Origin {
+                value: BytesStr::from(src),
+            })
+        } else {
+            Err(InvalidOrigin { _p: () })
+        }
+    }
+}
+
+impl AsRef<str> for Origin {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for Origin {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), fmt)
+    }
+}
+
+/// Returns true if `src` is of the form `scheme://host[:port]`.
+fn is_valid_origin(src: &str) -> bool {
+    if src.len() > Origin::MAX_LEN || !src.is_ascii() {
+        return false;
+    }
+
+    let uri = match src.parse::<Uri>() {
+        Ok(uri) => uri,
+        Err(_) => return false,
+    };
+
+    match (uri.scheme_str(), uri.authority()) {
+        (Some(scheme), Some(authority)) => {
+            // Rules out user info, paths, queries and fragments.
+            !authority.as_str().contains('@')
+                && src.len() == scheme.len() + 3 + authority.as_str().len()
+        }
+        _ => false,
+    }
+}
+
+// ===== impl InvalidOrigin =====
+
+impl fmt::Display for InvalidOrigin {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("invalid origin")
+    }
+}
+
+impl error::Error for InvalidOrigin {}
+
+// ===== impl ExtensionFrame =====
+
+impl ExtensionFrame {
+    /// The largest payload an extension frame may carry.
+    ///
+    /// This is the smallest `SETTINGS_MAX_FRAME_SIZE` a peer may advertise,
+    /// so frames of this size are always accepted.
+    pub const MAX_PAYLOAD_LEN: usize = frame::DEFAULT_MAX_FRAME_SIZE as usize;
+
+    /// Creates a connection-level extension frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frame_type` is the type of a frame that is implemented by
+    /// this library.
+    pub fn new(frame_type: u8, flags: u8, payload: Bytes) -> Self {
+        assert_extension_type(frame_type);
+        ExtensionFrame {
+            inner: frame::Extension::new(frame_type, flags, frame::StreamId::zero(), payload),
+        }
+    }
+
+    /// Sends the frame on the given stream, instead of on the connection.
+    pub fn set_stream_id(&mut self, stream_id: StreamId) {
+        self.inner.set_stream_id(stream_id.into_internal());
+    }
+
+    /// Returns the frame type.
+    pub fn frame_type(&self) -> u8 {
+        self.inner.kind()
+    }
+
+    /// Returns the frame flags.
+    pub fn flags(&self) -> u8 {
+        self.inner.flags()
+    }
+
+    /// Returns the stream the frame was sent on, or `None` for a
+    /// connection-level frame.
+    pub fn stream_id(&self) -> Option<StreamId> {
+        let id = self.inner.stream_id();
+        if id.is_zero() {
+            None
+        } else {
+            Some(StreamId::from_internal(id))
+        }
+    }
+
+    /// Returns the frame payload.
+    pub fn payload(&self) -> &Bytes {
+        self.inner.payload()
+    }
+
+    /// Consumes the frame, returning the payload.
+    pub fn into_payload(self) -> Bytes {
+        self.inner.into_payload()
+    }
+}
+
+// ===== impl ExtensionFrames =====
+
+impl ExtensionFrames {
+    pub(crate) fn new(inner: proto::UserExtensions) -> Self {
+        ExtensionFrames { inner }
+    }
+
+    /// Waits until there is room to queue another extension frame.
+    ///
+    /// Returns an error once the connection is gone.
+    pub async fn ready(&mut self) -> Result<(), crate::Error> {
+        futures_util::future::poll_fn(move |cx| self.poll_ready(cx)).await
+    }
+
+    /// Polls for room to queue another extension frame.
+    ///
+    /// At most as many frames as set with the `max_queued_extension_frames`
+    /// method of the client or server `Builder` wait to be written at a
+    /// time, so this should be called before each call to [`send_frame`].
+    ///
+    /// Returns an error once the connection is gone.
+    ///
+    /// [`send_frame`]: #method.send_frame
+    pub fn poll_ready(&mut self, cx: &mut Context) -> Poll<Result<(), crate::Error>> {
+        self.inner.poll_ready(cx).map_err(push_error)
+    }
+
+    /// Queues an extension frame to be sent to the peer.
+    ///
+    /// Frames are written in the order they are queued, but may be written
+    /// before or after frames that the connection sends on its own.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the payload is longer than
+    /// `ExtensionFrame::MAX_PAYLOAD_LEN`, if too many frames are already
+    /// waiting to be written because [`poll_ready`] was not called, or if
+    /// the connection is gone.
+    ///
+    /// [`poll_ready`]: #method.poll_ready
+    pub fn send_frame(&mut self, frame: ExtensionFrame) -> Result<(), crate::Error> {
+        if frame.payload().len() > ExtensionFrame::MAX_PAYLOAD_LEN {
+            return Err(UserError::PayloadTooBig.into());
+        }
+
+        self.inner.send_frame(frame.inner).map_err(push_error)
+    }
+
+    /// Receives the next extension frame from the peer.
+    ///
+    /// Returns `None` once the connection is gone.
+    pub async fn recv_frame(&mut self) -> Option<ExtensionFrame> {
+        futures_util::future::poll_fn(move |cx| self.poll_recv_frame(cx)).await
+    }
+
+    /// Polls for the next extension frame from the peer.
+    ///
+    /// Returns `None` once the connection is gone.
+    pub fn poll_recv_frame(&mut self, cx: &mut Context) -> Poll<Option<ExtensionFrame>> {
+        self.inner
+            .poll_recv(cx)
+            .map(|frame| frame.map(|inner| ExtensionFrame { inner }))
+    }
+}
+
+impl fmt::Debug for ExtensionFrames {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("ExtensionFrames").finish()
+    }
+}
+
+fn push_error(err: proto::PushError) -> crate::Error {
+    match err {
+        proto::PushError::Full => UserError::Rejected.into(),
+        proto::PushError::Closed => crate::Error::from_io(io::ErrorKind::BrokenPipe.into()),
+    }
+}
+
+/// Panics if `frame_type` is a frame type that is implemented by the library.
+pub(crate) fn assert_extension_type(frame_type: u8) {
+    assert!(
+        Kind::new(frame_type) == Kind::Unknown,
+        "frame type {:#x} is not an extension frame type",
+        frame_type
+    );
+}
+
+fn trim(mut src: &[u8]) -> &[u8] {
+    while let [b' ' | b'\t', rest @ ..] = src {
+        src = rest;
+    }
+    while let [rest @ .., b' ' | b'\t'] = src {
+        src = rest;
+    }
+    src
+}
@@ -0,0 +1,78 @@
+use crate::frame::{Error, Frame, Head, Kind, StreamId};
+
+use bytes::{BufMut, Bytes};
+
+/// An ALTSVC frame (RFC 7838, section 4).
+///
+/// On stream 0, the frame advertises alternative services for `origin`. On
+/// any other stream, `origin` is empty and the advertisement is for the
+/// origin of the stream's request.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AltSvc {
+    stream_id: StreamId,
+    origin: Bytes,
+    field_value: Bytes,
+}
+
+impl AltSvc {
+    pub fn new(stream_id: StreamId, origin: Bytes, field_value: Bytes) -> Self {
+        AltSvc {
+            stream_id,
+            origin,
+            field_value,
+        }
+    }
+
+    pub fn load(head: Head, payload: &[u8]) -> Result<Self, Error> {
+        debug_assert_eq!(head.kind(), Kind::AltSvc);
+
+        if payload.len() < 2 {
+            return Err(Error::BadFrameSize);
+        }
+
+        let origin_len = ((payload[0] as usize) << 8) | payload[1] as usize;
+        let payload = &payload[2..];
+
+        if payload.len() < origin_len {
+            return Err(Error::BadFrameSize);
+        }
+
+        Ok(AltSvc {
+            stream_id: head.stream_id(),
+            origin: Bytes::copy_from_slice(&payload[..origin_len]),
+            field_value: Bytes::copy_from_slice(&payload[origin_len..]),
+        })
+    }
+
+    pub fn stream_id(&self) -> StreamId {
+        self.stream_id
+    }
+
+    pub fn origin(&self) -> &Bytes {
+        &self.origin
+    }
+
+    pub fn field_value(&self) -> &Bytes {
+        &self.field_value
+    }
+
+    /// The length of the frame payload.
+    pub fn payload_len(&self) -> usize {
+        2 + self.origin.len() + self.field_value.len()
+    }
+
+    pub fn encode<B: BufMut>(&self, dst: &mut B) {
+        tracing::trace!("encoding ALTSVC; id={:?}", self.stream_id);
+        let head = Head::new(Kind::AltSvc, 0, self.stream_id);
+        head.encode(self.payload_len(), dst);
+        dst.put_u16(self.origin.len() as u16);
+        dst.put_slice(&self.origin);
+        dst.put_slice(&self.field_value);
+    }
+}
+
+impl<B> From<AltSvc> for Frame<B> {
+    fn from(src: AltSvc) -> Self {
+        Frame::AltSvc(src)
+    }
+}
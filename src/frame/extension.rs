@@ -0,0 +1,87 @@
+use crate::frame::{Frame, Head, Kind, StreamId};
+
+use bytes::{BufMut, Bytes};
+
+/// A frame of a type that is not defined by RFC 7540.
+///
+/// The frame is not interpreted by the library; the type, flags and payload
+/// are given to the extension that registered the frame type.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Extension {
+    kind: u8,
+    flags: u8,
+    stream_id: StreamId,
+    payload: Bytes,
+}
+
+impl Extension {
+    pub fn new(kind: u8, flags: u8, stream_id: StreamId, payload: Bytes) -> Self {
+        debug_assert_eq!(Kind::new(kind), Kind::Unknown);
+
+        Extension {
+            kind,
+            flags,
+            stream_id,
+            payload,
+        }
+    }
+
+    /// Loads an extension frame, `kind` being the raw frame type of `head`.
+    pub fn load(head: Head, kind: u8, payload: Bytes) -> Self {
+        debug_assert_eq!(head.kind(), Kind::Unknown);
+
+        Extension {
+            kind,
+            flags: head.flag(),
+            stream_id: head.stream_id(),
+            payload,
+        }
+    }
+
+    /// The raw frame type.
+    pub fn kind(&self) -> u8 {
+        self.kind
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    pub fn stream_id(&self) -> StreamId {
+        self.stream_id
+    }
+
+    pub fn set_stream_id(&mut self, stream_id: StreamId) {
+        self.stream_id = stream_id;
+    }
+
+    pub fn payload(&self) -> &Bytes {
+        &self.payload
+    }
+
+    pub fn into_payload(self) -> Bytes {
+        self.payload
+    }
+
+    pub fn encode<B: BufMut>(&self, dst: &mut B) {
+        tracing::trace!(
+            "encoding extension frame; type={:#x} id={:?}",
+            self.kind,
+            self.stream_id
+        );
+
+        // `Head` only knows about the frame types it can parse, so the frame
+        // header is written out by hand.
+        dst.put_uint(self.payload.len() as u64, 3);
+        dst.put_u8(self.kind);
+        dst.put_u8(self.flags);
+        dst.put_u32(self.stream_id.into());
+        dst.put_slice(&self.payload);
+    }
+}
+
+impl<B> From<Extension> for Frame<B> {
+    fn from(src: Extension) -> Self {
+        Frame::Extension(src)
+    }
+}
@@ -25,12 +25,16 @@ macro_rules! unpack_octets_4 {
     };
 }
 
+mod alt_svc;
 mod data;
+mod extension;
 mod go_away;
 mod head;
 mod headers;
+mod origin;
 mod ping;
 mod priority;
+mod priority_update;
 mod reason;
 mod reset;
 mod settings;
@@ -38,14 +42,18 @@ mod stream_id;
 mod util;
 mod window_update;
 
+pub use self::alt_svc::AltSvc;
 pub use self::data::Data;
+pub use self::extension::Extension;
 pub use self::go_away::GoAway;
 pub use self::head::{Head, Kind};
 pub use self::headers::{
     parse_u64, Continuation, Headers, Pseudo, PushPromise, PushPromiseHeaderError,
 };
+pub use self::origin::Origin;
 pub use self::ping::Ping;
 pub use self::priority::{Priority, StreamDependency};
+pub use self::priority_update::PriorityUpdate;
 pub use self::reason::Reason;
 pub use self::reset::Reset;
 pub use self::settings::Settings;
@@ -71,12 +79,16 @@ pub enum Frame<T = Bytes> {
     Data(Data<T>),
     Headers(Headers),
     Priority(Priority),
+    PriorityUpdate(PriorityUpdate),
     PushPromise(PushPromise),
     Settings(Settings),
     Ping(Ping),
     GoAway(GoAway),
     WindowUpdate(WindowUpdate),
     Reset(Reset),
+    Origin(Origin),
+    AltSvc(AltSvc),
+    Extension(Extension),
 }
 
 impl<T> Frame<T> {
@@ -90,12 +102,37 @@ impl<T> Frame<T> {
             Data(frame) => frame.map(f).into(),
             Headers(frame) => frame.into(),
             Priority(frame) => frame.into(),
+            PriorityUpdate(frame) => frame.into(),
             PushPromise(frame) => frame.into(),
             Settings(frame) => frame.into(),
             Ping(frame) => frame.into(),
             GoAway(frame) => frame.into(),
             WindowUpdate(frame) => frame.into(),
             Reset(frame) => frame.into(),
+            Origin(frame) => frame.into(),
+            AltSvc(frame) => frame.into(),
+            Extension(frame) => frame.into(),
+        }
+    }
+
+    /// Returns the type of the frame.
+    pub fn kind(&self) -> Kind {
+        use self::Frame::*;
+
+        match *self {
+            Data(_) => Kind::Data,
+            Headers(_) => Kind::Headers,
+            Priority(_) => Kind::Priority,
+            PriorityUpdate(_) => Kind::PriorityUpdate,
+            PushPromise(_) => Kind::PushPromise,
+            Settings(_) => Kind::Settings,
+            Ping(_) => Kind::Ping,
+            GoAway(_) => Kind::GoAway,
+            WindowUpdate(_) => Kind::WindowUpdate,
+            Reset(_) => Kind::Reset,
+            Origin(_) => Kind::Origin,
+            AltSvc(_) => Kind::AltSvc,
+            Extension(_) => Kind::Unknown,
         }
     }
 }
@@ -108,12 +145,16 @@ impl<T> fmt::Debug for Frame<T> {
             Data(ref frame) => fmt::Debug::fmt(frame, fmt),
             Headers(ref frame) => fmt::Debug::fmt(frame, fmt),
             Priority(ref frame) => fmt::Debug::fmt(frame, fmt),
+            PriorityUpdate(ref frame) => fmt::Debug::fmt(frame, fmt),
             PushPromise(ref frame) => fmt::Debug::fmt(frame, fmt),
             Settings(ref frame) => fmt::Debug::fmt(frame, fmt),
             Ping(ref frame) => fmt::Debug::fmt(frame, fmt),
             GoAway(ref frame) => fmt::Debug::fmt(frame, fmt),
             WindowUpdate(ref frame) => fmt::Debug::fmt(frame, fmt),
             Reset(ref frame) => fmt::Debug::fmt(frame, fmt),
+            Origin(ref frame) => fmt::Debug::fmt(frame, fmt),
+            AltSvc(ref frame) => fmt::Debug::fmt(frame, fmt),
+            Extension(ref frame) => fmt::Debug::fmt(frame, fmt),
         }
     }
 }
@@ -158,4 +199,8 @@ pub enum Error {
 
     /// Failed to perform HPACK decoding
     Hpack(hpack::DecoderError),
+
+    /// The `HTTP2-Settings` header of an upgrade request is not a valid
+    /// base64url encoded SETTINGS payload.
+    InvalidHttp2Settings,
 }
@@ -0,0 +1,77 @@
+use crate::frame::{Error, Frame, Head, Kind, StreamId};
+
+use bytes::{BufMut, Bytes};
+
+/// An ORIGIN frame (RFC 8336).
+///
+/// The frame is sent by servers on stream 0 and lists the ASCII
+/// serializations of the origins that the server is authoritative for.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Origin {
+    origins: Vec<Bytes>,
+}
+
+impl Origin {
+    pub fn new(origins: Vec<Bytes>) -> Self {
+        Origin { origins }
+    }
+
+    pub fn load(head: Head, payload: &[u8]) -> Result<Self, Error> {
+        debug_assert_eq!(head.kind(), Kind::Origin);
+
+        if !head.stream_id().is_zero() {
+            return Err(Error::InvalidStreamId);
+        }
+
+        let mut origins = Vec::new();
+        let mut rem = payload;
+
+        while !rem.is_empty() {
+            if rem.len() < 2 {
+                return Err(Error::BadFrameSize);
+            }
+
+            let len = ((rem[0] as usize) << 8) | rem[1] as usize;
+            rem = &rem[2..];
+
+            if rem.len() < len {
+                return Err(Error::BadFrameSize);
+            }
+
+            origins.push(Bytes::copy_from_slice(&rem[..len]));
+            rem = &rem[len..];
+        }
+
+        Ok(Origin { origins })
+    }
+
+    pub fn origins(&self) -> &[Bytes] {
+        &self.origins
+    }
+
+    pub fn into_origins(self) -> Vec<Bytes> {
+        self.origins
+    }
+
+    /// The length of the frame payload.
+    pub fn payload_len(&self) -> usize {
+        self.origins.iter().map(|origin| 2 + origin.len()).sum()
+    }
+
+    pub fn encode<B: BufMut>(&self, dst: &mut B) {
+        tracing::trace!("encoding ORIGIN; origins={}", self.origins.len());
+        let head = Head::new(Kind::Origin, 0, StreamId::zero());
+        head.encode(self.payload_len(), dst);
+
+        for origin in &self.origins {
+            dst.put_u16(origin.len() as u16);
+            dst.put_slice(origin);
+        }
+    }
+}
+
+impl<B> From<Origin> for Frame<B> {
+    fn from(src: Origin) -> Self {
+        Frame::Origin(src)
+    }
+}
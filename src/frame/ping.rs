@@ -15,6 +15,8 @@ pub struct Ping {
 // zeroes to distinguish this specific PING from any other.
 const SHUTDOWN_PAYLOAD: Payload = [0x0b, 0x7b, 0xa2, 0xf0, 0x8b, 0x9b, 0xfe, 0x54];
 const USER_PAYLOAD: Payload = [0x3b, 0x7c, 0xdb, 0x7a, 0x0b, 0x87, 0x16, 0xb4];
+const KEEP_ALIVE_PAYLOAD: Payload = [0x4d, 0x1f, 0x93, 0x6a, 0xc2, 0x05, 0xe8, 0x37];
+const BDP_PAYLOAD: Payload = [0x92, 0x6e, 0x3c, 0xd1, 0x58, 0xa4, 0x07, 0xbf];
 
 impl Ping {
     #[cfg(feature = "unstable")]
@@ -29,6 +31,18 @@ impl Ping {
     #[cfg(not(feature = "unstable"))]
     pub(crate) const USER: Payload = USER_PAYLOAD;
 
+    #[cfg(feature = "unstable")]
+    pub const KEEP_ALIVE: Payload = KEEP_ALIVE_PAYLOAD;
+
+    #[cfg(not(feature = "unstable"))]
+    pub(crate) const KEEP_ALIVE: Payload = KEEP_ALIVE_PAYLOAD;
+
+    #[cfg(feature = "unstable")]
+    pub const BDP: Payload = BDP_PAYLOAD;
+
+    #[cfg(not(feature = "unstable"))]
+    pub(crate) const BDP: Payload = BDP_PAYLOAD;
+
     pub fn new(payload: Payload) -> Ping {
         Ping {
             ack: false,
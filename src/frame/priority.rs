@@ -1,5 +1,7 @@
 use crate::frame::*;
 
+use bytes::BufMut;
+
 #[derive(Debug, Eq, PartialEq)]
 pub struct Priority {
     stream_id: StreamId,
@@ -21,6 +23,13 @@ pub struct StreamDependency {
 }
 
 impl Priority {
+    pub fn new(stream_id: StreamId, dependency: StreamDependency) -> Self {
+        Priority {
+            stream_id,
+            dependency,
+        }
+    }
+
     pub fn load(head: Head, payload: &[u8]) -> Result<Self, Error> {
         let dependency = StreamDependency::load(payload)?;
 
@@ -33,6 +42,21 @@ impl Priority {
             dependency,
         })
     }
+
+    pub fn stream_id(&self) -> StreamId {
+        self.stream_id
+    }
+
+    pub fn dependency(&self) -> &StreamDependency {
+        &self.dependency
+    }
+
+    pub fn encode<B: BufMut>(&self, dst: &mut B) {
+        tracing::trace!("encoding PRIORITY; id={:?}", self.stream_id);
+        let head = Head::new(Kind::Priority, 0, self.stream_id);
+        head.encode(5, dst);
+        self.dependency.encode(dst);
+    }
 }
 
 impl<B> From<Priority> for Frame<B> {
@@ -69,4 +93,22 @@ impl StreamDependency {
     pub fn dependency_id(&self) -> StreamId {
         self.dependency_id
     }
+
+    /// Returns the weight, in the range [0, 255].
+    pub fn weight(&self) -> u8 {
+        self.weight
+    }
+
+    pub fn is_exclusive(&self) -> bool {
+        self.is_exclusive
+    }
+
+    pub fn encode<B: BufMut>(&self, dst: &mut B) {
+        let mut id = u32::from(self.dependency_id);
+        if self.is_exclusive {
+            id |= 1 << 31;
+        }
+        dst.put_u32(id);
+        dst.put_u8(self.weight);
+    }
 }
@@ -0,0 +1,68 @@
+use crate::frame::{Error, Frame, Head, Kind, StreamId};
+
+use bytes::{BufMut, Bytes};
+
+/// A PRIORITY_UPDATE frame (RFC 9218, section 7.1).
+///
+/// The frame is sent on stream 0 and carries the priority of another stream
+/// as an ASCII priority field value, the same as the `priority` header.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PriorityUpdate {
+    prioritized_id: StreamId,
+    field_value: Bytes,
+}
+
+impl PriorityUpdate {
+    pub fn new(prioritized_id: StreamId, field_value: Bytes) -> Self {
+        PriorityUpdate {
+            prioritized_id,
+            field_value,
+        }
+    }
+
+    pub fn load(head: Head, payload: &[u8]) -> Result<Self, Error> {
+        debug_assert_eq!(head.kind(), Kind::PriorityUpdate);
+
+        if !head.stream_id().is_zero() {
+            return Err(Error::InvalidStreamId);
+        }
+
+        if payload.len() < 4 {
+            return Err(Error::BadFrameSize);
+        }
+
+        let (prioritized_id, _) = StreamId::parse(&payload[..4]);
+
+        if prioritized_id.is_zero() {
+            return Err(Error::InvalidStreamId);
+        }
+
+        Ok(PriorityUpdate {
+            prioritized_id,
+            field_value: Bytes::copy_from_slice(&payload[4..]),
+        })
+    }
+
+    /// The stream whose priority is updated.
+    pub fn prioritized_id(&self) -> StreamId {
+        self.prioritized_id
+    }
+
+    pub fn field_value(&self) -> &Bytes {
+        &self.field_value
+    }
+
+    pub fn encode<B: BufMut>(&self, dst: &mut B) {
+        tracing::trace!("encoding PRIORITY_UPDATE; id={:?}", self.prioritized_id);
+        let head = Head::new(Kind::PriorityUpdate, 0, StreamId::zero());
+        head.encode(4 + self.field_value.len(), dst);
+        dst.put_u32(self.prioritized_id.into());
+        dst.put_slice(&self.field_value);
+    }
+}
+
+impl<B> From<PriorityUpdate> for Frame<B> {
+    fn from(src: PriorityUpdate) -> Self {
+        Frame::PriorityUpdate(src)
+    }
+}
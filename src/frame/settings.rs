@@ -13,6 +13,8 @@ pub struct Settings {
     initial_window_size: Option<u32>,
     max_frame_size: Option<u32>,
     max_header_list_size: Option<u32>,
+    enable_connect_protocol: Option<u32>,
+    no_rfc7540_priorities: Option<u32>,
 }
 
 /// An enum that lists all valid settings that can be sent in a SETTINGS
@@ -27,6 +29,8 @@ pub enum Setting {
     InitialWindowSize(u32),
     MaxFrameSize(u32),
     MaxHeaderListSize(u32),
+    EnableConnectProtocol(u32),
+    NoRfc7540Priorities(u32),
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Default)]
@@ -107,15 +111,29 @@ impl Settings {
         self.enable_push = Some(enable as u32);
     }
 
+    pub fn is_extended_connect_protocol_enabled(&self) -> Option<bool> {
+        self.enable_connect_protocol.map(|val| val != 0)
+    }
+
+    pub fn set_enable_connect_protocol(&mut self, val: Option<u32>) {
+        self.enable_connect_protocol = val;
+    }
+
+    pub fn is_rfc7540_priorities_disabled(&self) -> Option<bool> {
+        self.no_rfc7540_priorities.map(|val| val != 0)
+    }
+
+    pub fn set_no_rfc7540_priorities(&mut self, val: Option<u32>) {
+        self.no_rfc7540_priorities = val;
+    }
+
     pub fn header_table_size(&self) -> Option<u32> {
         self.header_table_size
     }
 
-    /*
     pub fn set_header_table_size(&mut self, size: Option<u32>) {
         self.header_table_size = size;
     }
-    */
 
     pub fn load(head: Head, payload: &[u8]) -> Result<Settings, Error> {
         use self::Setting::*;
@@ -181,6 +199,22 @@ impl Settings {
                 Some(MaxHeaderListSize(val)) => {
                     settings.max_header_list_size = Some(val);
                 }
+                Some(EnableConnectProtocol(val)) => match val {
+                    0 | 1 => {
+                        settings.enable_connect_protocol = Some(val);
+                    }
+                    _ => {
+                        return Err(Error::InvalidSettingValue);
+                    }
+                },
+                Some(NoRfc7540Priorities(val)) => match val {
+                    0 | 1 => {
+                        settings.no_rfc7540_priorities = Some(val);
+                    }
+                    _ => {
+                        return Err(Error::InvalidSettingValue);
+                    }
+                },
                 None => {}
             }
         }
@@ -188,6 +222,29 @@ impl Settings {
         Ok(settings)
     }
 
+    /// Loads the settings carried by the `HTTP2-Settings` header of an
+    /// HTTP/1.1 `Upgrade: h2c` request.
+    ///
+    /// The header value is the base64url encoded payload of a SETTINGS frame,
+    /// without padding (RFC 7540, section 3.2.1).
+    pub fn load_http2_settings(value: &[u8]) -> Result<Settings, Error> {
+        let payload = base64url_decode(value).ok_or(Error::InvalidHttp2Settings)?;
+        let head = Head::new(Kind::Settings, 0, StreamId::zero());
+
+        Settings::load(head, &payload).map_err(|e| match e {
+            Error::InvalidPayloadAckSettings => Error::InvalidHttp2Settings,
+            e => e,
+        })
+    }
+
+    /// Encodes these settings as the value of the `HTTP2-Settings` header of
+    /// an HTTP/1.1 `Upgrade: h2c` request.
+    pub fn encode_http2_settings(&self) -> String {
+        let mut payload = BytesMut::with_capacity(self.payload_len());
+        self.for_each(|setting| setting.encode(&mut payload));
+        base64url_encode(&payload)
+    }
+
     fn payload_len(&self) -> usize {
         let mut len = 0;
         self.for_each(|_| len += 6);
@@ -236,9 +293,66 @@ impl Settings {
         if let Some(v) = self.max_header_list_size {
             f(MaxHeaderListSize(v));
         }
+
+        if let Some(v) = self.enable_connect_protocol {
+            f(EnableConnectProtocol(v));
+        }
+
+        if let Some(v) = self.no_rfc7540_priorities {
+            f(NoRfc7540Priorities(v));
+        }
     }
 }
 
+const BASE64URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+fn base64url_encode(src: &[u8]) -> String {
+    let mut dst = String::with_capacity(src.len() * 4 / 3 + 2);
+
+    for chunk in src.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+
+        // Each byte of input yields one character, plus one for the
+        // remainder. Padding is omitted.
+        for i in 0..=chunk.len() {
+            let idx = (n >> (18 - 6 * i)) & 0x3f;
+            dst.push(BASE64URL[idx as usize] as char);
+        }
+    }
+
+    dst
+}
+
+fn base64url_decode(src: &[u8]) -> Option<Vec<u8>> {
+    // Be lenient with senders that include padding anyway.
+    let end = src.iter().rposition(|&b| b != b'=').map_or(0, |i| i + 1);
+    let src = &src[..end];
+
+    if src.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut dst = Vec::with_capacity(src.len() * 3 / 4);
+
+    for chunk in src.chunks(4) {
+        let mut n = 0u32;
+
+        for (i, &b) in chunk.iter().enumerate() {
+            let val = BASE64URL.iter().position(|&c| c == b)? as u32;
+            n |= val << (18 - 6 * i);
+        }
+
+        for i in 0..chunk.len() - 1 {
+            dst.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+
+    Some(dst)
+}
+
 impl<T> From<Settings> for Frame<T> {
     fn from(src: Settings) -> Frame<T> {
         Frame::Settings(src)
@@ -269,6 +383,12 @@ impl fmt::Debug for Settings {
             Setting::MaxHeaderListSize(v) => {
                 builder.field("max_header_list_size", &v);
             }
+            Setting::EnableConnectProtocol(v) => {
+                builder.field("enable_connect_protocol", &v);
+            }
+            Setting::NoRfc7540Priorities(v) => {
+                builder.field("no_rfc7540_priorities", &v);
+            }
         });
 
         builder.finish()
@@ -291,6 +411,8 @@ impl Setting {
             4 => Some(InitialWindowSize(val)),
             5 => Some(MaxFrameSize(val)),
             6 => Some(MaxHeaderListSize(val)),
+            8 => Some(EnableConnectProtocol(val)),
+            9 => Some(NoRfc7540Priorities(val)),
             _ => None,
         }
     }
@@ -322,6 +444,8 @@ impl Setting {
             InitialWindowSize(v) => (4, v),
             MaxFrameSize(v) => (5, v),
             MaxHeaderListSize(v) => (6, v),
+            EnableConnectProtocol(v) => (8, v),
+            NoRfc7540Priorities(v) => (9, v),
         };
 
         dst.put_u16(kind);
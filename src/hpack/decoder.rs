@@ -155,8 +155,12 @@ impl Decoder {
         }
     }
 
+    /// Returns the size of the entries in the dynamic table.
+    pub fn table_size(&self) -> usize {
+        self.table.size
+    }
+
     /// Queues a potential size update
-    #[allow(dead_code)]
     pub fn queue_size_update(&mut self, size: usize) {
         let size = match self.max_size_update {
             Some(v) => cmp::max(v, size),
@@ -183,6 +187,10 @@ impl Decoder {
             self.last_max_update = size;
         }
 
+        // If the table is larger than the size we last advertised, the
+        // encoder must shrink it before anything else in the header block.
+        let mut must_resize = self.table.max_size > self.last_max_update;
+
         let span = tracing::trace_span!("hpack::decode");
         let _e = span.enter();
 
@@ -192,7 +200,13 @@ impl Decoder {
             // At this point we are always at the beginning of the next block
             // within the HPACK data. The type of the block can always be
             // determined from the first byte.
-            match Representation::load(ty)? {
+            let repr = Representation::load(ty)?;
+
+            if must_resize && !matches!(repr, SizeUpdate) {
+                return Err(DecoderError::InvalidMaxDynamicSize);
+            }
+
+            match repr {
                 Indexed => {
                     tracing::trace!(rem = src.remaining(), kind = %"Indexed");
                     can_resize = false;
@@ -236,6 +250,7 @@ impl Decoder {
 
                     // Handle the dynamic table size update
                     self.process_size_update(src)?;
+                    must_resize = false;
                     consume(src);
                 }
             }
@@ -845,6 +860,27 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_decode_requires_size_update_after_shrink() {
+        let mut de = Decoder::new(4096);
+        de.queue_size_update(0);
+
+        // `:method: GET` without shrinking the table first
+        let mut buf = BytesMut::new();
+        buf.extend(&[0x80 | 2]);
+        let res = de.decode(&mut Cursor::new(&mut buf), |_| {});
+        assert_eq!(res, Err(DecoderError::InvalidMaxDynamicSize));
+
+        // A size update of 0, then `:method: GET`
+        let mut buf = BytesMut::new();
+        buf.extend(&[0b0010_0000, 0x80 | 2]);
+        let mut res = vec![];
+        de.decode(&mut Cursor::new(&mut buf), |h| res.push(h))
+            .unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(de.table.max_size, 0);
+    }
+
     fn huff_encode(src: &[u8]) -> BytesMut {
         let mut buf = BytesMut::new();
         huffman::encode(src, &mut buf).unwrap();
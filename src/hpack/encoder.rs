@@ -43,6 +43,11 @@ impl Encoder {
         }
     }
 
+    /// Returns the size of the entries in the dynamic table.
+    pub fn table_size(&self) -> usize {
+        self.table.size()
+    }
+
     /// Queues a max size update.
     ///
     /// The next call to `encode` will include a dynamic size update frame.
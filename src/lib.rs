@@ -114,6 +114,7 @@ mod frame;
 pub mod frame;
 
 pub mod client;
+pub mod ext;
 pub mod server;
 mod share;
 
@@ -122,7 +123,10 @@ mod share;
 pub mod fuzz_bridge;
 
 pub use crate::error::{Error, Reason};
-pub use crate::share::{FlowControl, Ping, PingPong, Pong, RecvStream, SendStream, StreamId};
+pub use crate::share::{
+    ConnectionStats, FlowControl, FrameStats, Ping, PingPong, Pong, RecvStream, RecvStreamReader,
+    SendStream, SendStreamWriter, StreamId,
+};
 
 #[cfg(feature = "unstable")]
 pub use codec::{Codec, RecvError, SendError, UserError};
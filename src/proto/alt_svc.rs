@@ -0,0 +1,80 @@
+use super::frame_queue::{FrameQueue, PushError};
+use crate::frame;
+
+use std::task::{Context, Poll};
+
+/// Maximum number of ALTSVC frames waiting for the user. Advertisements are
+/// only hints, so any more are dropped.
+const MAX_QUEUED: usize = 64;
+
+/// Passes ALTSVC frames received from the peer to the user.
+///
+/// Only clients hand out a `UserAltSvcs`, so servers drop the frames, as
+/// RFC 7838 requires.
+#[derive(Debug)]
+pub(crate) struct AltSvcs {
+    user: Option<FrameQueue<frame::AltSvc>>,
+}
+
+#[derive(Debug)]
+pub(crate) struct UserAltSvcs(FrameQueue<frame::AltSvc>);
+
+// ===== impl AltSvcs =====
+
+impl AltSvcs {
+    pub(crate) fn new() -> Self {
+        AltSvcs { user: None }
+    }
+
+    pub(crate) fn take_user(&mut self) -> Option<UserAltSvcs> {
+        if self.user.is_some() {
+            return None;
+        }
+
+        let queue = FrameQueue::new(MAX_QUEUED);
+        self.user = Some(queue.clone());
+        Some(UserAltSvcs(queue))
+    }
+
+    pub(crate) fn recv_frame(&mut self, frame: frame::AltSvc) {
+        let user = match self.user {
+            Some(ref user) => user,
+            None => {
+                tracing::trace!("no handle to receive ALTSVC frame; dropping");
+                return;
+            }
+        };
+
+        match user.push(frame) {
+            Ok(()) => {}
+            Err(PushError::Closed) => {
+                tracing::trace!("ALTSVC handle dropped; dropping");
+            }
+            Err(PushError::Full) => {
+                tracing::debug!("too many ALTSVC frames queued; dropping");
+            }
+        }
+    }
+}
+
+impl Drop for AltSvcs {
+    fn drop(&mut self) {
+        if let Some(ref user) = self.user {
+            user.close();
+        }
+    }
+}
+
+// ===== impl UserAltSvcs =====
+
+impl UserAltSvcs {
+    pub(crate) fn poll_recv(&self, cx: &mut Context) -> Poll<Option<frame::AltSvc>> {
+        self.0.poll_pop(cx)
+    }
+}
+
+impl Drop for UserAltSvcs {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
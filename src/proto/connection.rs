@@ -7,12 +7,15 @@ use crate::proto::*;
 
 use bytes::{Buf, Bytes};
 use futures_core::Stream;
-use std::io;
+use std::collections::VecDeque;
+use std::future::Future;
 use std::marker::PhantomData;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use std::time::Duration;
+use std::{cmp, io};
 use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::{sleep, Sleep};
 
 /// An H2 connection
 #[derive(Debug)]
@@ -36,11 +39,12 @@ where
     /// Tracks the connection level state transitions.
     state: State,
 
-    /// An error to report back once complete.
+    /// A GOAWAY frame received from the peer, reported back as an error
+    /// once complete.
     ///
     /// This exists separately from State in order to support
     /// graceful shutdown.
-    error: Option<Reason>,
+    error: Option<frame::GoAway>,
 
     /// Pending GOAWAY frames to write.
     go_away: GoAway,
@@ -48,9 +52,34 @@ where
     /// Ping/pong handler
     ping_pong: PingPong,
 
+    /// The connection receive window we are aiming for
+    target_connection_window: WindowSize,
+
+    /// Closes the connection once it has been without streams for too long
+    idle_timeout: Option<IdleTimeout>,
+
+    /// When a graceful shutdown stops waiting for streams to complete
+    shutdown_deadline: Option<Pin<Box<Sleep>>>,
+
+    /// Set once the graceful shutdown deadline elapsed, after which the
+    /// connection closes even if its frames can't be flushed
+    is_shutdown_forced: bool,
+
     /// Connection settings
     settings: Settings,
 
+    /// Limits on the rate of inbound control frames
+    rate_limits: RateLimits,
+
+    /// Extension frames exchanged with the user
+    extensions: Extensions,
+
+    /// Alternative service advertisements received from the peer
+    alt_svc: AltSvcs,
+
+    /// Connection-level ORIGIN and ALTSVC frames to write
+    pending_frames: VecDeque<Frame>,
+
     /// Stream state handler
     streams: Streams<B, P>,
 
@@ -68,9 +97,13 @@ struct DynConnection<'a, B: Buf = Bytes> {
 
     streams: DynStreams<'a, B>,
 
-    error: &'a mut Option<Reason>,
+    error: &'a mut Option<frame::GoAway>,
 
     ping_pong: &'a mut PingPong,
+
+    extensions: &'a mut Extensions,
+
+    alt_svc: &'a mut AltSvcs,
 }
 
 #[derive(Debug, Clone)]
@@ -79,7 +112,19 @@ pub(crate) struct Config {
     pub initial_max_send_streams: usize,
     pub reset_stream_duration: Duration,
     pub reset_stream_max: usize,
+    pub max_send_buffer_size: usize,
+    pub remote_reset_stream_max: usize,
     pub settings: frame::Settings,
+    pub keep_alive_interval: Option<Duration>,
+    pub keep_alive_timeout: Duration,
+    pub keep_alive_while_idle: bool,
+    pub idle_timeout: Option<Duration>,
+    pub adaptive_window: bool,
+    pub auto_release_capacity: bool,
+    pub extension_frame_types: Vec<u8>,
+    pub max_queued_extension_frames: usize,
+    pub max_control_frames_per_second: usize,
+    pub max_empty_data_frames_per_second: usize,
 }
 
 #[derive(Debug)]
@@ -112,22 +157,62 @@ where
                 local_push_enabled: config.settings.is_push_enabled().unwrap_or(true),
                 local_reset_duration: config.reset_stream_duration,
                 local_reset_max: config.reset_stream_max,
+                local_max_buffer_size: config.max_send_buffer_size,
                 remote_init_window_sz: DEFAULT_INITIAL_WINDOW_SIZE,
                 remote_max_initiated: config
                     .settings
                     .max_concurrent_streams()
                     .map(|max| max as usize),
+                remote_reset_max: config.remote_reset_stream_max,
+                local_extended_connect_protocol_enabled: config
+                    .settings
+                    .is_extended_connect_protocol_enabled()
+                    .unwrap_or(false),
+                local_no_rfc7540_priorities: config
+                    .settings
+                    .is_rfc7540_priorities_disabled()
+                    .unwrap_or(false),
+                local_auto_release_capacity: config.auto_release_capacity,
             }
         }
-        let streams = Streams::new(streams_config(&config));
+        let streams_config = streams_config(&config);
+        let mut ping_pong = PingPong::new();
+        if config.adaptive_window {
+            ping_pong.set_adaptive_window(cmp::max(
+                streams_config.local_init_window_sz,
+                DEFAULT_INITIAL_WINDOW_SIZE,
+            ));
+        }
+        let streams = Streams::new(streams_config);
+        if let Some(interval) = config.keep_alive_interval {
+            ping_pong.set_keep_alive(
+                interval,
+                config.keep_alive_timeout,
+                config.keep_alive_while_idle,
+            );
+        }
         Connection {
             codec,
             inner: ConnectionInner {
                 state: State::Open,
                 error: None,
                 go_away: GoAway::new(),
-                ping_pong: PingPong::new(),
+                ping_pong,
+                target_connection_window: DEFAULT_INITIAL_WINDOW_SIZE,
+                idle_timeout: config.idle_timeout.map(IdleTimeout::new),
+                shutdown_deadline: None,
+                is_shutdown_forced: false,
                 settings: Settings::new(config.settings),
+                rate_limits: RateLimits::new(
+                    config.max_control_frames_per_second,
+                    config.max_empty_data_frames_per_second,
+                ),
+                extensions: Extensions::new(
+                    config.extension_frame_types,
+                    config.max_queued_extension_frames,
+                ),
+                alt_svc: AltSvcs::new(),
+                pending_frames: VecDeque::new(),
                 streams,
                 span: tracing::debug_span!("Connection", peer = %P::NAME),
                 _phantom: PhantomData,
@@ -137,6 +222,8 @@ where
 
     /// connection flow control
     pub(crate) fn set_target_window_size(&mut self, size: WindowSize) {
+        self.inner.target_connection_window = size;
+        self.inner.ping_pong.raise_adaptive_window(size);
         self.inner.streams.set_target_connection_window_size(size);
     }
 
@@ -167,6 +254,35 @@ where
         let _e = self.inner.span.enter();
         let span = tracing::trace_span!("poll_ready");
         let _e = span.enter();
+        // The keep-alive timer may queue a PING, so poll it before sending
+        // pending pings.
+        let streams = &self.inner.streams;
+        self.inner
+            .ping_pong
+            .poll_keep_alive(cx, || !streams.has_streams())
+            .map_err(io::Error::from)?;
+
+        // Grow the receive windows if the BDP estimate has increased. The
+        // estimate never shrinks a window that is already larger. The stream
+        // window is raised with a SETTINGS frame, so wait for any previous
+        // SETTINGS to be acknowledged first.
+        if self.inner.settings.is_synced() {
+            if let Some(size) = self.inner.ping_pong.take_window_update() {
+                if size > self.inner.target_connection_window {
+                    self.inner.target_connection_window = size;
+                    self.inner.streams.set_target_connection_window_size(size);
+                }
+                if size > self.inner.streams.recv_init_window_size() {
+                    let mut settings = frame::Settings::default();
+                    settings.set_initial_window_size(Some(size));
+                    self.inner
+                        .settings
+                        .send_settings(settings)
+                        .expect("settings are synced");
+                }
+            }
+        }
+
         // The order of these calls don't really matter too much
         ready!(self.inner.ping_pong.send_pending_pong(cx, &mut self.codec))?;
         ready!(self.inner.ping_pong.send_pending_ping(cx, &mut self.codec))?;
@@ -175,6 +291,15 @@ where
             .settings
             .poll_send(cx, &mut self.codec, &mut self.inner.streams))?;
         ready!(self.inner.streams.send_pending_refusal(cx, &mut self.codec))?;
+        ready!(self.inner.extensions.send_pending(cx, &mut self.codec))?;
+
+        while !self.inner.pending_frames.is_empty() {
+            ready!(self.codec.poll_ready(cx))?;
+            let frame = self.inner.pending_frames.pop_front().unwrap();
+            let frame = frame
+                .map(|_| unreachable!("Frame::map closure will only be called on DATA frames."));
+            self.codec.buffer(frame).expect("invalid frame");
+        }
 
         Poll::Ready(Ok(()))
     }
@@ -187,30 +312,64 @@ where
         self.inner.go_away.send_pending_go_away(cx, &mut self.codec)
     }
 
-    pub fn go_away_from_user(&mut self, e: Reason) {
-        self.inner.as_dyn().go_away_from_user(e)
+    pub fn go_away_gracefully(&mut self) {
+        if self.inner.go_away.is_going_away() {
+            // No reason to start a new one.
+            return;
+        }
+
+        if !P::is_server() {
+            // A client only has to tell the server that it won't send any
+            // more requests, nor accept any more pushes. Streams that are
+            // still active can complete.
+            let mut connection = self.inner.as_dyn();
+            let last_processed_id = connection.streams.last_processed_id();
+            connection.go_away(last_processed_id, Reason::NO_ERROR);
+            return;
+        }
+
+        // According to http://httpwg.org/specs/rfc7540.html#GOAWAY:
+        //
+        // > A server that is attempting to gracefully shut down a connection
+        // > SHOULD send an initial GOAWAY frame with the last stream
+        // > identifier set to 2^31-1 and a NO_ERROR code. This signals to the
+        // > client that a shutdown is imminent and that initiating further
+        // > requests is prohibited. After allowing time for any in-flight
+        // > stream creation (at least one round-trip time), the server can
+        // > send another GOAWAY frame with an updated last stream identifier.
+        // > This ensures that a connection can be cleanly shut down without
+        // > losing requests.
+        self.inner.as_dyn().go_away(StreamId::MAX, Reason::NO_ERROR);
+
+        // We take the advice of waiting 1 RTT literally, and wait
+        // for a pong before proceeding.
+        self.inner.ping_pong.ping_shutdown();
+    }
+
+    pub fn go_away_from_user(&mut self, e: Reason, debug_data: Bytes) {
+        self.inner.as_dyn().go_away_from_user(e, debug_data)
+    }
+
+    /// Sets the debug data sent in the GOAWAY frames of a graceful shutdown.
+    pub fn set_go_away_debug_data(&mut self, debug_data: Bytes) {
+        self.inner.go_away.set_debug_data(debug_data);
     }
 
     fn take_error(&mut self, ours: Reason) -> Poll<Result<(), proto::Error>> {
-        let reason = if let Some(theirs) = self.inner.error.take() {
-            match (ours, theirs) {
-                // If either side reported an error, return that
-                // to the user.
-                (Reason::NO_ERROR, err) | (err, Reason::NO_ERROR) => err,
-                // If both sides reported an error, give their
-                // error back to th user. We assume our error
-                // was a consequence of their error, and less
-                // important.
-                (_, theirs) => theirs,
-            }
-        } else {
-            ours
+        let theirs = match self.inner.error.take() {
+            Some(theirs) => theirs,
+            None if ours == Reason::NO_ERROR => return Poll::Ready(Ok(())),
+            None => return Poll::Ready(Err(proto::Error::Proto(ours))),
         };
 
-        if reason == Reason::NO_ERROR {
-            Poll::Ready(Ok(()))
-        } else {
-            Poll::Ready(Err(proto::Error::Proto(reason)))
+        match (ours, theirs.reason()) {
+            (Reason::NO_ERROR, Reason::NO_ERROR) => Poll::Ready(Ok(())),
+            // If only we reported an error, return that to the user.
+            (ours, Reason::NO_ERROR) => Poll::Ready(Err(proto::Error::Proto(ours))),
+            // If they reported an error, give their error back to the
+            // user. If both sides did, we assume our error was a
+            // consequence of their error, and less important.
+            (_, _) => Poll::Ready(Err(theirs.into())),
         }
     }
 
@@ -228,6 +387,61 @@ where
         self.inner.ping_pong.take_user_pings()
     }
 
+    /// Queues ORIGIN frames announcing `origins`, split so that each frame
+    /// fits in the smallest allowed frame size.
+    pub(crate) fn send_origin(&mut self, origins: Vec<crate::ext::Origin>) {
+        let mut entries = Vec::new();
+        let mut len = 0;
+
+        for origin in origins {
+            let origin = origin.into_bytes();
+            if len + 2 + origin.len() > frame::DEFAULT_MAX_FRAME_SIZE as usize {
+                let entries = std::mem::take(&mut entries);
+                self.inner
+                    .pending_frames
+                    .push_back(frame::Origin::new(entries).into());
+                len = 0;
+            }
+            len += 2 + origin.len();
+            entries.push(origin);
+        }
+
+        self.inner
+            .pending_frames
+            .push_back(frame::Origin::new(entries).into());
+    }
+
+    /// Queues a connection-level ALTSVC frame.
+    pub(crate) fn send_alt_svc(&mut self, origin: crate::ext::Origin, field_value: Bytes) {
+        let frame = frame::AltSvc::new(StreamId::zero(), origin.into_bytes(), field_value);
+        self.inner.pending_frames.push_back(frame.into());
+    }
+
+    pub(crate) fn take_user_alt_svc(&mut self) -> Option<UserAltSvcs> {
+        self.inner.alt_svc.take_user()
+    }
+
+    /// Returns a snapshot of the connection's counters.
+    pub(crate) fn stats(&self) -> crate::ConnectionStats {
+        let mut stats = crate::ConnectionStats {
+            bytes_sent: self.codec.bytes_sent(),
+            bytes_received: self.codec.bytes_received(),
+            frames_sent: self.codec.frames_sent().clone(),
+            frames_received: self.codec.frames_received().clone(),
+            frame_bytes_sent: self.codec.frame_bytes_sent().clone(),
+            frame_bytes_received: self.codec.frame_bytes_received().clone(),
+            send_header_table_size: self.codec.send_header_table_size(),
+            recv_header_table_size: self.codec.recv_header_table_size(),
+            ..Default::default()
+        };
+        self.inner.streams.fill_stats(&mut stats);
+        stats
+    }
+
+    pub(crate) fn take_user_extensions(&mut self) -> Option<UserExtensions> {
+        self.inner.extensions.take_user()
+    }
+
     /// Advances the internal state of the connection.
     pub fn poll(&mut self, cx: &mut Context) -> Poll<Result<(), proto::Error>> {
         // XXX(eliza): cloning the span is unfortunately necessary here in
@@ -249,10 +463,34 @@ where
                         Poll::Ready(result) => result,
                         // The connection is not ready to make progress
                         Poll::Pending => {
+                            // Timers are polled before flushing, so they
+                            // still elapse if the peer stops reading.
+                            if self.inner.streams.poll_request_deadlines(cx) {
+                                continue;
+                            }
+
+                            if let Some(ref mut deadline) = self.inner.shutdown_deadline {
+                                if deadline.as_mut().poll(cx).is_ready() {
+                                    tracing::debug!("graceful shutdown deadline elapsed");
+                                    self.inner.shutdown_deadline = None;
+                                    self.inner.is_shutdown_forced = true;
+                                    self.inner.as_dyn().cancel_remaining_streams();
+                                    continue;
+                                }
+                            }
+
                             // Ensure all window updates have been sent.
                             //
                             // This will also handle flushing `self.codec`
-                            ready!(self.inner.streams.poll_complete(cx, &mut self.codec))?;
+                            match self.inner.streams.poll_complete(cx, &mut self.codec)? {
+                                Poll::Ready(()) => {}
+                                Poll::Pending if self.inner.is_shutdown_forced => {
+                                    tracing::debug!("graceful shutdown deadline elapsed; closing without flushing");
+                                    self.inner.state = State::Closed(Reason::NO_ERROR);
+                                    continue;
+                                }
+                                Poll::Pending => return Poll::Pending,
+                            }
 
                             if (self.inner.error.is_some()
                                 || self.inner.go_away.should_close_on_idle())
@@ -262,6 +500,16 @@ where
                                 continue;
                             }
 
+                            if let Some(ref mut idle_timeout) = self.inner.idle_timeout {
+                                let is_idle = !self.inner.streams.has_streams();
+                                if idle_timeout.poll_expired(cx, is_idle) {
+                                    tracing::debug!("connection idle timeout elapsed; going away");
+                                    self.inner.idle_timeout = None;
+                                    self.inner.as_dyn().go_away_now(Reason::NO_ERROR);
+                                    continue;
+                                }
+                            }
+
                             return Poll::Pending;
                         }
                     };
@@ -271,7 +519,15 @@ where
                 State::Closing(reason) => {
                     tracing::trace!("connection closing after flush");
                     // Flush/shutdown the codec
-                    ready!(self.codec.shutdown(cx))?;
+                    match self.codec.shutdown(cx) {
+                        Poll::Ready(res) => res?,
+                        Poll::Pending if self.inner.is_shutdown_forced => {
+                            tracing::debug!(
+                                "graceful shutdown deadline elapsed; closing without flushing"
+                            );
+                        }
+                        Poll::Pending => return Poll::Pending,
+                    }
 
                     // Transition the state to error
                     self.inner.state = State::Closed(reason);
@@ -312,11 +568,13 @@ where
             }
             ready!(self.poll_ready(cx))?;
 
-            match self
-                .inner
-                .as_dyn()
-                .recv_frame(ready!(Pin::new(&mut self.codec).poll_next(cx)?))?
-            {
+            let frame = ready!(Pin::new(&mut self.codec).poll_next(cx)?);
+            if let Some(ref frame) = frame {
+                self.inner.ping_pong.record_read();
+                self.inner.rate_limits.recv_frame(frame)?;
+            }
+
+            match self.inner.as_dyn().recv_frame(frame)? {
                 ReceivedFrame::Settings(frame) => {
                     self.inner.settings.recv_settings(
                         frame,
@@ -349,6 +607,8 @@ where
             streams,
             error,
             ping_pong,
+            extensions,
+            alt_svc,
             ..
         } = self;
         let streams = streams.as_dyn();
@@ -358,6 +618,8 @@ where
             streams,
             error,
             ping_pong,
+            extensions,
+            alt_svc,
         }
     }
 }
@@ -367,20 +629,34 @@ where
     B: Buf,
 {
     fn go_away(&mut self, id: StreamId, e: Reason) {
-        let frame = frame::GoAway::new(id, e);
+        let frame = frame::GoAway::with_debug_data(id, e, self.go_away.debug_data().clone());
         self.streams.send_go_away(id);
         self.go_away.go_away(frame);
     }
 
+    /// Ends a graceful shutdown that ran out of time.
+    ///
+    /// Streams that are still open are reset, and unless it was already
+    /// sent, a GOAWAY tells the peer which streams were processed. The
+    /// connection then closes as soon as the resets are written.
+    fn cancel_remaining_streams(&mut self) {
+        self.streams.send_reset_all(Reason::CANCEL);
+
+        if !self.go_away.should_close_on_idle() {
+            let last_processed_id = self.streams.last_processed_id();
+            self.go_away(last_processed_id, Reason::NO_ERROR);
+        }
+    }
+
     fn go_away_now(&mut self, e: Reason) {
         let last_processed_id = self.streams.last_processed_id();
         let frame = frame::GoAway::new(last_processed_id, e);
         self.go_away.go_away_now(frame);
     }
 
-    fn go_away_from_user(&mut self, e: Reason) {
+    fn go_away_from_user(&mut self, e: Reason, debug_data: Bytes) {
         let last_processed_id = self.streams.last_processed_id();
-        let frame = frame::GoAway::new(last_processed_id, e);
+        let frame = frame::GoAway::with_debug_data(last_processed_id, e, debug_data);
         self.go_away.go_away_from_user(frame);
 
         // Notify all streams of reason we're abruptly closing.
@@ -450,6 +726,7 @@ where
             }
             Some(Data(frame)) => {
                 tracing::trace!(?frame, "recv DATA");
+                self.ping_pong.record_data(frame.payload().len());
                 self.streams.recv_data(frame)?;
             }
             Some(Reset(frame)) => {
@@ -471,7 +748,7 @@ where
                 // until they are all EOS. Once they are, State should
                 // transition to GoAway.
                 self.streams.recv_go_away(&frame)?;
-                *self.error = Some(frame.reason());
+                *self.error = Some(frame);
             }
             Some(Ping(frame)) => {
                 tracing::trace!(?frame, "recv PING");
@@ -492,7 +769,30 @@ where
             }
             Some(Priority(frame)) => {
                 tracing::trace!(?frame, "recv PRIORITY");
-                // TODO: handle
+                self.streams.recv_priority(frame);
+            }
+            Some(PriorityUpdate(frame)) => {
+                tracing::trace!(?frame, "recv PRIORITY_UPDATE");
+                self.streams.recv_priority_update(frame)?;
+            }
+            Some(Origin(frame)) => {
+                tracing::trace!(?frame, "recv ORIGIN");
+                self.streams.recv_origin(frame);
+            }
+            Some(AltSvc(frame)) => {
+                tracing::trace!(?frame, "recv ALTSVC");
+                self.alt_svc.recv_frame(frame);
+            }
+            Some(Extension(frame)) => {
+                tracing::trace!(?frame, "recv extension frame");
+                let id = frame.stream_id();
+                if !self.extensions.is_registered(&frame) {
+                    tracing::trace!("ignoring unknown frame; type={:#x}", frame.kind());
+                } else if !id.is_zero() && !self.streams.recv_extension_frame(id)? {
+                    tracing::trace!(?id, "ignoring extension frame for closed stream");
+                } else {
+                    self.extensions.recv_frame(frame)?;
+                }
             }
             None => {
                 tracing::trace!("codec closed");
@@ -529,29 +829,28 @@ where
         self.inner.streams.next_incoming()
     }
 
-    // Graceful shutdown only makes sense for server peers.
-    pub fn go_away_gracefully(&mut self) {
-        if self.inner.go_away.is_going_away() {
-            // No reason to start a new one.
-            return;
-        }
-
-        // According to http://httpwg.org/specs/rfc7540.html#GOAWAY:
-        //
-        // > A server that is attempting to gracefully shut down a connection
-        // > SHOULD send an initial GOAWAY frame with the last stream
-        // > identifier set to 2^31-1 and a NO_ERROR code. This signals to the
-        // > client that a shutdown is imminent and that initiating further
-        // > requests is prohibited. After allowing time for any in-flight
-        // > stream creation (at least one round-trip time), the server can
-        // > send another GOAWAY frame with an updated last stream identifier.
-        // > This ensures that a connection can be cleanly shut down without
-        // > losing requests.
-        self.inner.as_dyn().go_away(StreamId::MAX, Reason::NO_ERROR);
+    /// Applies the settings and request of an HTTP/1.1 `Upgrade: h2c`
+    /// request, which becomes stream 1, half-closed (remote).
+    pub(crate) fn recv_upgrade(
+        &mut self,
+        settings: &frame::Settings,
+        request: frame::Headers,
+    ) -> Result<(), RecvError> {
+        debug_assert!(request.is_end_stream());
+
+        self.inner.settings.recv_upgrade_settings(
+            settings,
+            &mut self.codec,
+            &mut self.inner.streams,
+        )?;
+        self.inner.as_dyn().streams.recv_headers(request)
+    }
 
-        // We take the advice of waiting 1 RTT literally, and wait
-        // for a pong before proceeding.
-        self.inner.ping_pong.ping_shutdown();
+    /// Like `go_away_gracefully`, but streams that are still open once
+    /// `timeout` elapses are cancelled.
+    pub fn go_away_gracefully_with_timeout(&mut self, timeout: Duration) {
+        self.go_away_gracefully();
+        self.inner.shutdown_deadline = Some(Box::pin(sleep(timeout)));
     }
 }
 
@@ -1,5 +1,6 @@
 use crate::codec::{RecvError, SendError};
-use crate::frame::Reason;
+use crate::error::{KeepAliveTimedOut, RequestTimedOut};
+use crate::frame::{self, Reason};
 
 use std::io;
 
@@ -7,6 +8,16 @@ use std::io;
 #[derive(Debug)]
 pub enum Error {
     Proto(Reason),
+    /// A RST_STREAM frame received from the peer.
+    Reset(Reason),
+    /// A GOAWAY frame received from the peer.
+    ///
+    /// `unprocessed` is set when the error is reported for a stream that the
+    /// peer did not process, i.e. one above the frame's last stream ID.
+    GoAway {
+        frame: frame::GoAway,
+        unprocessed: bool,
+    },
     Io(io::Error),
 }
 
@@ -17,6 +28,16 @@ impl Error {
     pub(super) fn shallow_clone(&self) -> Error {
         match *self {
             Error::Proto(reason) => Error::Proto(reason),
+            Error::Reset(reason) => Error::Reset(reason),
+            Error::GoAway {
+                ref frame,
+                unprocessed,
+            } => Error::GoAway {
+                frame: frame.clone(),
+                unprocessed,
+            },
+            Error::Io(ref io) if KeepAliveTimedOut::is(io) => Error::Io(KeepAliveTimedOut.into()),
+            Error::Io(ref io) if RequestTimedOut::is(io) => Error::Io(RequestTimedOut.into()),
             Error::Io(ref io) => Error::Io(io::Error::from(io.kind())),
         }
     }
@@ -28,6 +49,15 @@ impl From<Reason> for Error {
     }
 }
 
+impl From<frame::GoAway> for Error {
+    fn from(src: frame::GoAway) -> Self {
+        Error::GoAway {
+            frame: src,
+            unprocessed: false,
+        }
+    }
+}
+
 impl From<io::Error> for Error {
     fn from(src: io::Error) -> Self {
         Error::Io(src)
@@ -38,6 +68,8 @@ impl From<Error> for RecvError {
     fn from(src: Error) -> RecvError {
         match src {
             Error::Proto(reason) => RecvError::Connection(reason),
+            Error::Reset(reason) => RecvError::Connection(reason),
+            Error::GoAway { frame, .. } => RecvError::Connection(frame.reason()),
             Error::Io(e) => RecvError::Io(e),
         }
     }
@@ -47,6 +79,8 @@ impl From<Error> for SendError {
     fn from(src: Error) -> SendError {
         match src {
             Error::Proto(reason) => SendError::Connection(reason),
+            Error::Reset(reason) => SendError::Connection(reason),
+            Error::GoAway { frame, .. } => SendError::Connection(frame.reason()),
             Error::Io(e) => SendError::Io(e),
         }
     }
@@ -0,0 +1,170 @@
+use super::frame_queue::{FrameQueue, PushError};
+use crate::codec::{Codec, RecvError};
+use crate::frame::{self, Reason};
+
+use bytes::Buf;
+use std::io;
+use std::task::{Context, Poll};
+use tokio::io::AsyncWrite;
+
+/// Passes extension frames between the connection and the user.
+#[derive(Debug)]
+pub(crate) struct Extensions {
+    /// Frame types registered by the user. Frames of any other unknown type
+    /// are ignored, as RFC 7540 requires.
+    types: Vec<u8>,
+
+    /// Maximum number of frames queued in each direction.
+    max_queued: usize,
+
+    user: Option<Queues>,
+}
+
+#[derive(Debug)]
+pub(crate) struct UserExtensions(Queues);
+
+#[derive(Debug)]
+struct Queues {
+    /// Frames received from the peer, waiting for the user.
+    recv: FrameQueue<frame::Extension>,
+
+    /// Frames queued by the user, waiting to be written.
+    send: FrameQueue<frame::Extension>,
+}
+
+// ===== impl Extensions =====
+
+impl Extensions {
+    pub(crate) fn new(types: Vec<u8>, max_queued: usize) -> Self {
+        Extensions {
+            types,
+            max_queued,
+            user: None,
+        }
+    }
+
+    pub(crate) fn take_user(&mut self) -> Option<UserExtensions> {
+        if self.user.is_some() {
+            return None;
+        }
+
+        let queues = Queues {
+            recv: FrameQueue::new(self.max_queued),
+            send: FrameQueue::new(self.max_queued),
+        };
+        let user = Queues {
+            recv: queues.recv.clone(),
+            send: queues.send.clone(),
+        };
+        self.user = Some(queues);
+        Some(UserExtensions(user))
+    }
+
+    /// Returns `true` if the user registered the frame's type.
+    pub(crate) fn is_registered(&self, frame: &frame::Extension) -> bool {
+        self.types.contains(&frame.kind())
+    }
+
+    /// Queues a frame of a registered type for the user.
+    ///
+    /// If the user lets too many frames pile up, the peer is sending them
+    /// faster than they are handled, and the connection is closed with
+    /// `ENHANCE_YOUR_CALM`.
+    pub(crate) fn recv_frame(&mut self, frame: frame::Extension) -> Result<(), RecvError> {
+        debug_assert!(self.is_registered(&frame));
+
+        let user = match self.user {
+            Some(ref user) => user,
+            None => {
+                tracing::trace!("no handle to receive extension frame; dropping");
+                return Ok(());
+            }
+        };
+
+        match user.recv.push(frame) {
+            Ok(()) => Ok(()),
+            Err(PushError::Closed) => {
+                tracing::trace!("extension frame handle dropped; dropping");
+                Ok(())
+            }
+            Err(PushError::Full) => {
+                tracing::warn!(
+                    "recv_frame; queued extension frames reached limit ({:?})",
+                    self.max_queued,
+                );
+                Err(RecvError::Connection(Reason::ENHANCE_YOUR_CALM))
+            }
+        }
+    }
+
+    /// Writes the extension frames queued by the user.
+    pub(crate) fn send_pending<T, B>(
+        &mut self,
+        cx: &mut Context,
+        dst: &mut Codec<T, B>,
+    ) -> Poll<io::Result<()>>
+    where
+        T: AsyncWrite + Unpin,
+        B: Buf,
+    {
+        let user = match self.user {
+            Some(ref user) => user,
+            None => return Poll::Ready(Ok(())),
+        };
+
+        loop {
+            // The codec is only ready once any CONTINUATION frames of a
+            // header block have been written, so an extension frame never
+            // ends up in the middle of one.
+            if !user.send.is_empty() && !dst.poll_ready(cx)?.is_ready() {
+                return Poll::Pending;
+            }
+
+            let frame = match user.send.poll_pop(cx) {
+                Poll::Ready(Some(frame)) => frame,
+                _ => return Poll::Ready(Ok(())),
+            };
+
+            dst.buffer(frame.into()).expect("invalid extension frame");
+        }
+    }
+}
+
+impl Drop for Extensions {
+    fn drop(&mut self) {
+        if let Some(ref user) = self.user {
+            user.close();
+        }
+    }
+}
+
+// ===== impl UserExtensions =====
+
+impl UserExtensions {
+    pub(crate) fn poll_ready(&self, cx: &mut Context) -> Poll<Result<(), PushError>> {
+        self.0.send.poll_push_ready(cx)
+    }
+
+    pub(crate) fn send_frame(&self, frame: frame::Extension) -> Result<(), PushError> {
+        self.0.send.push(frame)
+    }
+
+    pub(crate) fn poll_recv(&self, cx: &mut Context) -> Poll<Option<frame::Extension>> {
+        self.0.recv.poll_pop(cx)
+    }
+}
+
+impl Drop for UserExtensions {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+// ===== impl Queues =====
+
+impl Queues {
+    fn close(&self) {
+        self.recv.close();
+        self.send.close();
+    }
+}
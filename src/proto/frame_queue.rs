@@ -0,0 +1,138 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// A bounded queue of frames passed between the connection and a user
+/// handle.
+///
+/// Both ends hold a clone of the queue. Once either end closes it, no more
+/// frames can be pushed, while frames that are already queued can still be
+/// popped.
+#[derive(Debug)]
+pub(crate) struct FrameQueue<T> {
+    inner: Arc<Mutex<Inner<T>>>,
+}
+
+#[derive(Debug)]
+struct Inner<T> {
+    frames: VecDeque<T>,
+
+    /// Maximum number of frames in `frames`.
+    max_len: usize,
+
+    /// Task waiting for a frame to pop.
+    pop_task: Option<Waker>,
+
+    /// Task waiting for room to push a frame.
+    push_task: Option<Waker>,
+
+    /// Set once either end is gone.
+    is_closed: bool,
+}
+
+/// Why a frame could not be pushed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum PushError {
+    /// The queue already holds `max_len` frames.
+    Full,
+
+    /// The other end is gone.
+    Closed,
+}
+
+// ===== impl FrameQueue =====
+
+impl<T> FrameQueue<T> {
+    pub fn new(max_len: usize) -> Self {
+        FrameQueue {
+            inner: Arc::new(Mutex::new(Inner {
+                frames: VecDeque::new(),
+                max_len,
+                pop_task: None,
+                push_task: None,
+                is_closed: false,
+            })),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().unwrap().frames.is_empty()
+    }
+
+    /// Queues a frame, waking the task waiting to pop it.
+    pub fn push(&self, frame: T) -> Result<(), PushError> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.is_closed {
+            return Err(PushError::Closed);
+        }
+
+        if inner.frames.len() >= inner.max_len {
+            return Err(PushError::Full);
+        }
+
+        inner.frames.push_back(frame);
+        if let Some(task) = inner.pop_task.take() {
+            task.wake();
+        }
+
+        Ok(())
+    }
+
+    /// Polls for room to push a frame.
+    pub fn poll_push_ready(&self, cx: &mut Context) -> Poll<Result<(), PushError>> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.is_closed {
+            return Poll::Ready(Err(PushError::Closed));
+        }
+
+        if inner.frames.len() < inner.max_len {
+            return Poll::Ready(Ok(()));
+        }
+
+        inner.push_task = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// Pops the next frame, or returns `None` once the queue is empty and
+    /// closed.
+    pub fn poll_pop(&self, cx: &mut Context) -> Poll<Option<T>> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if let Some(frame) = inner.frames.pop_front() {
+            if let Some(task) = inner.push_task.take() {
+                task.wake();
+            }
+            return Poll::Ready(Some(frame));
+        }
+
+        if inner.is_closed {
+            return Poll::Ready(None);
+        }
+
+        inner.pop_task = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// Closes the queue, waking the tasks of both ends.
+    pub fn close(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.is_closed = true;
+
+        if let Some(task) = inner.pop_task.take() {
+            task.wake();
+        }
+        if let Some(task) = inner.push_task.take() {
+            task.wake();
+        }
+    }
+}
+
+impl<T> Clone for FrameQueue<T> {
+    fn clone(&self) -> Self {
+        FrameQueue {
+            inner: self.inner.clone(),
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use crate::codec::Codec;
 use crate::frame::{self, Reason, StreamId};
 
-use bytes::Buf;
+use bytes::{Buf, Bytes};
 use std::io;
 use std::task::{Context, Poll};
 use tokio::io::AsyncWrite;
@@ -17,6 +17,9 @@ pub(super) struct GoAway {
     is_user_initiated: bool,
     /// A GOAWAY frame that must be buffered in the Codec immediately.
     pending: Option<frame::GoAway>,
+    /// Opaque debug data sent in the GOAWAY frames of a graceful shutdown
+    /// started by the user.
+    debug_data: Bytes,
 }
 
 /// Keeps a memory of any GOAWAY frames we've sent before.
@@ -27,9 +30,9 @@ pub(super) struct GoAway {
 /// serialize it. We **only** want to be able to look up these fields at a
 /// later time.
 ///
-/// (Technically, `frame::GoAway` should gain an opaque_debug_data field as
-/// well, and we wouldn't want to save that here to accidentally dump in logs,
-/// or waste struct space.)
+/// (Technically, `frame::GoAway` also has an opaque debug_data field, and we
+/// wouldn't want to save that here to accidentally dump in logs, or waste
+/// struct space.)
 #[derive(Debug)]
 struct GoingAway {
     /// Stores the highest stream ID of a GOAWAY that has been sent.
@@ -48,9 +51,18 @@ impl GoAway {
             going_away: None,
             is_user_initiated: false,
             pending: None,
+            debug_data: Bytes::new(),
         }
     }
 
+    pub fn set_debug_data(&mut self, debug_data: Bytes) {
+        self.debug_data = debug_data;
+    }
+
+    pub fn debug_data(&self) -> &Bytes {
+        &self.debug_data
+    }
+
     /// Enqueue a GOAWAY frame to be written.
     ///
     /// The connection is expected to continue to run until idle.
@@ -130,12 +142,16 @@ impl GoAway {
         T: AsyncWrite + Unpin,
         B: Buf,
     {
-        if let Some(frame) = self.pending.take() {
+        if let Some(mut frame) = self.pending.take() {
             if !dst.poll_ready(cx)?.is_ready() {
                 self.pending = Some(frame);
                 return Poll::Pending;
             }
 
+            // The debug data must fit in a single frame, after the last stream
+            // ID and the error code.
+            frame.truncate_debug_data(dst.max_send_frame_size() - 8);
+
             let reason = frame.reason();
             dst.buffer(frame.into()).expect("invalid GOAWAY frame");
 
@@ -0,0 +1,39 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Context;
+use std::time::Duration;
+use tokio::time::{sleep, Sleep};
+
+/// Closes the connection once it has had no active streams for a while.
+#[derive(Debug)]
+pub(crate) struct IdleTimeout {
+    /// How long the connection may go without active streams.
+    duration: Duration,
+
+    /// Armed while the connection has no active streams.
+    timer: Option<Pin<Box<Sleep>>>,
+}
+
+impl IdleTimeout {
+    pub(crate) fn new(duration: Duration) -> Self {
+        IdleTimeout {
+            duration,
+            timer: None,
+        }
+    }
+
+    /// Returns `true` once the connection has been idle for the whole
+    /// duration.
+    ///
+    /// The timer starts over whenever the connection stops being idle.
+    pub(crate) fn poll_expired(&mut self, cx: &mut Context, is_idle: bool) -> bool {
+        if !is_idle {
+            self.timer = None;
+            return false;
+        }
+
+        let duration = self.duration;
+        let timer = self.timer.get_or_insert_with(|| Box::pin(sleep(duration)));
+        timer.as_mut().poll(cx).is_ready()
+    }
+}
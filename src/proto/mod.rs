@@ -1,13 +1,21 @@
+mod alt_svc;
 mod connection;
 mod error;
+mod extension;
+mod frame_queue;
 mod go_away;
+mod idle_timeout;
 mod peer;
 mod ping_pong;
+mod rate_limit;
 mod settings;
 mod streams;
 
+pub(crate) use self::alt_svc::UserAltSvcs;
 pub(crate) use self::connection::{Config, Connection};
 pub(crate) use self::error::Error;
+pub(crate) use self::extension::UserExtensions;
+pub(crate) use self::frame_queue::PushError;
 pub(crate) use self::peer::{Dyn as DynPeer, Peer};
 pub(crate) use self::ping_pong::UserPings;
 pub(crate) use self::streams::{DynStreams, OpaqueStreamRef, StreamRef, Streams};
@@ -15,8 +23,12 @@ pub(crate) use self::streams::{Open, PollReset, Prioritized};
 
 use crate::codec::Codec;
 
+use self::alt_svc::AltSvcs;
+use self::extension::Extensions;
 use self::go_away::GoAway;
+use self::idle_timeout::IdleTimeout;
 use self::ping_pong::PingPong;
+use self::rate_limit::RateLimits;
 use self::settings::Settings;
 
 use crate::frame::{self, Frame};
@@ -32,4 +44,10 @@ pub type WindowSize = u32;
 // Constants
 pub const MAX_WINDOW_SIZE: WindowSize = (1 << 31) - 1;
 pub const DEFAULT_RESET_STREAM_MAX: usize = 10;
+pub const DEFAULT_MAX_SEND_BUFFER_SIZE: usize = 1024 * 400;
+pub const DEFAULT_REMOTE_RESET_STREAM_MAX: usize = 20;
+pub const DEFAULT_MAX_CONTROL_FRAMES_PER_SECOND: usize = 10_000;
+pub const DEFAULT_MAX_EMPTY_DATA_FRAMES_PER_SECOND: usize = 100;
+pub const DEFAULT_MAX_QUEUED_EXTENSION_FRAMES: usize = 64;
 pub const DEFAULT_RESET_STREAM_SECS: u64 = 30;
+pub const DEFAULT_KEEP_ALIVE_TIMEOUT_SECS: u64 = 20;
@@ -1,14 +1,20 @@
 use crate::codec::Codec;
+use crate::error::KeepAliveTimedOut;
 use crate::frame::Ping;
-use crate::proto::{self, PingPayload};
+use crate::proto::{self, PingPayload, WindowSize};
 
 use bytes::Buf;
 use futures_util::task::AtomicWaker;
+use std::cmp;
+use std::future::Future;
 use std::io;
+use std::pin::Pin;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 use tokio::io::AsyncWrite;
+use tokio::time::{Instant, Sleep};
 
 /// Acknowledges ping requests from the remote.
 #[derive(Debug)]
@@ -16,6 +22,77 @@ pub(crate) struct PingPong {
     pending_ping: Option<PendingPing>,
     pending_pong: Option<PingPayload>,
     user_pings: Option<UserPingsRx>,
+    keep_alive: Option<KeepAlive>,
+    bdp: Option<Bdp>,
+}
+
+/// Periodically pings the remote to detect a dead connection.
+#[derive(Debug)]
+struct KeepAlive {
+    /// How long the connection may go without receiving a frame before a
+    /// PING is sent.
+    interval: Duration,
+
+    /// How long to wait for the PING to be acknowledged.
+    timeout: Duration,
+
+    /// If PINGs should be sent when there are no active streams.
+    while_idle: bool,
+
+    state: KeepAliveState,
+
+    /// Set when a PING must be written.
+    ping_pending: bool,
+
+    /// When the last frame was received from the remote.
+    last_read_at: Instant,
+
+    timer: Pin<Box<Sleep>>,
+}
+
+/// Estimates the bandwidth-delay product of the connection by timing PING
+/// round trips, so that receive windows can grow to keep the pipe full.
+#[derive(Debug)]
+struct Bdp {
+    /// Current BDP estimate, in bytes.
+    bdp: WindowSize,
+
+    /// Largest bandwidth sample seen so far, in bytes per second.
+    max_bandwidth: f64,
+
+    /// Smoothed round trip time, in seconds.
+    rtt: f64,
+
+    /// DATA bytes received since the BDP PING was queued.
+    bytes: usize,
+
+    /// Set when a PING must be written.
+    ping_pending: bool,
+
+    /// When the outstanding BDP PING was queued, if any.
+    ping_sent_at: Option<Instant>,
+
+    /// How long to wait between samples once the estimate is stable.
+    ping_delay: Duration,
+
+    /// Number of consecutive samples that did not change the estimate.
+    stable_count: u32,
+
+    /// Samples are not taken before this instant.
+    next_sample_at: Option<Instant>,
+
+    /// A grown window size that has not been applied yet.
+    window_update: Option<WindowSize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeepAliveState {
+    /// The timer is not armed.
+    Init,
+    /// Waiting for the interval to elapse.
+    Scheduled,
+    /// A PING has been sent, waiting for the acknowledgement.
+    PingSent,
 }
 
 #[derive(Debug)]
@@ -58,6 +135,13 @@ const USER_STATE_RECEIVED_PONG: usize = 3;
 /// The connection is closed.
 const USER_STATE_CLOSED: usize = 4;
 
+/// The receive window never grows beyond this size.
+const BDP_LIMIT: WindowSize = 16 * 1024 * 1024;
+/// Delay between samples when the estimate first stabilizes.
+const BDP_INITIAL_PING_DELAY: Duration = Duration::from_millis(100);
+/// Once the delay between samples reaches this, it stops growing.
+const BDP_MAX_PING_DELAY: Duration = Duration::from_secs(10);
+
 // ===== impl PingPong =====
 
 impl PingPong {
@@ -66,6 +150,89 @@ impl PingPong {
             pending_ping: None,
             pending_pong: None,
             user_pings: None,
+            keep_alive: None,
+            bdp: None,
+        }
+    }
+
+    /// Enables growing the receive windows based on the estimated
+    /// bandwidth-delay product, starting from `initial` bytes.
+    pub(crate) fn set_adaptive_window(&mut self, initial: WindowSize) {
+        self.bdp = Some(Bdp {
+            bdp: initial,
+            max_bandwidth: 0.0,
+            rtt: 0.0,
+            bytes: 0,
+            ping_pending: false,
+            ping_sent_at: None,
+            ping_delay: BDP_INITIAL_PING_DELAY,
+            stable_count: 0,
+            next_sample_at: None,
+            window_update: None,
+        });
+    }
+
+    /// Records that `len` bytes of DATA were received, possibly starting a
+    /// new BDP sample.
+    pub(crate) fn record_data(&mut self, len: usize) {
+        if let Some(ref mut bdp) = self.bdp {
+            bdp.record_data(len);
+        }
+    }
+
+    /// Raises the BDP estimate to at least `size`, so that it only grows the
+    /// receive windows once it exceeds the configured connection window.
+    pub(crate) fn raise_adaptive_window(&mut self, size: WindowSize) {
+        if let Some(ref mut bdp) = self.bdp {
+            bdp.bdp = cmp::max(bdp.bdp, size);
+        }
+    }
+
+    /// Takes the new BDP estimate, if it has grown since the last call.
+    pub(crate) fn take_window_update(&mut self) -> Option<WindowSize> {
+        self.bdp.as_mut().and_then(|bdp| bdp.window_update.take())
+    }
+
+    /// Enables sending keep-alive PINGs.
+    pub(crate) fn set_keep_alive(
+        &mut self,
+        interval: Duration,
+        timeout: Duration,
+        while_idle: bool,
+    ) {
+        self.keep_alive = Some(KeepAlive {
+            interval,
+            timeout,
+            while_idle,
+            state: KeepAliveState::Init,
+            ping_pending: false,
+            last_read_at: Instant::now(),
+            timer: Box::pin(tokio::time::sleep(interval)),
+        });
+    }
+
+    /// Records that a frame was received from the remote, which postpones the
+    /// next keep-alive PING.
+    pub(crate) fn record_read(&mut self) {
+        if let Some(ref mut keep_alive) = self.keep_alive {
+            keep_alive.last_read_at = Instant::now();
+        }
+    }
+
+    /// Drives the keep-alive timer.
+    ///
+    /// Returns an error if a keep-alive PING was not acknowledged in time.
+    pub(crate) fn poll_keep_alive<F>(
+        &mut self,
+        cx: &mut Context,
+        is_idle: F,
+    ) -> Result<(), KeepAliveTimedOut>
+    where
+        F: FnOnce() -> bool,
+    {
+        match self.keep_alive {
+            Some(ref mut keep_alive) => keep_alive.poll(cx, is_idle()),
+            None => Ok(()),
         }
     }
 
@@ -94,6 +261,10 @@ impl PingPong {
     }
 
     /// Process a ping
+    ///
+    /// PING floods are stopped by the connection's rate limits; the peer
+    /// cannot make acknowledgements pile up, since frames are only read once
+    /// the previous acknowledgement was buffered.
     pub(crate) fn recv_ping(&mut self, ping: Ping) -> ReceivedPing {
         // The caller should always check that `send_pongs` returns ready before
         // calling `recv_ping`.
@@ -122,6 +293,20 @@ impl PingPong {
                 }
             }
 
+            if let Some(ref mut keep_alive) = self.keep_alive {
+                if ping.payload() == &Ping::KEEP_ALIVE && keep_alive.receive_pong() {
+                    tracing::trace!("recv PING KEEP_ALIVE ack");
+                    return ReceivedPing::Unknown;
+                }
+            }
+
+            if let Some(ref mut bdp) = self.bdp {
+                if ping.payload() == &Ping::BDP && bdp.receive_pong() {
+                    tracing::trace!("recv PING BDP ack");
+                    return ReceivedPing::Unknown;
+                }
+            }
+
             // else we were acked a ping we didn't send?
             // The spec doesn't require us to do anything about this,
             // so for resiliency, just ignore it for now.
@@ -167,6 +352,30 @@ impl PingPong {
         T: AsyncWrite + Unpin,
         B: Buf,
     {
+        if let Some(ref mut keep_alive) = self.keep_alive {
+            if keep_alive.ping_pending {
+                if !dst.poll_ready(cx)?.is_ready() {
+                    return Poll::Pending;
+                }
+
+                dst.buffer(Ping::new(Ping::KEEP_ALIVE).into())
+                    .expect("invalid ping frame");
+                keep_alive.ping_pending = false;
+            }
+        }
+
+        if let Some(ref mut bdp) = self.bdp {
+            if bdp.ping_pending {
+                if !dst.poll_ready(cx)?.is_ready() {
+                    return Poll::Pending;
+                }
+
+                dst.buffer(Ping::new(Ping::BDP).into())
+                    .expect("invalid ping frame");
+                bdp.ping_pending = false;
+            }
+        }
+
         if let Some(ref mut ping) = self.pending_ping {
             if !ping.sent {
                 if !dst.poll_ready(cx)?.is_ready() {
@@ -198,6 +407,143 @@ impl PingPong {
     }
 }
 
+// ===== impl KeepAlive =====
+
+impl KeepAlive {
+    fn poll(&mut self, cx: &mut Context, is_idle: bool) -> Result<(), KeepAliveTimedOut> {
+        loop {
+            match self.state {
+                KeepAliveState::Init => {
+                    if is_idle && !self.while_idle {
+                        return Ok(());
+                    }
+
+                    let deadline = self.last_read_at + self.interval;
+                    self.timer.as_mut().reset(deadline);
+                    self.state = KeepAliveState::Scheduled;
+                }
+                KeepAliveState::Scheduled => {
+                    if self.timer.as_mut().poll(cx).is_pending() {
+                        return Ok(());
+                    }
+
+                    // Frames may have been received since the timer was
+                    // armed, in which case there is no need to ping yet.
+                    let deadline = self.last_read_at + self.interval;
+                    if deadline > Instant::now() {
+                        self.timer.as_mut().reset(deadline);
+                        continue;
+                    }
+
+                    if is_idle && !self.while_idle {
+                        self.state = KeepAliveState::Init;
+                        return Ok(());
+                    }
+
+                    tracing::trace!("keep-alive interval elapsed; sending PING");
+                    self.ping_pending = true;
+                    self.state = KeepAliveState::PingSent;
+                    self.timer.as_mut().reset(Instant::now() + self.timeout);
+                }
+                KeepAliveState::PingSent => {
+                    if self.timer.as_mut().poll(cx).is_pending() {
+                        return Ok(());
+                    }
+
+                    tracing::debug!("keep-alive PING not acknowledged in time");
+                    return Err(KeepAliveTimedOut);
+                }
+            }
+        }
+    }
+
+    fn receive_pong(&mut self) -> bool {
+        if self.state != KeepAliveState::PingSent {
+            return false;
+        }
+
+        self.state = KeepAliveState::Init;
+        true
+    }
+}
+
+// ===== impl Bdp =====
+
+impl Bdp {
+    fn record_data(&mut self, len: usize) {
+        if let Some(next) = self.next_sample_at {
+            if Instant::now() < next {
+                return;
+            }
+            self.next_sample_at = None;
+        }
+
+        self.bytes += len;
+
+        if self.ping_sent_at.is_none() {
+            tracing::trace!("starting BDP sample");
+            self.ping_pending = true;
+            self.ping_sent_at = Some(Instant::now());
+        }
+    }
+
+    fn receive_pong(&mut self) -> bool {
+        let sent_at = match self.ping_sent_at.take() {
+            Some(sent_at) => sent_at,
+            None => return false,
+        };
+        let bytes = std::mem::replace(&mut self.bytes, 0);
+
+        let sample = sent_at.elapsed().as_secs_f64();
+        if self.rtt == 0.0 {
+            self.rtt = sample;
+        } else {
+            self.rtt += (sample - self.rtt) * 0.125;
+        }
+
+        // Allow for the remote taking a while to acknowledge the PING.
+        let bandwidth = bytes as f64 / (self.rtt * 1.5);
+        tracing::trace!(
+            bytes,
+            rtt = self.rtt,
+            bandwidth,
+            bdp = self.bdp,
+            "BDP sample"
+        );
+
+        if bandwidth < self.max_bandwidth {
+            self.stabilize();
+            return true;
+        }
+        self.max_bandwidth = bandwidth;
+
+        // If the peer came close to filling the window during a round trip,
+        // the window is what is holding it back.
+        if bytes >= self.bdp as usize * 2 / 3 && self.bdp < BDP_LIMIT {
+            self.bdp = (bytes * 2).min(BDP_LIMIT as usize) as WindowSize;
+            tracing::debug!(window = self.bdp, "BDP estimate grew");
+            self.window_update = Some(self.bdp);
+            self.stable_count = 0;
+        } else {
+            self.stabilize();
+        }
+
+        true
+    }
+
+    /// Backs off sampling while the estimate isn't changing.
+    fn stabilize(&mut self) {
+        if self.ping_delay < BDP_MAX_PING_DELAY {
+            self.stable_count += 1;
+            if self.stable_count >= 2 {
+                self.ping_delay = (self.ping_delay * 4).min(BDP_MAX_PING_DELAY);
+                self.stable_count = 0;
+            }
+        }
+        self.next_sample_at = Some(Instant::now() + self.ping_delay);
+    }
+}
+
 impl ReceivedPing {
     pub(crate) fn is_shutdown(&self) -> bool {
         match *self {
@@ -0,0 +1,91 @@
+use crate::codec::RecvError;
+use crate::frame::{Frame, Reason};
+
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Frames are counted over windows of this length.
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Limits the rate of inbound frames that are cheap for the peer to send but
+/// cost us work, such as PINGs and SETTINGS that must be acknowledged.
+#[derive(Debug)]
+pub(crate) struct RateLimits {
+    /// Maximum number of frames of each control frame type per window.
+    max_control_frames: usize,
+
+    /// Maximum number of empty DATA frames per window.
+    max_empty_data_frames: usize,
+
+    /// When the current window started, if any frame was counted yet.
+    window_start: Option<Instant>,
+
+    /// Number of frames of each type received in the current window.
+    counts: Counts,
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    ping: usize,
+    settings: usize,
+    priority: usize,
+    window_update: usize,
+    empty_data: usize,
+}
+
+impl RateLimits {
+    pub(crate) fn new(max_control_frames: usize, max_empty_data_frames: usize) -> Self {
+        RateLimits {
+            max_control_frames,
+            max_empty_data_frames,
+            window_start: None,
+            counts: Counts::default(),
+        }
+    }
+
+    /// Counts a received frame, returning an error if the peer is sending
+    /// frames of its type faster than allowed.
+    pub(crate) fn recv_frame(&mut self, frame: &Frame) -> Result<(), RecvError> {
+        let (kind, max) = match *frame {
+            // Only requests are limited, acknowledgements answer frames we
+            // sent ourselves.
+            Frame::Ping(ref ping) if !ping.is_ack() => ("PING", self.max_control_frames),
+            Frame::Settings(ref settings) if !settings.is_ack() => {
+                ("SETTINGS", self.max_control_frames)
+            }
+            Frame::Priority(_) => ("PRIORITY", self.max_control_frames),
+            Frame::WindowUpdate(_) => ("WINDOW_UPDATE", self.max_control_frames),
+            // Empty DATA frames don't consume any flow control, and unless
+            // they end the stream, they don't do anything either.
+            Frame::Data(ref data) if data.payload().is_empty() && !data.is_end_stream() => {
+                ("empty DATA", self.max_empty_data_frames)
+            }
+            _ => return Ok(()),
+        };
+
+        let now = Instant::now();
+        match self.window_start {
+            Some(start) if now.saturating_duration_since(start) < WINDOW => {}
+            _ => {
+                self.window_start = Some(now);
+                self.counts = Counts::default();
+            }
+        }
+
+        let count = match *frame {
+            Frame::Ping(_) => &mut self.counts.ping,
+            Frame::Settings(_) => &mut self.counts.settings,
+            Frame::Priority(_) => &mut self.counts.priority,
+            Frame::WindowUpdate(_) => &mut self.counts.window_update,
+            _ => &mut self.counts.empty_data,
+        };
+        *count += 1;
+
+        if *count > max {
+            proto_err!(conn: "too many {} frames; limit={} per second", kind, max);
+            return Err(RecvError::Connection(Reason::ENHANCE_YOUR_CALM));
+        }
+
+        Ok(())
+    }
+}
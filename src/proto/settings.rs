@@ -60,6 +60,10 @@ impl Settings {
                         codec.set_max_recv_header_list_size(max as usize);
                     }
 
+                    if let Some(val) = local.header_table_size() {
+                        codec.set_recv_header_table_size(val as usize);
+                    }
+
                     streams.apply_local_settings(local)?;
                     self.local = Local::Synced;
                     Ok(())
@@ -80,6 +84,48 @@ impl Settings {
         }
     }
 
+    /// Applies the settings received in the `HTTP2-Settings` header of an
+    /// HTTP/1.1 upgrade request.
+    ///
+    /// The 101 response acknowledges them, so no SETTINGS ACK is sent.
+    pub(crate) fn recv_upgrade_settings<T, B, C, P>(
+        &mut self,
+        settings: &frame::Settings,
+        codec: &mut Codec<T, B>,
+        streams: &mut Streams<C, P>,
+    ) -> Result<(), RecvError>
+    where
+        T: AsyncWrite + Unpin,
+        B: Buf,
+        C: Buf,
+        P: Peer,
+    {
+        tracing::debug!("applying upgrade settings {:?}", settings);
+        Self::apply_remote_settings(settings, codec, streams)
+    }
+
+    fn apply_remote_settings<T, B, C, P>(
+        settings: &frame::Settings,
+        codec: &mut Codec<T, B>,
+        streams: &mut Streams<C, P>,
+    ) -> Result<(), RecvError>
+    where
+        T: AsyncWrite + Unpin,
+        B: Buf,
+        C: Buf,
+        P: Peer,
+    {
+        if let Some(val) = settings.header_table_size() {
+            codec.set_send_header_table_size(val as usize);
+        }
+
+        if let Some(val) = settings.max_frame_size() {
+            codec.set_max_send_frame_size(val as usize);
+        }
+
+        streams.apply_remote_settings(settings)
+    }
+
     pub(crate) fn send_settings(&mut self, frame: frame::Settings) -> Result<(), UserError> {
         assert!(!frame.is_ack());
         match &self.local {
@@ -92,6 +138,15 @@ impl Settings {
         }
     }
 
+    /// Returns `true` if no local SETTINGS are waiting to be sent or
+    /// acknowledged.
+    pub(crate) fn is_synced(&self) -> bool {
+        match self.local {
+            Local::Synced => true,
+            Local::ToSend(..) | Local::WaitingAck(..) => false,
+        }
+    }
+
     pub(crate) fn poll_send<T, B, C, P>(
         &mut self,
         cx: &mut Context,
@@ -117,15 +172,7 @@ impl Settings {
 
             tracing::trace!("ACK sent; applying settings");
 
-            if let Some(val) = settings.header_table_size() {
-                dst.set_send_header_table_size(val as usize);
-            }
-
-            if let Some(val) = settings.max_frame_size() {
-                dst.set_max_send_frame_size(val as usize);
-            }
-
-            streams.apply_remote_settings(settings)?;
+            Self::apply_remote_settings(settings, dst, streams)?;
         }
 
         self.remote = None;
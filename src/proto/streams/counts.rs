@@ -1,4 +1,5 @@
 use super::*;
+use crate::ConnectionStats;
 
 use std::usize;
 
@@ -25,6 +26,27 @@ pub(super) struct Counts {
 
     /// Current number of pending locally reset streams
     num_reset_streams: usize,
+
+    /// Maximum number of remotely reset streams that have not been accepted
+    /// yet
+    max_remote_reset_streams: usize,
+
+    /// Current number of remotely reset streams that have not been accepted
+    /// yet
+    num_remote_reset_streams: usize,
+
+    /// Totals since the connection was created, for `ConnectionStats`
+    stats: Stats,
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    local_opened: u64,
+    remote_opened: u64,
+    local_closed: u64,
+    remote_closed: u64,
+    reset_locally: u64,
+    reset_remotely: u64,
 }
 
 impl Counts {
@@ -38,6 +60,9 @@ impl Counts {
             num_recv_streams: 0,
             max_reset_streams: config.local_reset_max,
             num_reset_streams: 0,
+            max_remote_reset_streams: config.remote_reset_max,
+            num_remote_reset_streams: 0,
+            stats: Stats::default(),
         }
     }
 
@@ -66,9 +91,15 @@ impl Counts {
 
         // Increment the number of remote initiated streams
         self.num_recv_streams += 1;
+        self.stats.remote_opened += 1;
         stream.is_counted = true;
     }
 
+    /// Returns the number of active locally initiated streams.
+    pub fn num_send_streams(&self) -> usize {
+        self.num_send_streams
+    }
+
     /// Returns true if the send stream concurrency can be incremented
     pub fn can_inc_num_send_streams(&self) -> bool {
         self.max_send_streams > self.num_send_streams
@@ -85,6 +116,7 @@ impl Counts {
 
         // Increment the number of remote initiated streams
         self.num_send_streams += 1;
+        self.stats.local_opened += 1;
         stream.is_counted = true;
     }
 
@@ -104,6 +136,52 @@ impl Counts {
         self.num_reset_streams += 1;
     }
 
+    /// Returns true if the number of remotely reset streams that have not
+    /// been accepted yet can be incremented.
+    pub fn can_inc_num_remote_reset_streams(&self) -> bool {
+        self.max_remote_reset_streams > self.num_remote_reset_streams
+    }
+
+    /// Increments the number of remotely reset streams that have not been
+    /// accepted yet.
+    ///
+    /// # Panics
+    ///
+    /// Panics on failure as this should have been validated before hand.
+    pub fn inc_num_remote_reset_streams(&mut self) {
+        assert!(self.can_inc_num_remote_reset_streams());
+
+        self.num_remote_reset_streams += 1;
+    }
+
+    pub fn dec_num_remote_reset_streams(&mut self) {
+        assert!(self.num_remote_reset_streams > 0);
+        self.num_remote_reset_streams -= 1;
+    }
+
+    pub(crate) fn max_remote_reset_streams(&self) -> usize {
+        self.max_remote_reset_streams
+    }
+
+    /// Records that a stream was reset by this endpoint.
+    pub fn inc_num_local_resets(&mut self) {
+        self.stats.reset_locally += 1;
+    }
+
+    /// Records that a stream was reset by the peer.
+    pub fn inc_num_remote_resets(&mut self) {
+        self.stats.reset_remotely += 1;
+    }
+
+    pub(crate) fn fill_stats(&self, dst: &mut ConnectionStats) {
+        dst.local_streams_opened = self.stats.local_opened;
+        dst.remote_streams_opened = self.stats.remote_opened;
+        dst.local_streams_closed = self.stats.local_closed;
+        dst.remote_streams_closed = self.stats.remote_closed;
+        dst.streams_reset_locally = self.stats.reset_locally;
+        dst.streams_reset_remotely = self.stats.reset_remotely;
+    }
+
     pub fn apply_remote_settings(&mut self, settings: &frame::Settings) {
         if let Some(val) = settings.max_concurrent_streams() {
             self.max_send_streams = val as usize;
@@ -185,10 +263,12 @@ impl Counts {
         if self.peer.is_local_init(stream.id) {
             assert!(self.num_send_streams > 0);
             self.num_send_streams -= 1;
+            self.stats.local_closed += 1;
             stream.is_counted = false;
         } else {
             assert!(self.num_recv_streams > 0);
             self.num_recv_streams -= 1;
+            self.stats.remote_closed += 1;
             stream.is_counted = false;
         }
     }
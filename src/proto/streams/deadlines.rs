@@ -0,0 +1,114 @@
+use crate::frame::StreamId;
+
+use std::cmp::{self, Reverse};
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Context;
+use tokio::time::{sleep_until, Instant, Sleep};
+
+/// Deadlines of completed streams are never swept while there are fewer
+/// than this many.
+const MIN_SWEEP_LEN: usize = 32;
+
+/// Tracks the deadlines of requests that were sent with a timeout.
+#[derive(Debug, Default)]
+pub(super) struct Deadlines {
+    /// Deadlines that have not elapsed yet, earliest first.
+    ///
+    /// Streams that complete before their deadline are forgotten once it
+    /// elapses, or when the heap is swept.
+    pending: BinaryHeap<Reverse<(Instant, StreamId)>>,
+
+    /// The heap is swept once it grows past this length.
+    sweep_len: usize,
+
+    /// Armed for the earliest pending deadline.
+    timer: Option<Pin<Box<Sleep>>>,
+}
+
+impl Deadlines {
+    pub fn push(&mut self, id: StreamId, deadline: Instant) {
+        self.pending.push(Reverse((deadline, id)));
+    }
+
+    /// Returns the streams whose deadline has elapsed.
+    ///
+    /// Streams for which `is_active` returns `false` are forgotten. The timer
+    /// is armed for the earliest remaining deadline.
+    pub fn poll_expired<F>(&mut self, cx: &mut Context, mut is_active: F) -> Vec<StreamId>
+    where
+        F: FnMut(StreamId) -> bool,
+    {
+        // The heap is only swept once it has doubled in size since the last
+        // sweep, so completed streams don't pile up, and polling stays cheap.
+        if self.pending.len() > cmp::max(self.sweep_len, MIN_SWEEP_LEN) {
+            let mut pending = std::mem::take(&mut self.pending).into_vec();
+            pending.retain(|&Reverse((_, id))| is_active(id));
+            self.pending = BinaryHeap::from(pending);
+            self.sweep_len = 2 * self.pending.len();
+        }
+
+        let mut expired = Vec::new();
+
+        loop {
+            let now = Instant::now();
+            while let Some(&Reverse((deadline, id))) = self.pending.peek() {
+                if deadline > now {
+                    break;
+                }
+
+                self.pending.pop();
+                if is_active(id) {
+                    expired.push(id);
+                }
+            }
+
+            let next = match self.pending.peek() {
+                Some(&Reverse((next, _))) => next,
+                None => {
+                    self.timer = None;
+                    return expired;
+                }
+            };
+
+            let timer = self
+                .timer
+                .get_or_insert_with(|| Box::pin(sleep_until(next)));
+            if timer.deadline() != next {
+                timer.as_mut().reset(next);
+            }
+
+            if timer.as_mut().poll(cx).is_pending() {
+                return expired;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures_util::future::poll_fn;
+    use std::task::Poll;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn completed_streams_are_swept() {
+        let mut deadlines = Deadlines::default();
+        let deadline = Instant::now() + Duration::from_secs(60);
+        for id in 0..1000 {
+            deadlines.push(StreamId::from(2 * id + 1), deadline);
+        }
+
+        // Only every other stream is still active.
+        poll_fn(|cx| {
+            let expired = deadlines.poll_expired(cx, |id| u32::from(id) % 4 == 1);
+            assert!(expired.is_empty());
+            Poll::Ready(())
+        })
+        .await;
+
+        assert_eq!(deadlines.pending.len(), 500);
+    }
+}
@@ -1,7 +1,9 @@
 mod buffer;
 mod counts;
+mod deadlines;
 mod flow_control;
 mod prioritize;
+mod priority;
 mod recv;
 mod send;
 mod state;
@@ -18,6 +20,7 @@ use self::buffer::Buffer;
 use self::counts::Counts;
 use self::flow_control::FlowControl;
 use self::prioritize::Prioritize;
+use self::priority::PriorityTree;
 use self::recv::Recv;
 use self::send::Send;
 use self::state::State;
@@ -53,9 +56,24 @@ pub struct Config {
     /// Maximum number of locally reset streams to keep at a time
     pub local_reset_max: usize,
 
+    /// Maximum amount of data a stream may buffer before it is sent
+    pub local_max_buffer_size: usize,
+
     /// Initial window size of remote initiated streams
     pub remote_init_window_sz: WindowSize,
 
     /// Maximum number of remote initiated streams
     pub remote_max_initiated: Option<usize>,
+
+    /// Maximum number of remotely reset streams waiting to be accepted
+    pub remote_reset_max: usize,
+
+    /// If the local peer accepts extended CONNECT requests
+    pub local_extended_connect_protocol_enabled: bool,
+
+    /// If the local peer ignores RFC 7540 priority signals
+    pub local_no_rfc7540_priorities: bool,
+
+    /// If received data releases its capacity once returned to the user
+    pub local_auto_release_capacity: bool,
 }
@@ -1,32 +1,41 @@
 use super::store::Resolve;
 use super::*;
 
+use crate::ext;
 use crate::frame::{Reason, StreamId};
 
 use crate::codec::UserError;
 use crate::codec::UserError::*;
 
 use bytes::buf::{Buf, Take};
+use std::collections::VecDeque;
 use std::io;
 use std::task::{Context, Poll, Waker};
 use std::{cmp, fmt, mem};
 
+/// The most connection capacity assigned to a stream at a time when a window
+/// update is shared between streams waiting for capacity.
+const CAPACITY_QUANTUM: WindowSize = frame::DEFAULT_MAX_FRAME_SIZE;
+
 /// # Warning
 ///
-/// Queued streams are ordered by stream ID, as we need to ensure that
-/// lower-numbered streams are sent headers before higher-numbered ones.
-/// This is because "idle" stream IDs – those which have been initiated but
-/// have yet to receive frames – will be implicitly closed on receipt of a
-/// frame on a higher stream ID. If these queues was not ordered by stream
-/// IDs, some mechanism would be necessary to ensure that the lowest-numbered]
-/// idle stream is opened first.
+/// Streams that have not been opened yet are queued by stream ID, as we need
+/// to ensure that lower-numbered streams are sent headers before
+/// higher-numbered ones. This is because "idle" stream IDs – those which have
+/// been initiated but have yet to receive frames – will be implicitly closed
+/// on receipt of a frame on a higher stream ID. If these queues was not
+/// ordered by stream IDs, some mechanism would be necessary to ensure that
+/// the lowest-numbered] idle stream is opened first.
+///
+/// Once opened, streams are scheduled according to their priority instead.
 #[derive(Debug)]
 pub(super) struct Prioritize {
-    /// Queue of streams waiting for socket capacity to send a frame.
+    /// Queue of idle streams waiting for socket capacity to send a frame.
     pending_send: store::Queue<stream::NextSend>,
 
-    /// Queue of streams waiting for window capacity to produce data.
-    pending_capacity: store::Queue<stream::NextSendCapacity>,
+    /// Schedules open streams waiting for socket capacity to send a frame,
+    /// and streams waiting for window capacity to produce data.
+    tree: PriorityTree,
 
     /// Streams waiting for capacity due to max concurrency
     ///
@@ -43,6 +52,9 @@ pub(super) struct Prioritize {
     /// futures library.
     pending_open: store::Queue<stream::NextOpen>,
 
+    /// Number of streams in `pending_open`.
+    num_pending_open: usize,
+
     /// Connection level flow control governing sent data
     flow: FlowControl,
 
@@ -51,6 +63,15 @@ pub(super) struct Prioritize {
 
     /// What `DATA` frame is currently being sent in the codec.
     in_flight_data_frame: InFlightData,
+
+    /// PRIORITY_UPDATE frames waiting to be sent, at most one per stream.
+    pending_priority_updates: VecDeque<frame::PriorityUpdate>,
+
+    /// If RFC 7540 priority signals from the peer are ignored.
+    ignore_rfc7540_priorities: bool,
+
+    /// The maximum amount of data a stream may buffer.
+    max_buffer_size: usize,
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -88,14 +109,22 @@ impl Prioritize {
 
         Prioritize {
             pending_send: store::Queue::new(),
-            pending_capacity: store::Queue::new(),
+            tree: PriorityTree::new(),
             pending_open: store::Queue::new(),
+            num_pending_open: 0,
             flow,
             last_opened_id: StreamId::ZERO,
             in_flight_data_frame: InFlightData::Nothing,
+            pending_priority_updates: VecDeque::new(),
+            ignore_rfc7540_priorities: config.local_no_rfc7540_priorities,
+            max_buffer_size: config.local_max_buffer_size,
         }
     }
 
+    pub(crate) fn max_buffer_size(&self) -> usize {
+        self.max_buffer_size
+    }
+
     /// Queue a frame to be sent to the remote
     pub fn queue_frame<B>(
         &mut self,
@@ -116,7 +145,7 @@ impl Prioritize {
         if stream.is_send_ready() {
             tracing::trace!(?stream.id, "schedule_send");
             // Queue the stream
-            self.pending_send.push(stream);
+            self.push_pending_send(stream);
 
             // Notify the connection.
             if let Some(task) = task.take() {
@@ -125,8 +154,111 @@ impl Prioritize {
         }
     }
 
+    /// Returns the connection-level send window.
+    pub fn connection_window_size(&self) -> WindowSize {
+        self.flow.window_size()
+    }
+
     pub fn queue_open(&mut self, stream: &mut store::Ptr) {
-        self.pending_open.push(stream);
+        if self.pending_open.push(stream) {
+            self.num_pending_open += 1;
+        }
+    }
+
+    /// Returns the number of streams waiting for the send concurrency limit.
+    pub fn num_pending_open(&self) -> usize {
+        self.num_pending_open
+    }
+
+    /// Applies a priority signal received from the peer.
+    pub fn recv_priority(&mut self, id: StreamId, dep: &frame::StreamDependency, store: &Store) {
+        if self.ignore_rfc7540_priorities {
+            tracing::trace!(?id, "ignoring RFC 7540 priority signal");
+            return;
+        }
+
+        self.tree.reprioritize(id, dep, store.contains_id(&id));
+    }
+
+    /// Applies an RFC 9218 priority received from the peer, either in the
+    /// `priority` header or in a PRIORITY_UPDATE frame.
+    pub fn recv_priority_update(
+        &mut self,
+        id: StreamId,
+        priority: ext::Priority,
+        is_update: bool,
+        store: &Store,
+    ) {
+        self.tree
+            .set_priority(id, priority, store.contains_id(&id), is_update);
+    }
+
+    /// Changes the priority of a stream, optionally sending a PRIORITY_UPDATE
+    /// frame to let the peer know.
+    ///
+    /// If the stream's HEADERS frame hasn't been written yet, the priority is
+    /// only applied once it is, so the stream can't be opened ahead of
+    /// streams with lower IDs.
+    pub fn set_priority(
+        &mut self,
+        stream: &mut store::Ptr,
+        priority: ext::Priority,
+        send_update: bool,
+        task: &mut Option<Waker>,
+    ) {
+        if stream.is_pending_headers {
+            stream.pending_priority = Some((priority, send_update));
+            return;
+        }
+
+        self.apply_priority(stream, priority, send_update);
+
+        if send_update {
+            if let Some(task) = task.take() {
+                task.wake();
+            }
+        }
+    }
+
+    fn apply_priority(
+        &mut self,
+        stream: &mut store::Ptr,
+        priority: ext::Priority,
+        send_update: bool,
+    ) {
+        self.tree.set_priority(stream.id, priority, true, true);
+
+        if !send_update {
+            return;
+        }
+
+        let frame = frame::PriorityUpdate::new(stream.id, priority.to_field_value());
+        match self
+            .pending_priority_updates
+            .iter_mut()
+            .find(|pending| pending.prioritized_id() == stream.id)
+        {
+            Some(pending) => *pending = frame,
+            None => self.pending_priority_updates.push_back(frame),
+        }
+    }
+
+    fn push_pending_send(&mut self, stream: &mut store::Ptr) {
+        // Streams must be opened in order, so they are only prioritized once
+        // their HEADERS frame has been written.
+        if stream.is_pending_headers {
+            self.pending_send.push(stream);
+        } else {
+            self.tree.push::<stream::NextSend>(stream);
+        }
+    }
+
+    fn pop_pending_send<'a>(&mut self, store: &'a mut Store) -> Option<store::Ptr<'a>> {
+        if !self.pending_send.is_empty() {
+            return self.pending_send.pop(store);
+        }
+
+        self.tree.pop::<stream::NextSend, _>(store)
     }
 
     /// Send a data frame
@@ -327,7 +459,7 @@ impl Prioritize {
     pub fn clear_pending_capacity(&mut self, store: &mut Store, counts: &mut Counts) {
         let span = tracing::trace_span!("clear_pending_capacity");
         let _e = span.enter();
-        while let Some(stream) = self.pending_capacity.pop(store) {
+        while let Some(stream) = self.tree.pop::<stream::NextSendCapacity, _>(store) {
             counts.transition(stream, |_, stream| {
                 tracing::trace!(?stream.id, "clear_pending_capacity");
             })
@@ -349,7 +481,7 @@ impl Prioritize {
 
         // Assign newly acquired capacity to streams pending capacity.
         while self.flow.available() > 0 {
-            let stream = match self.pending_capacity.pop(store) {
+            let stream = match self.tree.pop::<stream::NextSendCapacity, _>(store) {
                 Some(stream) => stream,
                 None => return,
             };
@@ -366,13 +498,22 @@ impl Prioritize {
                 // Try to assign capacity to the stream. This will also re-queue the
                 // stream if there isn't enough connection level capacity to fulfill
                 // the capacity request.
-                self.try_assign_capacity(&mut stream);
+                //
+                // Capacity is handed out a frame's worth at a time so that
+                // it is shared between streams according to their priority.
+                self.try_assign_capacity_up_to(&mut stream, CAPACITY_QUANTUM);
             })
         }
     }
 
     /// Request capacity to send data
     fn try_assign_capacity(&mut self, stream: &mut store::Ptr) {
+        self.try_assign_capacity_up_to(stream, MAX_WINDOW_SIZE)
+    }
+
+    /// Request capacity to send data, assigning at most `max` from the
+    /// connection.
+    fn try_assign_capacity_up_to(&mut self, stream: &mut store::Ptr, max: WindowSize) {
         let total_requested = stream.requested_send_capacity;
 
         // Total requested should never go below actual assigned
@@ -416,16 +557,18 @@ impl Prioritize {
         // First check if capacity is immediately available
         if conn_available > 0 {
             // The amount of capacity to assign to the stream
-            // TODO: Should prioritization factor into this?
-            let assign = cmp::min(conn_available, additional);
+            let assign = cmp::min(cmp::min(conn_available, additional), max);
 
             tracing::trace!(capacity = assign, "assigning");
 
             // Assign the capacity to the stream
-            stream.assign_capacity(assign);
+            stream.assign_capacity(assign, self.max_buffer_size);
 
             // Claim the capacity from the connection
             self.flow.claim_capacity(assign);
+
+            self.tree
+                .charge::<stream::NextSendCapacity>(stream.id, assign);
         }
 
         tracing::trace!(
@@ -444,7 +587,7 @@ impl Prioritize {
             //
             // In this case, the stream needs to be queued up for when the
             // connection has more capacity.
-            self.pending_capacity.push(stream);
+            self.tree.push::<stream::NextSendCapacity>(stream);
         }
 
         // If data is buffered and the stream is send ready, then
@@ -462,7 +605,7 @@ impl Prioritize {
             //
             // debug_assert!(!stream.pending_send.is_empty());
 
-            self.pending_send.push(stream);
+            self.push_pending_send(stream);
         }
     }
 
@@ -481,6 +624,9 @@ impl Prioritize {
         // Ensure codec is ready
         ready!(dst.poll_ready(cx))?;
 
+        // Forget the priority of streams that closed a while ago
+        self.tree.sweep(store);
+
         // Reclaim any frame that has previously been written
         self.reclaim_frame(buffer, store, dst);
 
@@ -492,7 +638,12 @@ impl Prioritize {
         loop {
             self.schedule_pending_open(store, counts);
 
-            match self.pop_frame(buffer, store, max_frame_len, counts) {
+            let frame = match self.pending_priority_updates.pop_front() {
+                Some(frame) => Some(frame.into()),
+                None => self.pop_frame(buffer, store, max_frame_len, counts),
+            };
+
+            match frame {
                 Some(frame) => {
                     tracing::trace!(?frame, "writing");
 
@@ -615,7 +766,7 @@ impl Prioritize {
         // If needed, schedule the sender
         if stream.send_flow.available() > 0 {
             debug_assert!(!stream.pending_send.is_empty());
-            self.pending_send.push(stream);
+            self.push_pending_send(stream);
         }
     }
 
@@ -639,7 +790,7 @@ impl Prioritize {
     }
 
     pub fn clear_pending_send(&mut self, store: &mut Store, counts: &mut Counts) {
-        while let Some(stream) = self.pending_send.pop(store) {
+        while let Some(stream) = self.pop_pending_send(store) {
             let is_pending_reset = stream.is_pending_reset_expiration();
             counts.transition_after(stream, is_pending_reset);
         }
@@ -647,6 +798,7 @@ impl Prioritize {
 
     pub fn clear_pending_open(&mut self, store: &mut Store, counts: &mut Counts) {
         while let Some(stream) = self.pending_open.pop(store) {
+            self.num_pending_open -= 1;
             let is_pending_reset = stream.is_pending_reset_expiration();
             counts.transition_after(stream, is_pending_reset);
         }
@@ -666,7 +818,7 @@ impl Prioritize {
         let _e = span.enter();
 
         loop {
-            match self.pending_send.pop(store) {
+            match self.pop_pending_send(store) {
                 Some(mut stream) => {
                     let span = tracing::trace_span!("popped", ?stream.id, ?stream.state);
                     let _e = span.enter();
@@ -730,14 +882,11 @@ impl Prioritize {
 
                             tracing::trace!(len, "sending data frame");
 
+                            self.tree.charge::<stream::NextSend>(stream.id, len);
+
                             // Update the flow control
                             tracing::trace_span!("updating stream flow").in_scope(|| {
-                                stream.send_flow.send_data(len);
-
-                                // Decrement the stream's buffered data counter
-                                debug_assert!(stream.buffered_send_data >= len);
-                                stream.buffered_send_data -= len;
-                                stream.requested_send_capacity -= len;
+                                stream.send_data(len, self.max_buffer_size);
 
                                 // Assign the capacity back to the connection that
                                 // was just consumed from the stream in the previous
@@ -767,6 +916,13 @@ impl Prioritize {
                                 stream: stream.key(),
                             }))
                         }
+                        Some(frame @ Frame::Headers(_)) => {
+                            stream.is_pending_headers = false;
+                            if let Some((priority, send_update)) = stream.pending_priority.take() {
+                                self.apply_priority(&mut stream, priority, send_update);
+                            }
+                            frame.map(|_| unreachable!("HEADERS frames have no payload"))
+                        }
                         Some(Frame::PushPromise(pp)) => {
                             let mut pushed =
                                 stream.store_mut().find_mut(&pp.promised_id()).unwrap();
@@ -776,7 +932,7 @@ impl Prioritize {
                             if !pushed.pending_send.is_empty() {
                                 if counts.can_inc_num_send_streams() {
                                     counts.inc_num_send_streams(&mut pushed);
-                                    self.pending_send.push(&mut pushed);
+                                    self.push_pending_send(&mut pushed);
                                 } else {
                                     self.queue_open(&mut pushed);
                                 }
@@ -822,7 +978,7 @@ impl Prioritize {
                         // the next frame. i.e. don't requeue it if the next
                         // frame is a data frame and the stream does not have
                         // any more capacity.
-                        self.pending_send.push(&mut stream);
+                        self.push_pending_send(&mut stream);
                     }
 
                     counts.transition_after(stream, is_pending_reset);
@@ -840,9 +996,10 @@ impl Prioritize {
         while counts.can_inc_num_send_streams() {
             if let Some(mut stream) = self.pending_open.pop(store) {
                 tracing::trace!("schedule_pending_open; stream={:?}", stream.id);
+                self.num_pending_open -= 1;
 
                 counts.inc_num_send_streams(&mut stream);
-                self.pending_send.push(&mut stream);
+                self.push_pending_send(&mut stream);
                 stream.notify_send();
             } else {
                 return;
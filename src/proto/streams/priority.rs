@@ -0,0 +1,573 @@
+use super::store::{self, Next, Resolve};
+use super::stream::{NextSend, NextSendCapacity};
+use super::*;
+
+use crate::ext;
+use crate::frame::StreamDependency;
+
+use std::cmp;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+/// Weight of streams that have not been prioritized (RFC 7540, section 5.3.5).
+const DEFAULT_WEIGHT: u16 = 16;
+
+/// Maximum number of idle or closed streams kept in the tree so that other
+/// streams may still depend on them.
+const MAX_RETAINED: usize = 100;
+
+/// The tree is not swept for closed streams until it has at least this many
+/// nodes.
+const MIN_SWEEP: usize = 128;
+
+/// The RFC 7540 stream dependency tree.
+///
+/// Streams waiting to send frames or waiting for connection capacity are
+/// scheduled through the tree. A stream is only scheduled if none of its
+/// ancestors are waiting, and siblings share resources in proportion to
+/// their weights using weighted fair queuing: every time a stream is served,
+/// its virtual time advances by the amount served divided by its weight, and
+/// the sibling with the lowest virtual time is served next.
+///
+/// Streams may also be given an RFC 9218 priority. Siblings with a lower
+/// urgency are always served first. Siblings of the same urgency that are not
+/// incremental are served one at a time in the order of their stream IDs,
+/// before the incremental ones, which share resources the same way as
+/// streams without an RFC 9218 priority.
+///
+/// Each of these two schedules is a "lane" of the tree. Streams are queued
+/// in a lane the same way as in a `store::Queue`, and the lane's `Next` flag
+/// is used to know if a stream is queued. Every node keeps its active
+/// children ordered by the order in which they are served, so popping a
+/// stream only walks down one path of the tree.
+#[derive(Debug)]
+pub(super) struct PriorityTree {
+    nodes: HashMap<StreamId, Node>,
+
+    /// Nodes of idle or closed streams, oldest first.
+    retained: VecDeque<StreamId>,
+
+    /// Sweep for closed streams once the tree has this many nodes.
+    sweep_at: usize,
+
+    /// Incremented every time a stream is queued. Breaks ties between
+    /// siblings in the order they were queued.
+    seq: u64,
+}
+
+#[derive(Debug)]
+struct Node {
+    parent: StreamId,
+
+    /// Weight in the range [1, 256].
+    weight: u16,
+
+    children: Vec<StreamId>,
+
+    /// Whether the node is in the `retained` list.
+    retained: bool,
+
+    /// The RFC 9218 priority of the stream, if any.
+    priority: Option<ext::Priority>,
+
+    /// Set once the priority was set by a PRIORITY_UPDATE frame, which takes
+    /// precedence over the `priority` header.
+    updated: bool,
+
+    lanes: [LaneState; 2],
+}
+
+/// The order in which active siblings are served, lowest first.
+type SortKey = (u8, bool, u64, u64);
+
+#[derive(Debug, Default)]
+struct LaneState {
+    /// Set when the stream itself is queued.
+    key: Option<store::Key>,
+
+    /// Number of queued streams in this subtree, including this stream.
+    active: usize,
+
+    /// Virtual time at which this subtree is next served.
+    cycle: u64,
+
+    /// Virtual time of the child that was last served.
+    last_cycle: u64,
+
+    /// Used to break ties with siblings that have the same `cycle`.
+    seq: u64,
+
+    /// Active children, in the order they are served.
+    ready: BTreeSet<(SortKey, StreamId)>,
+
+    /// The key under which this node is in its parent's `ready` set.
+    ready_key: Option<SortKey>,
+}
+
+/// A schedule maintained by the tree.
+pub(super) trait Lane: Next {
+    const INDEX: usize;
+}
+
+impl Lane for NextSend {
+    const INDEX: usize = 0;
+}
+
+impl Lane for NextSendCapacity {
+    const INDEX: usize = 1;
+}
+
+// ===== impl PriorityTree =====
+
+impl PriorityTree {
+    pub fn new() -> Self {
+        let mut nodes = HashMap::new();
+        nodes.insert(StreamId::ZERO, Node::new(StreamId::ZERO, DEFAULT_WEIGHT));
+
+        PriorityTree {
+            nodes,
+            retained: VecDeque::new(),
+            sweep_at: MIN_SWEEP,
+            seq: 0,
+        }
+    }
+
+    /// Queue the stream in lane `N`.
+    ///
+    /// If the stream is already queued, return `false`.
+    pub fn push<N: Lane>(&mut self, stream: &mut store::Ptr) -> bool {
+        if N::is_queued(stream) {
+            return false;
+        }
+
+        N::set_queued(stream, true);
+
+        let id = stream.id;
+        if self.nodes.contains_key(&id) {
+            self.unretain(id);
+        } else {
+            self.insert(id, StreamId::ZERO, DEFAULT_WEIGHT);
+        }
+
+        let node = self.node_mut(id);
+        debug_assert!(node.lanes[N::INDEX].key.is_none());
+        node.lanes[N::INDEX].key = Some(stream.key());
+
+        self.seq += 1;
+        self.add_active(N::INDEX, id, 1, self.seq);
+
+        true
+    }
+
+    /// Pop the next stream to serve from lane `N`.
+    pub fn pop<'a, N, R>(&mut self, store: &'a mut R) -> Option<store::Ptr<'a>>
+    where
+        N: Lane,
+        R: Resolve,
+    {
+        let lane = N::INDEX;
+
+        if self.nodes[&StreamId::ZERO].lanes[lane].active == 0 {
+            return None;
+        }
+
+        // Walk down from the root, always picking the active child with the
+        // lowest virtual time, until reaching a node that is queued itself.
+        let mut id = StreamId::ZERO;
+        loop {
+            let node = &self.nodes[&id];
+
+            if id != StreamId::ZERO && node.lanes[lane].key.is_some() {
+                break;
+            }
+
+            let &(_, next) = node.lanes[lane]
+                .ready
+                .iter()
+                .next()
+                .expect("active node has no active children");
+
+            let cycle = self.nodes[&next].lanes[lane].cycle;
+            self.node_mut(id).lanes[lane].last_cycle = cycle;
+            id = next;
+        }
+
+        let key = self.node_mut(id).lanes[lane].key.take().unwrap();
+        self.sub_active(lane, id, 1);
+
+        let mut stream = store.resolve(key);
+        debug_assert!(N::is_queued(&stream));
+        N::set_queued(&mut stream, false);
+
+        Some(stream)
+    }
+
+    /// Records that `amount` was served to the stream in lane `N`, delaying
+    /// it (and its ancestors) relative to their siblings.
+    pub fn charge<N: Lane>(&mut self, id: StreamId, amount: WindowSize) {
+        if amount == 0 {
+            return;
+        }
+
+        let mut id = id;
+        while id != StreamId::ZERO {
+            let node = match self.nodes.get_mut(&id) {
+                Some(node) => node,
+                None => return,
+            };
+
+            let cost = cmp::max(u64::from(amount) * 256 / u64::from(node.weight), 1);
+            let parent = node.parent;
+
+            self.unlink(N::INDEX, id);
+            self.node_mut(id).lanes[N::INDEX].cycle += cost;
+            self.link(N::INDEX, id);
+            id = parent;
+        }
+    }
+
+    /// Applies a priority signal from the peer, either from a PRIORITY frame
+    /// or from a HEADERS frame.
+    ///
+    /// `is_open` is `false` if the stream is idle or closed.
+    pub fn reprioritize(&mut self, id: StreamId, dep: &StreamDependency, is_open: bool) {
+        let parent = dep.dependency_id();
+        let weight = u16::from(dep.weight()) + 1;
+
+        // A stream cannot depend on itself, which is rejected when decoding
+        // the frame.
+        debug_assert_ne!(id, parent);
+
+        tracing::trace!(
+            ?id,
+            ?parent,
+            weight,
+            exclusive = dep.is_exclusive(),
+            "reprioritize"
+        );
+
+        if !self.nodes.contains_key(&id) {
+            self.insert(id, StreamId::ZERO, DEFAULT_WEIGHT);
+            if !is_open {
+                self.retain(id);
+            }
+        } else if is_open {
+            self.unretain(id);
+        }
+
+        // Streams may depend on streams that are not in the tree yet, such
+        // as idle streams used as grouping nodes.
+        if !self.nodes.contains_key(&parent) {
+            self.insert(parent, StreamId::ZERO, DEFAULT_WEIGHT);
+            self.retain(parent);
+        }
+
+        // If the new parent depends on the stream, the parent is first moved
+        // to take the stream's place (RFC 7540, section 5.3.3).
+        if self.is_descendant(parent, id) {
+            let prev = self.nodes[&id].parent;
+            self.detach(parent);
+            self.attach(parent, prev);
+        }
+
+        self.detach(id);
+        self.node_mut(id).weight = weight;
+        self.attach(id, parent);
+
+        if dep.is_exclusive() {
+            // The stream becomes the sole dependency of its parent, adopting
+            // all of the parent's other dependencies.
+            let siblings: Vec<_> = self.nodes[&parent]
+                .children
+                .iter()
+                .copied()
+                .filter(|child| *child != id)
+                .collect();
+
+            for sibling in siblings {
+                self.detach(sibling);
+                self.attach(sibling, id);
+            }
+        }
+
+        self.evict();
+    }
+
+    /// Sets the RFC 9218 priority of a stream.
+    ///
+    /// `is_update` is `false` if the priority comes from the `priority`
+    /// header, and `true` if it comes from a PRIORITY_UPDATE frame or is set
+    /// locally. Once a stream has been updated, the header is ignored, as
+    /// the frame may have been sent before the request headers were
+    /// (RFC 9218, section 7).
+    pub fn set_priority(
+        &mut self,
+        id: StreamId,
+        priority: ext::Priority,
+        is_open: bool,
+        is_update: bool,
+    ) {
+        tracing::trace!(?id, ?priority, is_update, "set_priority");
+
+        if !self.nodes.contains_key(&id) {
+            self.insert(id, StreamId::ZERO, DEFAULT_WEIGHT);
+            if !is_open {
+                self.retain(id);
+            }
+        } else if is_open {
+            self.unretain(id);
+        }
+
+        let node = &self.nodes[&id];
+        if node.updated && !is_update {
+            return;
+        }
+
+        for lane in 0..2 {
+            self.unlink(lane, id);
+        }
+        let node = self.node_mut(id);
+        node.priority = Some(priority);
+        node.updated |= is_update;
+        for lane in 0..2 {
+            self.link(lane, id);
+        }
+
+        self.evict();
+    }
+
+    /// Retains the nodes of streams that have closed, evicting the oldest
+    /// retained nodes once there are too many.
+    pub fn sweep(&mut self, store: &Store) {
+        if self.nodes.len() < self.sweep_at {
+            return;
+        }
+
+        let mut closed: Vec<_> = self
+            .nodes
+            .iter()
+            .filter(|&(id, node)| *id != StreamId::ZERO && !node.retained && !store.contains_id(id))
+            .map(|(id, _)| *id)
+            .collect();
+        closed.sort();
+
+        for id in closed {
+            self.retain(id);
+        }
+
+        self.evict();
+        self.sweep_at = cmp::max(self.nodes.len() * 2, MIN_SWEEP);
+    }
+
+    fn node_mut(&mut self, id: StreamId) -> &mut Node {
+        self.nodes.get_mut(&id).expect("missing priority node")
+    }
+
+    fn insert(&mut self, id: StreamId, parent: StreamId, weight: u16) {
+        self.nodes.insert(id, Node::new(parent, weight));
+        self.node_mut(parent).children.push(id);
+    }
+
+    /// Returns `true` if `id` is in the subtree of `ancestor`.
+    fn is_descendant(&self, mut id: StreamId, ancestor: StreamId) -> bool {
+        while id != StreamId::ZERO {
+            id = self.nodes[&id].parent;
+            if id == ancestor {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Removes the node from its parent's children.
+    fn detach(&mut self, id: StreamId) {
+        let parent = self.nodes[&id].parent;
+        self.node_mut(parent).children.retain(|child| *child != id);
+
+        for lane in 0..2 {
+            self.unlink(lane, id);
+            let active = self.nodes[&id].lanes[lane].active;
+            if active > 0 {
+                self.sub_active(lane, parent, active);
+            }
+        }
+    }
+
+    /// Makes the node a child of `parent`.
+    fn attach(&mut self, id: StreamId, parent: StreamId) {
+        self.node_mut(id).parent = parent;
+        self.node_mut(parent).children.push(id);
+
+        for lane in 0..2 {
+            let active = self.nodes[&id].lanes[lane].active;
+            if active > 0 {
+                // The subtree must not get ahead of its new siblings.
+                let last_cycle = self.nodes[&parent].lanes[lane].last_cycle;
+                let state = &mut self.node_mut(id).lanes[lane];
+                state.cycle = cmp::max(state.cycle, last_cycle);
+                self.link(lane, id);
+
+                self.seq += 1;
+                self.add_active(lane, parent, active, self.seq);
+            }
+        }
+    }
+
+    fn add_active(&mut self, lane: usize, mut id: StreamId, n: usize, seq: u64) {
+        loop {
+            let parent = self.nodes[&id].parent;
+            let last_cycle = if id == StreamId::ZERO {
+                0
+            } else {
+                self.nodes[&parent].lanes[lane].last_cycle
+            };
+
+            self.unlink(lane, id);
+            let state = &mut self.node_mut(id).lanes[lane];
+            if state.active == 0 {
+                // A subtree that becomes active starts from where its
+                // siblings currently are, so it doesn't get credit for the
+                // time it was inactive.
+                state.cycle = cmp::max(state.cycle, last_cycle);
+                state.seq = seq;
+            }
+            state.active += n;
+            self.link(lane, id);
+
+            if id == StreamId::ZERO {
+                return;
+            }
+            id = parent;
+        }
+    }
+
+    fn sub_active(&mut self, lane: usize, mut id: StreamId, n: usize) {
+        loop {
+            self.unlink(lane, id);
+            let node = self.node_mut(id);
+            debug_assert!(node.lanes[lane].active >= n);
+            node.lanes[lane].active -= n;
+            let parent = node.parent;
+            self.link(lane, id);
+
+            if id == StreamId::ZERO {
+                return;
+            }
+            id = parent;
+        }
+    }
+
+    /// Removes the node from its parent's `ready` set.
+    fn unlink(&mut self, lane: usize, id: StreamId) {
+        let node = self.node_mut(id);
+        if let Some(key) = node.lanes[lane].ready_key.take() {
+            let parent = node.parent;
+            self.node_mut(parent).lanes[lane].ready.remove(&(key, id));
+        }
+    }
+
+    /// Adds the node to its parent's `ready` set if its subtree is active.
+    fn link(&mut self, lane: usize, id: StreamId) {
+        let node = &self.nodes[&id];
+        if id == StreamId::ZERO || node.lanes[lane].active == 0 {
+            return;
+        }
+        debug_assert!(node.lanes[lane].ready_key.is_none());
+
+        let key = node.sort_key(id, lane);
+        let parent = node.parent;
+        self.node_mut(id).lanes[lane].ready_key = Some(key);
+        self.node_mut(parent).lanes[lane].ready.insert((key, id));
+    }
+
+    fn retain(&mut self, id: StreamId) {
+        let node = self.node_mut(id);
+        if !node.retained {
+            node.retained = true;
+            self.retained.push_back(id);
+        }
+    }
+
+    fn unretain(&mut self, id: StreamId) {
+        let node = self.node_mut(id);
+        if node.retained {
+            node.retained = false;
+            self.retained.retain(|retained| *retained != id);
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.retained.len() > MAX_RETAINED {
+            let id = self.retained.pop_front().unwrap();
+            self.remove(id);
+        }
+    }
+
+    /// Removes a node, giving its children to its parent. The node's weight
+    /// is distributed among the children in proportion to their own weights
+    /// (RFC 7540, section 5.3.4).
+    fn remove(&mut self, id: StreamId) {
+        tracing::trace!(?id, "removing priority node");
+
+        for lane in 0..2 {
+            self.unlink(lane, id);
+        }
+        let node = self.nodes.remove(&id).expect("missing priority node");
+        debug_assert!(node.lanes.iter().all(|state| state.key.is_none()));
+
+        let parent = node.parent;
+        self.node_mut(parent).children.retain(|child| *child != id);
+
+        let total: u32 = node
+            .children
+            .iter()
+            .map(|child| u32::from(self.nodes[child].weight))
+            .sum();
+
+        for child in node.children {
+            let weight = u32::from(self.nodes[&child].weight) * u32::from(node.weight) / total;
+
+            let child_node = self.node_mut(child);
+            child_node.parent = parent;
+            child_node.weight = cmp::max(weight, 1) as u16;
+            // The child was in the removed node's `ready` set.
+            for lane in 0..2 {
+                self.node_mut(child).lanes[lane].ready_key = None;
+                self.link(lane, child);
+            }
+            self.node_mut(parent).children.push(child);
+        }
+    }
+}
+
+// ===== impl Node =====
+
+impl Node {
+    fn new(parent: StreamId, weight: u16) -> Self {
+        Node {
+            parent,
+            weight,
+            children: Vec::new(),
+            retained: false,
+            priority: None,
+            updated: false,
+            lanes: Default::default(),
+        }
+    }
+
+    fn sort_key(&self, id: StreamId, lane: usize) -> SortKey {
+        let state = &self.lanes[lane];
+        match self.priority {
+            Some(priority) if !priority.is_incremental() => {
+                (priority.urgency(), false, u64::from(u32::from(id)), 0)
+            }
+            Some(priority) => (priority.urgency(), true, state.cycle, state.seq),
+            None => (
+                ext::Priority::default().urgency(),
+                true,
+                state.cycle,
+                state.seq,
+            ),
+        }
+    }
+}
@@ -1,15 +1,19 @@
 use super::*;
 use crate::codec::{RecvError, UserError};
 use crate::frame::{PushPromiseHeaderError, Reason, DEFAULT_INITIAL_WINDOW_SIZE};
-use crate::{frame, proto};
+use crate::{ext, frame, proto};
 use std::task::Context;
 
 use http::{HeaderMap, Request, Response};
+use indexmap::IndexSet;
 
 use std::io;
 use std::task::{Poll, Waker};
 use std::time::{Duration, Instant};
 
+/// Maximum number of origins kept in the origin set.
+const MAX_ORIGINS: usize = 256;
+
 #[derive(Debug)]
 pub(super) struct Recv {
     /// Initial window size of remote initiated streams
@@ -56,6 +60,17 @@ pub(super) struct Recv {
 
     /// If push promises are allowed to be received.
     is_push_enabled: bool,
+
+    /// If extended connect protocol is enabled.
+    is_extended_connect_protocol_enabled: bool,
+
+    /// Origins received in ORIGIN frames, in the order they were received,
+    /// if any were received.
+    origin_set: Option<IndexSet<ext::Origin>>,
+
+    /// If streams release the capacity of received data once it is returned
+    /// to the user, unless overridden per stream.
+    is_auto_release_capacity: bool,
 }
 
 #[derive(Debug)]
@@ -109,14 +124,49 @@ impl Recv {
             buffer: Buffer::new(),
             refused: None,
             is_push_enabled: config.local_push_enabled,
+            is_extended_connect_protocol_enabled: config.local_extended_connect_protocol_enabled,
+            origin_set: None,
+            is_auto_release_capacity: config.local_auto_release_capacity,
         }
     }
 
     /// Returns the initial receive window size
+    /// Returns the connection-level receive window.
+    pub fn connection_window_size(&self) -> WindowSize {
+        self.flow.window_size()
+    }
+
     pub fn init_window_sz(&self) -> WindowSize {
         self.init_window_sz
     }
 
+    /// Adds the origins of an ORIGIN frame to the origin set.
+    ///
+    /// Origins that are not valid ASCII serializations are ignored, as are
+    /// any origins past the first `MAX_ORIGINS`.
+    pub fn recv_origin(&mut self, frame: frame::Origin) {
+        let origin_set = self.origin_set.get_or_insert_with(IndexSet::new);
+
+        for origin in frame.into_origins() {
+            match ext::Origin::from_bytes(origin) {
+                Some(origin) => {
+                    if origin_set.len() >= MAX_ORIGINS && !origin_set.contains(&origin) {
+                        tracing::debug!("ignoring origin; origin set is full");
+                        continue;
+                    }
+                    origin_set.insert(origin);
+                }
+                None => tracing::debug!("ignoring invalid origin"),
+            }
+        }
+    }
+
+    pub fn origin_set(&self) -> Option<Vec<ext::Origin>> {
+        self.origin_set
+            .as_ref()
+            .map(|origins| origins.iter().cloned().collect())
+    }
+
     /// Returns the ID of the last processed stream
     pub fn last_processed_id(&self) -> StreamId {
         self.last_processed_id
@@ -226,6 +276,19 @@ impl Recv {
 
         let stream_id = frame.stream_id();
         let (pseudo, fields) = frame.into_parts();
+
+        if pseudo.protocol.is_some()
+            && counts.peer().is_server()
+            && !self.is_extended_connect_protocol_enabled
+        {
+            proto_err!(stream: "cannot use :protocol if extended connect protocol is disabled; stream={:?}", stream.id);
+            return Err(RecvError::Stream {
+                id: stream.id,
+                reason: Reason::PROTOCOL_ERROR,
+            }
+            .into());
+        }
+
         if !pseudo.is_informational() {
             let message = counts
                 .peer()
@@ -1050,6 +1113,14 @@ impl Recv {
         }
     }
 
+    /// Returns true if the capacity of data returned by `poll_data` is
+    /// released automatically for this stream.
+    pub fn is_auto_release_capacity(&self, stream: &Stream) -> bool {
+        stream
+            .auto_release_capacity
+            .unwrap_or(self.is_auto_release_capacity)
+    }
+
     pub fn poll_trailers(
         &mut self,
         cx: &Context,
@@ -3,6 +3,7 @@ use super::{
     StreamIdOverflow, WindowSize,
 };
 use crate::codec::{RecvError, UserError};
+use crate::ext;
 use crate::frame::{self, Reason};
 
 use bytes::Buf;
@@ -34,6 +35,13 @@ pub(super) struct Send {
     prioritize: Prioritize,
 
     is_push_enabled: bool,
+
+    /// If extended connect protocol is enabled.
+    is_extended_connect_protocol_enabled: bool,
+
+    /// Set once the local client has sent a GOAWAY, after which no new
+    /// streams may be opened.
+    is_going_away: bool,
 }
 
 /// A value to detect which public API has called `poll_reset`.
@@ -52,10 +60,21 @@ impl Send {
             next_stream_id: Ok(config.local_next_stream_id),
             prioritize: Prioritize::new(config),
             is_push_enabled: true,
+            is_extended_connect_protocol_enabled: false,
+            is_going_away: false,
         }
     }
 
     /// Returns the initial send window size
+    /// Returns the connection-level send window.
+    pub fn connection_window_size(&self) -> WindowSize {
+        self.prioritize.connection_window_size()
+    }
+
+    pub fn num_pending_open(&self) -> usize {
+        self.prioritize.num_pending_open()
+    }
+
     pub fn init_window_sz(&self) -> WindowSize {
         self.init_window_sz
     }
@@ -139,9 +158,15 @@ impl Send {
 
         let end_stream = frame.is_end_stream();
 
+        let is_idle = stream.state.is_idle();
+
         // Update the state
         stream.state.send_open(end_stream)?;
 
+        if is_idle {
+            stream.is_pending_headers = true;
+        }
+
         if counts.peer().is_local_init(frame.stream_id()) {
             // If we're waiting on a PushPromise anyway
             // handle potentially queueing the stream at that point
@@ -215,6 +240,7 @@ impl Send {
         // the reset frame before transitioning the stream inside
         // `reclaim_all_capacity`.
         self.prioritize.clear_queue(buffer, stream);
+        counts.inc_num_local_resets();
 
         let frame = frame::Reset::new(stream.id, reason);
 
@@ -237,6 +263,7 @@ impl Send {
         }
 
         stream.state.set_scheduled_reset(reason);
+        counts.inc_num_local_resets();
 
         self.prioritize.reclaim_reserved_capacity(stream, counts);
         self.prioritize.schedule_send(stream, task);
@@ -286,6 +313,25 @@ impl Send {
         Ok(())
     }
 
+    pub fn send_alt_svc<B>(
+        &mut self,
+        frame: frame::AltSvc,
+        buffer: &mut Buffer<Frame<B>>,
+        stream: &mut store::Ptr,
+        task: &mut Option<Waker>,
+    ) -> Result<(), UserError> {
+        // An ALTSVC frame may not be sent once the stream is closed.
+        if stream.state.is_send_closed() {
+            return Err(UserError::UnexpectedFrameType);
+        }
+
+        tracing::trace!("send_alt_svc -- queuing; frame={:?}", frame);
+        self.prioritize
+            .queue_frame(frame.into(), buffer, stream, task);
+
+        Ok(())
+    }
+
     pub fn poll_complete<T, B>(
         &mut self,
         cx: &mut Context,
@@ -333,14 +379,7 @@ impl Send {
 
     /// Current available stream send capacity
     pub fn capacity(&self, stream: &mut store::Ptr) -> WindowSize {
-        let available = stream.send_flow.available().as_size();
-        let buffered = stream.buffered_send_data;
-
-        if available <= buffered {
-            0
-        } else {
-            available - buffered
-        }
+        stream.capacity(self.prioritize.max_buffer_size())
     }
 
     pub fn poll_reset(
@@ -387,6 +426,32 @@ impl Send {
         Ok(())
     }
 
+    pub fn recv_priority(&mut self, id: StreamId, dep: &frame::StreamDependency, store: &Store) {
+        self.prioritize.recv_priority(id, dep, store)
+    }
+
+    pub fn recv_priority_update(
+        &mut self,
+        id: StreamId,
+        priority: ext::Priority,
+        is_update: bool,
+        store: &Store,
+    ) {
+        self.prioritize
+            .recv_priority_update(id, priority, is_update, store)
+    }
+
+    pub fn set_priority(
+        &mut self,
+        stream: &mut store::Ptr,
+        priority: ext::Priority,
+        send_update: bool,
+        task: &mut Option<Waker>,
+    ) {
+        self.prioritize
+            .set_priority(stream, priority, send_update, task)
+    }
+
     pub(super) fn recv_go_away(&mut self, last_stream_id: StreamId) -> Result<(), RecvError> {
         if last_stream_id > self.max_stream_id {
             // The remote endpoint sent a `GOAWAY` frame indicating a stream
@@ -507,6 +572,10 @@ impl Send {
             self.is_push_enabled = val
         }
 
+        if let Some(val) = settings.is_extended_connect_protocol_enabled() {
+            self.is_extended_connect_protocol_enabled = val;
+        }
+
         Ok(())
     }
 
@@ -516,6 +585,10 @@ impl Send {
         self.prioritize.clear_pending_open(store, counts);
     }
 
+    pub(crate) fn is_extended_connect_protocol_enabled(&self) -> bool {
+        self.is_extended_connect_protocol_enabled
+    }
+
     pub fn ensure_not_idle(&self, id: StreamId) -> Result<(), Reason> {
         if let Ok(next) = self.next_stream_id {
             if id >= next {
@@ -528,10 +601,19 @@ impl Send {
     }
 
     pub fn ensure_next_stream_id(&self) -> Result<StreamId, UserError> {
+        if self.is_going_away {
+            return Err(UserError::GoingAway);
+        }
+
         self.next_stream_id
             .map_err(|_| UserError::OverflowedStreamId)
     }
 
+    /// Stops opening new streams.
+    pub fn go_away(&mut self) {
+        self.is_going_away = true;
+    }
+
     pub fn may_have_created_stream(&self, id: StreamId) -> bool {
         if let Ok(next_id) = self.next_stream_id {
             // Peer::is_local_init should have been called beforehand
@@ -2,6 +2,7 @@ use std::io;
 
 use crate::codec::UserError::*;
 use crate::codec::{RecvError, UserError};
+use crate::error::RequestTimedOut;
 use crate::frame::{self, Reason};
 use crate::proto::{self, PollReset};
 
@@ -53,7 +54,7 @@ pub struct State {
     inner: Inner,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 enum Inner {
     Idle,
     // TODO: these states shouldn't count against concurrency limits:
@@ -71,13 +72,21 @@ enum Peer {
     Streaming,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 enum Cause {
     EndStream,
+
+    /// The peer reset the stream.
     Proto(Reason),
     LocallyReset(Reason),
     Io,
 
+    /// The peer sent a GOAWAY frame without having processed the stream.
+    GoAway(frame::GoAway),
+
+    /// The request timed out, so the stream was reset with `CANCEL`.
+    TimedOut,
+
     /// This indicates to the connection that a reset frame must be sent out
     /// once the send queue has been flushed.
     ///
@@ -195,7 +204,7 @@ impl State {
                     HalfClosedLocal(Streaming)
                 }
             }
-            state => {
+            ref state => {
                 // All other transitions result in a protocol error
                 proto_err!(conn: "recv_open: in unexpected state {:?}", state);
                 return Err(RecvError::Connection(Reason::PROTOCOL_ERROR));
@@ -212,7 +221,7 @@ impl State {
                 self.inner = ReservedRemote;
                 Ok(())
             }
-            state => {
+            ref state => {
                 proto_err!(conn: "reserve_remote: in unexpected state {:?}", state);
                 Err(RecvError::Connection(Reason::PROTOCOL_ERROR))
             }
@@ -244,7 +253,7 @@ impl State {
                 self.inner = Closed(Cause::EndStream);
                 Ok(())
             }
-            state => {
+            ref state => {
                 proto_err!(conn: "recv_close: in unexpected state {:?}", state);
                 Err(RecvError::Connection(Reason::PROTOCOL_ERROR))
             }
@@ -275,7 +284,7 @@ impl State {
             // In either of these cases, we want to overwrite the stream's
             // previous state with the received RST_STREAM, so that the queue
             // will be cleared by `Prioritize::pop_frame`.
-            state => {
+            ref state => {
                 tracing::trace!(
                     "recv_reset; reason={:?}; state={:?}; queued={:?}",
                     reason,
@@ -296,7 +305,8 @@ impl State {
             _ => {
                 tracing::trace!("recv_err; err={:?}", err);
                 self.inner = Closed(match *err {
-                    Proto(reason) => Cause::LocallyReset(reason),
+                    Proto(reason) | Reset(reason) => Cause::LocallyReset(reason),
+                    GoAway { ref frame, .. } => Cause::GoAway(frame.clone()),
                     Io(..) => Cause::Io,
                 });
             }
@@ -306,7 +316,7 @@ impl State {
     pub fn recv_eof(&mut self) {
         match self.inner {
             Closed(..) => {}
-            s => {
+            ref s => {
                 tracing::trace!("recv_eof; state={:?}", s);
                 self.inner = Closed(Cause::Io);
             }
@@ -325,7 +335,7 @@ impl State {
                 tracing::trace!("send_close: HalfClosedRemote => Closed");
                 self.inner = Closed(Cause::EndStream);
             }
-            state => panic!("send_close: unexpected state {:?}", state),
+            ref state => panic!("send_close: unexpected state {:?}", state),
         }
     }
 
@@ -334,6 +344,13 @@ impl State {
         self.inner = Closed(Cause::LocallyReset(reason));
     }
 
+    /// Marks a stream that was just reset as timed out.
+    pub fn set_timed_out(&mut self) {
+        if let Closed(Cause::LocallyReset(_)) = self.inner {
+            self.inner = Closed(Cause::TimedOut);
+        }
+    }
+
     /// Set the stream state to a scheduled reset.
     pub fn set_scheduled_reset(&mut self, reason: Reason) {
         debug_assert!(!self.is_closed());
@@ -354,10 +371,16 @@ impl State {
         }
     }
 
+    pub fn is_remote_reset(&self) -> bool {
+        matches!(self.inner, Closed(Cause::Proto(_)))
+    }
+
     pub fn is_local_reset(&self) -> bool {
         match self.inner {
             Closed(Cause::LocallyReset(_)) => true,
             Closed(Cause::Scheduled(..)) => true,
+            Closed(Cause::TimedOut) => true,
+            Closed(Cause::GoAway(..)) => true,
             _ => false,
         }
     }
@@ -436,9 +459,15 @@ impl State {
     pub fn ensure_recv_open(&self) -> Result<bool, proto::Error> {
         // TODO: Is this correct?
         match self.inner {
-            Closed(Cause::Proto(reason))
-            | Closed(Cause::LocallyReset(reason))
-            | Closed(Cause::Scheduled(reason)) => Err(proto::Error::Proto(reason)),
+            Closed(Cause::Proto(reason)) => Err(proto::Error::Reset(reason)),
+            Closed(Cause::LocallyReset(reason)) | Closed(Cause::Scheduled(reason)) => {
+                Err(proto::Error::Proto(reason))
+            }
+            Closed(Cause::GoAway(ref frame)) => Err(proto::Error::GoAway {
+                frame: frame.clone(),
+                unprocessed: true,
+            }),
+            Closed(Cause::TimedOut) => Err(proto::Error::Io(RequestTimedOut.into())),
             Closed(Cause::Io) => Err(proto::Error::Io(io::ErrorKind::BrokenPipe.into())),
             Closed(Cause::EndStream) | HalfClosedRemote(..) | ReservedLocal => Ok(false),
             _ => Ok(true),
@@ -451,6 +480,8 @@ impl State {
             Closed(Cause::Proto(reason))
             | Closed(Cause::LocallyReset(reason))
             | Closed(Cause::Scheduled(reason)) => Ok(Some(reason)),
+            Closed(Cause::GoAway(ref frame)) => Ok(Some(frame.reason())),
+            Closed(Cause::TimedOut) => Ok(Some(Reason::CANCEL)),
             Closed(Cause::Io) => Err(proto::Error::Io(io::ErrorKind::BrokenPipe.into()).into()),
             Open {
                 local: Streaming, ..
@@ -1,4 +1,5 @@
 use super::*;
+use crate::ext;
 
 use std::task::{Context, Waker};
 use std::time::Instant;
@@ -72,6 +73,13 @@ pub(super) struct Stream {
     /// Set to true when a push is pending for this stream
     pub is_pending_push: bool,
 
+    /// Set to true until the HEADERS frame opening this stream is written
+    pub is_pending_headers: bool,
+
+    /// Priority set by the user while `is_pending_headers` is set, and
+    /// whether to send a PRIORITY_UPDATE frame for it
+    pub pending_priority: Option<(ext::Priority, bool)>,
+
     // ===== Fields related to receiving =====
     /// Next node in the accept linked list
     pub next_pending_accept: Option<store::Key>,
@@ -107,6 +115,10 @@ pub(super) struct Stream {
 
     /// Validate content-length headers
     pub content_length: ContentLength,
+
+    /// Overrides whether received data releases its capacity once returned
+    /// to the user.
+    pub auto_release_capacity: Option<bool>,
 }
 
 /// State related to validating a stream's content-length
@@ -169,6 +181,8 @@ impl Stream {
             is_pending_open: false,
             next_open: None,
             is_pending_push: false,
+            is_pending_headers: false,
+            pending_priority: None,
 
             // ===== Fields related to receiving =====
             next_pending_accept: None,
@@ -183,6 +197,7 @@ impl Stream {
             recv_task: None,
             pending_push_promises: store::Queue::new(),
             content_length: ContentLength::Omitted,
+            auto_release_capacity: None,
         }
     }
 
@@ -260,25 +275,57 @@ impl Stream {
         self.ref_count == 0 && !self.state.is_closed()
     }
 
-    pub fn assign_capacity(&mut self, capacity: WindowSize) {
+    /// Returns the capacity the user may fill with new data, such that no
+    /// more than `max_buffer_size` bytes are buffered.
+    pub fn capacity(&self, max_buffer_size: usize) -> WindowSize {
+        let available = self.send_flow.available().as_size() as usize;
+        let buffered = self.buffered_send_data as usize;
+
+        available.min(max_buffer_size).saturating_sub(buffered) as WindowSize
+    }
+
+    pub fn assign_capacity(&mut self, capacity: WindowSize, max_buffer_size: usize) {
+        let prev_capacity = self.capacity(max_buffer_size);
         debug_assert!(capacity > 0);
-        self.send_capacity_inc = true;
         self.send_flow.assign_capacity(capacity);
 
         tracing::trace!(
-            "  assigned capacity to stream; available={}; buffered={}; id={:?}",
+            "  assigned capacity to stream; available={}; buffered={}; id={:?}; max_buffer_size={}",
             self.send_flow.available(),
             self.buffered_send_data,
-            self.id
+            self.id,
+            max_buffer_size
         );
 
-        // Only notify if the capacity exceeds the amount of buffered data
-        if self.send_flow.available() > self.buffered_send_data {
-            tracing::trace!("  notifying task");
-            self.notify_send();
+        // Only notify if the capacity the user may fill has grown
+        if prev_capacity < self.capacity(max_buffer_size) {
+            self.notify_capacity();
+        }
+    }
+
+    /// Accounts for `len` bytes of buffered data being written.
+    pub fn send_data(&mut self, len: WindowSize, max_buffer_size: usize) {
+        let prev_capacity = self.capacity(max_buffer_size);
+
+        self.send_flow.send_data(len);
+
+        // Decrement the stream's buffered data counter
+        debug_assert!(self.buffered_send_data >= len);
+        self.buffered_send_data -= len;
+        self.requested_send_capacity -= len;
+
+        // Writing buffered data makes room in the send buffer
+        if prev_capacity < self.capacity(max_buffer_size) {
+            self.notify_capacity();
         }
     }
 
+    fn notify_capacity(&mut self) {
+        self.send_capacity_inc = true;
+        tracing::trace!("  notifying task");
+        self.notify_send();
+    }
+
     /// Returns `Err` when the decrement cannot be completed due to overflow.
     pub fn dec_content_length(&mut self, len: usize) -> Result<(), ()> {
         match self.content_length {
@@ -1,7 +1,9 @@
+use super::deadlines::Deadlines;
 use super::recv::RecvHeaderBlockError;
 use super::store::{self, Entry, Resolve, Store};
 use super::{Buffer, Config, Counts, Prioritized, Recv, Send, Stream, StreamId};
-use crate::codec::{Codec, RecvError, SendError, UserError};
+use crate::codec::{Codec, RecvError, UserError};
+use crate::ext::{self, Protocol};
 use crate::frame::{self, Frame, Reason};
 use crate::proto::{peer, Open, Peer, WindowSize};
 use crate::{client, proto, server};
@@ -10,10 +12,11 @@ use bytes::{Buf, Bytes};
 use http::{HeaderMap, Request, Response};
 use std::task::{Context, Poll, Waker};
 use tokio::io::AsyncWrite;
+use tokio::time::Instant;
 
 use crate::PollExt;
 use std::sync::{Arc, Mutex};
-use std::{fmt, io};
+use std::{cmp, fmt, io};
 
 #[derive(Debug)]
 pub(crate) struct Streams<B, P>
@@ -76,6 +79,9 @@ struct Inner {
     /// Stores stream state
     store: Store,
 
+    /// Deadlines of requests sent with a timeout.
+    deadlines: Deadlines,
+
     /// The number of stream refs to this shared state.
     refs: usize,
 }
@@ -127,6 +133,10 @@ where
             .set_target_connection_window(size, &mut me.actions.task)
     }
 
+    pub fn recv_init_window_size(&self) -> WindowSize {
+        self.inner.lock().unwrap().actions.recv.init_window_sz()
+    }
+
     pub fn next_incoming(&mut self) -> Option<StreamRef<B>> {
         let mut me = self.inner.lock().unwrap();
         let me = &mut *me;
@@ -140,6 +150,12 @@ where
             // TODO: ideally, OpaqueStreamRefs::new would do this, but we're holding
             // the lock, so it can't.
             me.refs += 1;
+
+            // Remotely reset streams are only counted until accepted.
+            if stream.state.is_remote_reset() {
+                me.counts.dec_num_remote_reset_streams();
+            }
+
             StreamRef {
                 opaque: OpaqueStreamRef::new(self.inner.clone(), stream),
                 send_buffer: self.send_buffer.clone(),
@@ -160,6 +176,14 @@ where
         me.actions.recv.send_pending_refusal(cx, dst)
     }
 
+    /// Resets the requests whose timeout has elapsed with `CANCEL`.
+    ///
+    /// Returns `true` if any stream was reset.
+    pub fn poll_request_deadlines(&mut self, cx: &mut Context) -> bool {
+        let mut me = self.inner.lock().unwrap();
+        me.poll_request_deadlines(cx, &self.send_buffer)
+    }
+
     pub fn clear_expired_reset_streams(&mut self) {
         let mut me = self.inner.lock().unwrap();
         let me = &mut *me;
@@ -205,18 +229,32 @@ where
         me.actions.recv.apply_local_settings(frame, &mut me.store)
     }
 
+    pub fn is_extended_connect_protocol_enabled(&self) -> bool {
+        self.inner
+            .lock()
+            .unwrap()
+            .actions
+            .send
+            .is_extended_connect_protocol_enabled()
+    }
+
+    pub fn origin_set(&self) -> Option<Vec<ext::Origin>> {
+        self.inner.lock().unwrap().actions.recv.origin_set()
+    }
+
     pub fn send_request(
         &mut self,
         request: Request<()>,
         end_of_stream: bool,
         pending: Option<&OpaqueStreamRef>,
-    ) -> Result<StreamRef<B>, SendError> {
+    ) -> Result<StreamRef<B>, crate::Error> {
         use super::stream::ContentLength;
         use http::Method;
 
-        // TODO: There is a hazard with assigning a stream ID before the
-        // prioritize layer. If prioritization reorders new streams, this
-        // implicitly closes the earlier stream IDs.
+        // Assigning the stream ID before the prioritize layer is only safe
+        // because it writes the HEADERS frames of new streams in the order
+        // they were queued. Reordering them would implicitly close the
+        // earlier stream IDs.
         //
         // See: hyperium/h2#11
         let mut me = self.inner.lock().unwrap();
@@ -228,6 +266,14 @@ where
         me.actions.ensure_no_conn_error()?;
         me.actions.send.ensure_next_stream_id()?;
 
+        // An extended CONNECT request may only be sent once the server has
+        // advertised SETTINGS_ENABLE_CONNECT_PROTOCOL.
+        if request.extensions().get::<Protocol>().is_some()
+            && !me.actions.send.is_extended_connect_protocol_enabled()
+        {
+            return Err(UserError::ExtendedConnectProtocolNotSupported.into());
+        }
+
         // The `pending` argument is provided by the `Client`, and holds
         // a store `Key` of a `Stream` that may have been not been opened
         // yet.
@@ -257,6 +303,8 @@ where
             stream.content_length = ContentLength::Head;
         }
 
+        let timeout = request.extensions().get::<ext::RequestTimeout>().cloned();
+
         // Convert the message
         let headers = client::Peer::convert_send_message(stream_id, request, end_of_stream)?;
 
@@ -282,6 +330,11 @@ where
         // closed state.
         debug_assert!(!stream.state.is_closed());
 
+        if let Some(timeout) = timeout {
+            let deadline = Instant::now() + timeout.duration();
+            me.deadlines.push(stream_id, deadline);
+        }
+
         // TODO: ideally, OpaqueStreamRefs::new would do this, but we're holding
         // the lock, so it can't.
         me.refs += 1;
@@ -291,6 +344,54 @@ where
             send_buffer: self.send_buffer.clone(),
         })
     }
+
+    /// Opens stream 1 for a request that was sent as an HTTP/1.1
+    /// `Upgrade: h2c` request.
+    ///
+    /// The request has already been sent in full, so the stream starts
+    /// half-closed (local), waiting for the response.
+    pub fn send_upgrade_request(
+        &mut self,
+        method: &http::Method,
+    ) -> Result<OpaqueStreamRef, crate::Error> {
+        use super::stream::ContentLength;
+        use http::Method;
+
+        let mut me = self.inner.lock().unwrap();
+        let me = &mut *me;
+
+        me.actions.ensure_no_conn_error()?;
+
+        // The upgrade request counts against the send stream limit like any
+        // other request, so it can't be opened if that limit is 0.
+        if !me.counts.can_inc_num_send_streams() {
+            return Err(UserError::Rejected.into());
+        }
+
+        let stream_id = me.actions.send.open()?;
+        assert_eq!(stream_id, StreamId::from(1), "upgrade must use stream 1");
+
+        let mut stream = Stream::new(
+            stream_id,
+            me.actions.send.init_window_sz(),
+            me.actions.recv.init_window_sz(),
+        );
+
+        if *method == Method::HEAD {
+            stream.content_length = ContentLength::Head;
+        }
+
+        let mut stream = me.store.insert(stream.id, stream);
+
+        stream.state.send_open(true)?;
+        me.counts.inc_num_send_streams(&mut stream);
+
+        // TODO: ideally, OpaqueStreamRefs::new would do this, but we're holding
+        // the lock, so it can't.
+        me.refs += 1;
+
+        Ok(OpaqueStreamRef::new(self.inner.clone(), &mut stream))
+    }
 }
 
 impl<B> DynStreams<'_, B> {
@@ -308,7 +409,7 @@ impl<B> DynStreams<'_, B> {
     pub fn recv_reset(&mut self, frame: frame::Reset) -> Result<(), RecvError> {
         let mut me = self.inner.lock().unwrap();
 
-        me.recv_reset(&self.send_buffer, frame)
+        me.recv_reset(self.peer, &self.send_buffer, frame)
     }
 
     /// Handle a received error and return the ID of the last processed stream.
@@ -331,6 +432,47 @@ impl<B> DynStreams<'_, B> {
         me.recv_window_update(&self.send_buffer, frame)
     }
 
+    pub fn recv_priority(&mut self, frame: frame::Priority) {
+        let mut me = self.inner.lock().unwrap();
+        me.recv_priority(frame)
+    }
+
+    pub fn recv_priority_update(&mut self, frame: frame::PriorityUpdate) -> Result<(), RecvError> {
+        let mut me = self.inner.lock().unwrap();
+        me.recv_priority_update(self.peer, frame)
+    }
+
+    pub fn recv_origin(&mut self, frame: frame::Origin) {
+        // Only servers send ORIGIN frames; servers ignore them.
+        if self.peer.is_server() {
+            tracing::trace!("ignoring ORIGIN frame received by server");
+            return;
+        }
+
+        let mut me = self.inner.lock().unwrap();
+        me.actions.recv.recv_origin(frame);
+    }
+
+    /// Checks the stream of a registered extension frame received from the
+    /// peer.
+    ///
+    /// Returns `false` if the frame should be ignored because the stream is
+    /// closed. Extension frames on idle streams are a connection error.
+    pub fn recv_extension_frame(&mut self, id: StreamId) -> Result<bool, RecvError> {
+        let mut me = self.inner.lock().unwrap();
+        let me = &mut *me;
+
+        match me.store.find_mut(&id) {
+            Some(stream) if !stream.state.is_idle() => Ok(!stream.state.is_closed()),
+            _ => {
+                me.actions
+                    .ensure_not_idle(self.peer, id)
+                    .map_err(RecvError::Connection)?;
+                Ok(false)
+            }
+        }
+    }
+
     pub fn recv_push_promise(&mut self, frame: frame::PushPromise) -> Result<(), RecvError> {
         let mut me = self.inner.lock().unwrap();
         me.recv_push_promise(&self.send_buffer, frame)
@@ -349,6 +491,17 @@ impl<B> DynStreams<'_, B> {
     pub fn send_go_away(&mut self, last_processed_id: StreamId) {
         let mut me = self.inner.lock().unwrap();
         me.actions.recv.go_away(last_processed_id);
+
+        // A client that is going away won't send any more requests.
+        if !self.peer.is_server() {
+            me.actions.send.go_away();
+        }
+    }
+
+    /// Resets every stream that is not closed yet.
+    pub fn send_reset_all(&mut self, reason: Reason) {
+        let mut me = self.inner.lock().unwrap();
+        me.send_reset_all(self.send_buffer, reason)
     }
 }
 
@@ -363,6 +516,7 @@ impl Inner {
                 conn_error: None,
             },
             store: Store::new(),
+            deadlines: Deadlines::default(),
             refs: 1,
         }))
     }
@@ -375,9 +529,10 @@ impl Inner {
     ) -> Result<(), RecvError> {
         let id = frame.stream_id();
 
-        // The GOAWAY process has begun. All streams with a greater ID than
-        // specified as part of GOAWAY should be ignored.
-        if id > self.actions.recv.max_stream_id() {
+        // The GOAWAY process has begun. All streams initiated by the peer
+        // with a greater ID than specified as part of GOAWAY should be
+        // ignored.
+        if id > self.actions.recv.max_stream_id() && !peer.is_local_init(id) {
             tracing::trace!(
                 "id ({:?}) > max_stream_id ({:?}), ignoring HEADERS",
                 id,
@@ -386,6 +541,8 @@ impl Inner {
             return Ok(());
         }
 
+        let is_request = peer.is_server() && !self.store.contains_id(&id);
+
         let key = match self.store.find_entry(id) {
             Entry::Occupied(e) => e.key(),
             Entry::Vacant(e) => {
@@ -428,6 +585,21 @@ impl Inner {
             }
         };
 
+        if let Some(dep) = frame.stream_dep() {
+            self.actions.send.recv_priority(id, dep, &self.store);
+        }
+
+        // The priority of a request is signalled by its `priority` header,
+        // unless a PRIORITY_UPDATE frame was received first.
+        if is_request {
+            if let Some(value) = frame.fields().get("priority") {
+                let priority = ext::Priority::parse(value.as_bytes());
+                self.actions
+                    .send
+                    .recv_priority_update(id, priority, false, &self.store);
+            }
+        }
+
         let stream = self.store.resolve(key);
 
         if stream.state.is_local_reset() {
@@ -559,6 +731,7 @@ impl Inner {
 
     fn recv_reset<B>(
         &mut self,
+        peer: peer::Dyn,
         send_buffer: &SendBuffer<B>,
         frame: frame::Reset,
     ) -> Result<(), RecvError> {
@@ -569,9 +742,10 @@ impl Inner {
             return Err(RecvError::Connection(Reason::PROTOCOL_ERROR));
         }
 
-        // The GOAWAY process has begun. All streams with a greater ID than
-        // specified as part of GOAWAY should be ignored.
-        if id > self.actions.recv.max_stream_id() {
+        // The GOAWAY process has begun. All streams initiated by the peer
+        // with a greater ID than specified as part of GOAWAY should be
+        // ignored.
+        if id > self.actions.recv.max_stream_id() && !peer.is_local_init(id) {
             tracing::trace!(
                 "id ({:?}) > max_stream_id ({:?}), ignoring RST_STREAM",
                 id,
@@ -598,7 +772,27 @@ impl Inner {
         let actions = &mut self.actions;
 
         self.counts.transition(stream, |counts, stream| {
+            if !stream.state.is_closed() {
+                counts.inc_num_remote_resets();
+            }
+            let was_remote_reset = stream.state.is_remote_reset();
             actions.recv.recv_reset(frame, stream);
+
+            // Streams that are reset before the user accepts them still cost
+            // work, without counting against the concurrency limit, so the
+            // number of such streams is limited. A stream that was already
+            // closed may be left as is, and then isn't counted.
+            if stream.is_pending_accept && !was_remote_reset && stream.state.is_remote_reset() {
+                if !counts.can_inc_num_remote_reset_streams() {
+                    tracing::warn!(
+                        "recv_reset; remotely-reset pending-accept streams reached limit ({:?})",
+                        counts.max_remote_reset_streams(),
+                    );
+                    return Err(RecvError::Connection(Reason::ENHANCE_YOUR_CALM));
+                }
+                counts.inc_num_remote_reset_streams();
+            }
+
             actions.send.recv_err(send_buffer, stream, counts);
             assert!(stream.state.is_closed());
             Ok(())
@@ -644,6 +838,42 @@ impl Inner {
         Ok(())
     }
 
+    fn recv_priority(&mut self, frame: frame::Priority) {
+        // PRIORITY frames may be received for streams in any state,
+        // including idle streams, which may be depended on by other streams.
+        self.actions
+            .send
+            .recv_priority(frame.stream_id(), frame.dependency(), &self.store);
+    }
+
+    fn recv_priority_update(
+        &mut self,
+        peer: peer::Dyn,
+        frame: frame::PriorityUpdate,
+    ) -> Result<(), RecvError> {
+        // PRIORITY_UPDATE frames are only sent by clients.
+        if !peer.is_server() {
+            proto_err!(conn: "recv_priority_update: client received PRIORITY_UPDATE");
+            return Err(RecvError::Connection(Reason::PROTOCOL_ERROR));
+        }
+
+        let id = frame.prioritized_id();
+
+        // Updates for idle streams are kept until the stream is opened, but
+        // updates for closed streams are ignored.
+        if !self.store.contains_id(&id) && self.actions.may_have_forgotten_stream(peer, id) {
+            tracing::trace!(?id, "ignoring PRIORITY_UPDATE for closed stream");
+            return Ok(());
+        }
+
+        let priority = ext::Priority::parse(frame.field_value());
+        self.actions
+            .send
+            .recv_priority_update(id, priority, true, &self.store);
+
+        Ok(())
+    }
+
     fn recv_err<B>(&mut self, send_buffer: &SendBuffer<B>, err: &proto::Error) -> StreamId {
         let actions = &mut self.actions;
         let counts = &mut self.counts;
@@ -681,7 +911,10 @@ impl Inner {
 
         actions.send.recv_go_away(last_stream_id)?;
 
-        let err = frame.reason().into();
+        let err = proto::Error::GoAway {
+            frame: frame.clone(),
+            unprocessed: true,
+        };
 
         self.store
             .for_each(|stream| {
@@ -713,12 +946,13 @@ impl Inner {
         // First, ensure that the initiating stream is still in a valid state.
         let parent_key = match self.store.find_mut(&id) {
             Some(stream) => {
-                // The GOAWAY process has begun. All streams with a greater ID
-                // than specified as part of GOAWAY should be ignored.
-                if id > self.actions.recv.max_stream_id() {
+                // The GOAWAY process has begun. Streams promised with a
+                // greater ID than specified as part of GOAWAY should be
+                // ignored.
+                if promised_id > self.actions.recv.max_stream_id() {
                     tracing::trace!(
                         "id ({:?}) > max_stream_id ({:?}), ignoring PUSH_PROMISE",
-                        id,
+                        promised_id,
                         self.actions.recv.max_stream_id()
                     );
                     return Ok(());
@@ -886,12 +1120,64 @@ impl Inner {
         self.actions
             .send_reset(stream, reason, &mut self.counts, send_buffer);
     }
+
+    fn poll_request_deadlines<B>(&mut self, cx: &mut Context, send_buffer: &SendBuffer<B>) -> bool {
+        let store = &mut self.store;
+        let expired = self.deadlines.poll_expired(cx, |id| {
+            store
+                .find_mut(&id)
+                .into_iter()
+                .any(|stream| !stream.state.is_closed())
+        });
+
+        if expired.is_empty() {
+            return false;
+        }
+
+        let mut send_buffer = send_buffer.inner.lock().unwrap();
+        let send_buffer = &mut *send_buffer;
+
+        for id in expired {
+            if let Some(stream) = self.store.find_mut(&id) {
+                tracing::debug!("request timed out; resetting stream={:?}", id);
+                self.actions
+                    .send_timeout(stream, &mut self.counts, send_buffer);
+            }
+        }
+
+        true
+    }
+
+    fn send_reset_all<B>(&mut self, send_buffer: &SendBuffer<B>, reason: Reason) {
+        let actions = &mut self.actions;
+        let counts = &mut self.counts;
+        let mut send_buffer = send_buffer.inner.lock().unwrap();
+        let send_buffer = &mut *send_buffer;
+
+        self.store
+            .for_each(|stream| {
+                if !stream.state.is_closed() {
+                    actions.send_reset(stream, reason, counts, send_buffer);
+                }
+                Ok::<_, ()>(())
+            })
+            .unwrap();
+    }
 }
 
 impl<B> Streams<B, client::Peer>
 where
     B: Buf,
 {
+    /// Returns `true` if new requests may still be sent on this connection.
+    ///
+    /// This is not the case after receiving or sending a GOAWAY frame, after
+    /// a connection error, or once the stream IDs are exhausted.
+    pub fn can_open_streams(&self) -> bool {
+        let me = self.inner.lock().unwrap();
+        me.actions.ensure_no_conn_error().is_ok() && me.actions.send.ensure_next_stream_id().is_ok()
+    }
+
     pub fn poll_pending_open(
         &mut self,
         cx: &Context,
@@ -943,6 +1229,13 @@ where
         self.inner.lock().unwrap().counts.max_send_streams()
     }
 
+    /// Returns the number of locally initiated streams, including those
+    /// waiting for the send concurrency limit before they can be opened.
+    pub(crate) fn num_send_streams(&self) -> usize {
+        let me = self.inner.lock().unwrap();
+        me.counts.num_send_streams() + me.actions.send.num_pending_open()
+    }
+
     pub(crate) fn max_recv_streams(&self) -> usize {
         self.inner.lock().unwrap().counts.max_recv_streams()
     }
@@ -968,6 +1261,14 @@ where
         let me = self.inner.lock().unwrap();
         me.store.num_wired_streams()
     }
+
+    /// Fills in the stream counters and connection windows of `dst`.
+    pub(crate) fn fill_stats(&self, dst: &mut crate::ConnectionStats) {
+        let me = self.inner.lock().unwrap();
+        me.counts.fill_stats(dst);
+        dst.send_window = me.actions.send.connection_window_size();
+        dst.recv_window = me.actions.recv.connection_window_size();
+    }
 }
 
 // no derive because we don't need B and P to be Clone.
@@ -1048,6 +1349,21 @@ impl<B> StreamRef<B> {
         })
     }
 
+    pub fn send_alt_svc(&mut self, field_value: Bytes) -> Result<(), UserError> {
+        let mut me = self.opaque.inner.lock().unwrap();
+        let me = &mut *me;
+
+        let mut stream = me.store.resolve(self.opaque.key);
+        let actions = &mut me.actions;
+        let mut send_buffer = self.send_buffer.inner.lock().unwrap();
+        let send_buffer = &mut *send_buffer;
+
+        let frame = frame::AltSvc::new(stream.id, Bytes::new(), field_value);
+        actions
+            .send
+            .send_alt_svc(frame, send_buffer, &mut stream, &mut actions.task)
+    }
+
     pub fn send_reset(&mut self, reason: Reason) {
         let mut me = self.opaque.inner.lock().unwrap();
         let me = &mut *me;
@@ -1154,6 +1470,20 @@ impl<B> StreamRef<B> {
         me.store.resolve(self.opaque.key).is_pending_open
     }
 
+    /// Changes the priority of the stream. Clients also let the server know
+    /// by sending a PRIORITY_UPDATE frame.
+    pub fn set_priority(&mut self, priority: ext::Priority) {
+        let mut me = self.opaque.inner.lock().unwrap();
+        let me = &mut *me;
+
+        let mut stream = me.store.resolve(self.opaque.key);
+        let send_update = !me.counts.peer().is_server() && !stream.state.is_closed();
+
+        me.actions
+            .send
+            .set_priority(&mut stream, priority, send_update, &mut me.actions.task)
+    }
+
     /// Request capacity to send data
     pub fn reserve_capacity(&mut self, capacity: WindowSize) {
         let mut me = self.opaque.inner.lock().unwrap();
@@ -1278,7 +1608,27 @@ impl OpaqueStreamRef {
 
         let mut stream = me.store.resolve(self.key);
 
-        me.actions.recv.poll_data(cx, &mut stream)
+        let res = me.actions.recv.poll_data(cx, &mut stream);
+
+        if let Poll::Ready(Some(Ok(ref data))) = res {
+            if me.actions.recv.is_auto_release_capacity(&stream) {
+                // The user may have released some of it already.
+                let capacity = cmp::min(data.len() as WindowSize, stream.in_flight_recv_data);
+                if capacity > 0 {
+                    me.actions
+                        .recv
+                        .release_capacity(capacity, &mut stream, &mut me.actions.task)
+                        .expect("capacity is in flight");
+                }
+            }
+        }
+
+        res
+    }
+
+    pub fn set_auto_release_capacity(&mut self, enabled: bool) {
+        let mut me = self.inner.lock().unwrap();
+        me.store.resolve(self.key).auto_release_capacity = Some(enabled);
     }
 
     pub fn poll_trailers(&mut self, cx: &Context) -> Poll<Option<Result<HeaderMap, proto::Error>>> {
@@ -1470,6 +1820,23 @@ impl Actions {
         });
     }
 
+    /// Resets a stream with `CANCEL` because its request timed out.
+    fn send_timeout<B>(
+        &mut self,
+        stream: store::Ptr,
+        counts: &mut Counts,
+        send_buffer: &mut Buffer<Frame<B>>,
+    ) {
+        counts.transition(stream, |counts, stream| {
+            self.send
+                .send_reset(Reason::CANCEL, send_buffer, stream, counts, &mut self.task);
+            stream.state.set_timed_out();
+            self.recv.enqueue_reset_expiration(stream, counts);
+            // Wake the `ResponseFuture` or `RecvStream`
+            stream.notify_recv();
+        });
+    }
+
     fn reset_on_recv_stream_err<B>(
         &mut self,
         buffer: &mut Buffer<Frame<B>>,
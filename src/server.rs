@@ -116,17 +116,18 @@
 //! [`TcpListener`]: https://docs.rs/tokio-core/0.1/tokio_core/net/struct.TcpListener.html
 
 use crate::codec::{Codec, RecvError, UserError};
+use crate::ext::{ExtensionFrames, Origin};
 use crate::frame::{self, Pseudo, PushPromiseHeaderError, Reason, Settings, StreamId};
 use crate::proto::{self, Config, Prioritized};
-use crate::{FlowControl, PingPong, RecvStream, SendStream};
+use crate::{ConnectionStats, FlowControl, PingPong, RecvStream, SendStream};
 
 use bytes::{Buf, Bytes};
-use http::{HeaderMap, Method, Request, Response};
+use http::{HeaderMap, HeaderValue, Method, Request, Response};
 use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use std::time::Duration;
-use std::{convert, fmt, io, mem};
+use std::{cmp, convert, fmt, io, mem};
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tracing::instrument::{Instrument, Instrumented};
 
@@ -150,6 +151,10 @@ pub struct Handshake<T, B: Buf = Bytes> {
     builder: Builder,
     /// The current state of the handshake.
     state: Handshaking<T, B>,
+    /// The HTTP/1.1 upgrade request the connection starts from, if any.
+    upgrade: Option<Upgrade>,
+    /// Bytes that were already read from the I/O before the handshake.
+    prefix: Bytes,
     /// Span tracking the handshake
     span: tracing::Span,
 }
@@ -240,11 +245,57 @@ pub struct Builder {
     /// Maximum number of locally reset streams to keep at a time.
     reset_stream_max: usize,
 
+    /// Maximum amount of data each stream may buffer before it is sent.
+    max_send_buffer_size: usize,
+
+    /// Maximum number of remotely reset streams waiting to be accepted.
+    pending_accept_reset_stream_max: usize,
+
     /// Initial `Settings` frame to send as part of the handshake.
     settings: Settings,
 
     /// Initial target window size for new connections.
     initial_target_connection_window_size: Option<u32>,
+
+    /// Interval at which keep-alive PINGs are sent, if any.
+    keep_alive_interval: Option<Duration>,
+
+    /// How long to wait for a keep-alive PING to be acknowledged.
+    keep_alive_timeout: Duration,
+
+    /// Whether keep-alive PINGs are sent without any active streams.
+    keep_alive_while_idle: bool,
+
+    /// How long the connection may go without active streams, if limited.
+    idle_timeout: Option<Duration>,
+
+    /// Whether receive windows grow with the estimated bandwidth-delay
+    /// product.
+    adaptive_window: bool,
+
+    /// Whether received data releases its capacity automatically.
+    auto_release_capacity: bool,
+
+    /// Extension frame types to hand to the user.
+    extension_frame_types: Vec<u8>,
+
+    /// Maximum number of extension frames queued in each direction.
+    max_queued_extension_frames: usize,
+
+    /// Maximum number of CONTINUATION frames in a received header block.
+    max_continuation_frames: Option<usize>,
+
+    /// Maximum number of encoded bytes in a received header block.
+    max_header_block_size: Option<usize>,
+
+    /// Maximum size of the header table used to encode header blocks.
+    max_send_header_table_size: Option<usize>,
+
+    /// Maximum number of each type of control frame received per second.
+    max_control_frames_per_second: usize,
+
+    /// Maximum number of empty DATA frames received per second.
+    max_empty_data_frames_per_second: usize,
 }
 
 /// Send a response back to the client
@@ -302,6 +353,14 @@ enum Handshaking<T, B: Buf> {
     Empty,
 }
 
+/// An HTTP/1.1 `Upgrade: h2c` request, to be processed as stream 1.
+struct Upgrade {
+    /// The settings decoded from the `HTTP2-Settings` header.
+    settings: Result<Settings, frame::Error>,
+    /// The request, converted to a HEADERS frame.
+    request: frame::Headers,
+}
+
 /// Flush a Sink
 struct Flush<T, B> {
     codec: Option<Codec<T, B>>,
@@ -311,6 +370,9 @@ struct Flush<T, B> {
 struct ReadPreface<T, B> {
     codec: Option<Codec<T, B>>,
     pos: usize,
+    /// Bytes that were already read from the I/O, starting with (part of)
+    /// the preface.
+    prefix: Bytes,
 }
 
 #[derive(Debug)]
@@ -356,6 +418,31 @@ where
     Builder::new().handshake(io)
 }
 
+/// Returns `true` if `buf` starts with the HTTP/2.0 [client connection
+/// preface].
+///
+/// This does not consume anything, so it can be used to find out which
+/// protocol a client speaks from the first bytes read from a connection. The
+/// preface is 24 bytes long, and `buf` must contain all of them for this to
+/// return `true`.
+///
+/// See [`Builder::handshake_with_prefix`] to continue the handshake from the
+/// bytes that were read.
+///
+/// [client connection preface]: http://httpwg.org/specs/rfc7540.html#ConnectionHeader
+/// [`Builder::handshake_with_prefix`]: struct.Builder.html#method.handshake_with_prefix
+///
+/// # Examples
+///
+/// ```
+/// # use h2::server;
+/// assert!(server::starts_with_preface(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n"));
+/// assert!(!server::starts_with_preface(b"GET / HTTP/1.1\r\n"));
+/// ```
+pub fn starts_with_preface(buf: &[u8]) -> bool {
+    buf.starts_with(&PREFACE)
+}
+
 // ===== impl Connection =====
 
 impl<T, B> Connection<T, B>
@@ -363,7 +450,12 @@ where
     T: AsyncRead + AsyncWrite + Unpin,
     B: Buf + 'static,
 {
-    fn handshake2(io: T, builder: Builder) -> Handshake<T, B> {
+    fn handshake2(
+        io: T,
+        builder: Builder,
+        upgrade: Option<Upgrade>,
+        prefix: Bytes,
+    ) -> Handshake<T, B> {
         let span = tracing::trace_span!("server_handshake", io = %std::any::type_name::<T>());
         let entered = span.enter();
 
@@ -378,6 +470,18 @@ where
             codec.set_max_recv_header_list_size(max as usize);
         }
 
+        if let Some(max) = builder.max_continuation_frames {
+            codec.set_max_recv_continuation_frames(max);
+        }
+
+        if let Some(max) = builder.max_header_block_size {
+            codec.set_max_recv_header_block_size(max);
+        }
+
+        if let Some(max) = builder.max_send_header_table_size {
+            codec.set_max_send_header_table_size(max);
+        }
+
         // Send initial settings frame.
         codec
             .buffer(builder.settings.clone().into())
@@ -391,6 +495,8 @@ where
         Handshake {
             builder,
             state,
+            upgrade,
+            prefix,
             span,
         }
     }
@@ -506,7 +612,7 @@ where
     ///
     /// For graceful shutdowns, see [`graceful_shutdown`](Connection::graceful_shutdown).
     pub fn abrupt_shutdown(&mut self, reason: Reason) {
-        self.connection.go_away_from_user(reason);
+        self.connection.go_away_from_user(reason, Bytes::new());
     }
 
     /// Starts a [graceful shutdown][1] process.
@@ -523,6 +629,51 @@ where
         self.connection.go_away_gracefully();
     }
 
+    /// Starts a [graceful shutdown][1] process that gives active streams at
+    /// most `timeout` to complete.
+    ///
+    /// Must continue being polled to close connection.
+    ///
+    /// This works like [`graceful_shutdown`], but once `timeout` elapses,
+    /// streams that are still active are reset with the `CANCEL` error code,
+    /// and a final GOAWAY frame tells the client the last stream that was
+    /// processed. The connection is then closed. If the client stopped
+    /// reading, it is closed without waiting for these frames to be written.
+    ///
+    /// [1]: http://httpwg.org/specs/rfc7540.html#GOAWAY
+    /// [`graceful_shutdown`]: #method.graceful_shutdown
+    pub fn graceful_shutdown_with_timeout(&mut self, timeout: Duration) {
+        self.connection.go_away_gracefully_with_timeout(timeout);
+    }
+
+    /// Like [`abrupt_shutdown`], but the GOAWAY frame carries opaque
+    /// `debug_data`, such as a message explaining why the connection is
+    /// being closed.
+    ///
+    /// The debug data is meant for diagnostics. It must fit in a single frame
+    /// along with the rest of the GOAWAY payload, so anything past the
+    /// client's maximum frame size minus 8 bytes is truncated.
+    ///
+    /// [`abrupt_shutdown`]: #method.abrupt_shutdown
+    pub fn abrupt_shutdown_with_debug_data(&mut self, reason: Reason, debug_data: Bytes) {
+        self.connection.go_away_from_user(reason, debug_data);
+    }
+
+    /// Like [`graceful_shutdown`], but the GOAWAY frames carry opaque
+    /// `debug_data`, such as a message explaining why the connection is
+    /// being closed.
+    ///
+    /// The debug data is meant for diagnostics. It must fit in a single frame
+    /// along with the rest of the GOAWAY payload, so anything past the
+    /// client's maximum frame size minus 8 bytes is truncated. GOAWAY frames
+    /// sent because of a protocol error never carry it.
+    ///
+    /// [`graceful_shutdown`]: #method.graceful_shutdown
+    pub fn graceful_shutdown_with_debug_data(&mut self, debug_data: Bytes) {
+        self.connection.set_go_away_debug_data(debug_data);
+        self.connection.go_away_gracefully();
+    }
+
     /// Takes a `PingPong` instance from the connection.
     ///
     /// # Note
@@ -532,6 +683,72 @@ where
         self.connection.take_user_pings().map(PingPong::new)
     }
 
+    /// Returns a snapshot of the connection's statistics.
+    ///
+    /// This includes the number of bytes and frames sent and received, the
+    /// number of streams opened, closed and reset by each side, the current
+    /// connection-level flow control windows, and the size of the HPACK
+    /// dynamic tables.
+    pub fn stats(&self) -> ConnectionStats {
+        self.connection.stats()
+    }
+
+    /// Sends an [ORIGIN frame] listing the origins that this server is
+    /// authoritative for.
+    ///
+    /// Clients may then send requests for any of these origins on this
+    /// connection, instead of opening a new one. ORIGIN frames add to the
+    /// origins sent before, so this may be called more than once. The frame
+    /// is best sent right after the handshake, before any responses.
+    ///
+    /// Origins that do not fit in a single frame are sent in several.
+    ///
+    /// [ORIGIN frame]: https://www.rfc-editor.org/rfc/rfc8336.html
+    pub fn send_origin(&mut self, origins: Vec<Origin>) {
+        self.connection.send_origin(origins);
+    }
+
+    /// Sends an [ALTSVC frame] advertising alternative services for `origin`.
+    ///
+    /// `field_value` uses the syntax of the `Alt-Svc` header field, such as
+    /// `h3=":443"; ma=3600`. To advertise alternative services for the
+    /// origin of a single request, use [`SendResponse::send_alt_svc`]
+    /// instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame would be larger than the smallest
+    /// allowed frame size.
+    ///
+    /// [ALTSVC frame]: https://www.rfc-editor.org/rfc/rfc7838.html#section-4
+    /// [`SendResponse::send_alt_svc`]: struct.SendResponse.html#method.send_alt_svc
+    pub fn send_alt_svc(
+        &mut self,
+        origin: Origin,
+        field_value: HeaderValue,
+    ) -> Result<(), crate::Error> {
+        let len = 2 + origin.as_str().len() + field_value.len();
+        if len > frame::DEFAULT_MAX_FRAME_SIZE as usize {
+            return Err(UserError::PayloadTooBig.into());
+        }
+
+        self.connection
+            .send_alt_svc(origin, Bytes::copy_from_slice(field_value.as_bytes()));
+        Ok(())
+    }
+
+    /// Takes an `ExtensionFrames` handle from the connection, used to send
+    /// extension frames and receive those of the registered types.
+    ///
+    /// # Note
+    ///
+    /// This may only be called once. Calling multiple times will return `None`.
+    pub fn extension_frames(&mut self) -> Option<ExtensionFrames> {
+        self.connection
+            .take_user_extensions()
+            .map(ExtensionFrames::new)
+    }
+
     /// Returns the maximum number of concurrent streams that may be initiated
     /// by the server on this connection.
     ///
@@ -584,21 +801,700 @@ where
             .field("connection", &self.connection)
             .finish()
     }
-}
-
-// ===== impl Builder =====
+}
+
+// ===== impl Builder =====
+
+impl Builder {
+    /// Returns a new server builder instance initialized with default
+    /// configuration values.
+    ///
+    /// Configuration methods can be chained on the return value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::server::*;
+    /// #
+    /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Handshake<T>
+    /// # {
+    /// // `server_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let server_fut = Builder::new()
+    ///     .initial_window_size(1_000_000)
+    ///     .max_concurrent_streams(1000)
+    ///     .handshake(my_io);
+    /// # server_fut
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn new() -> Builder {
+        Builder {
+            reset_stream_duration: Duration::from_secs(proto::DEFAULT_RESET_STREAM_SECS),
+            reset_stream_max: proto::DEFAULT_RESET_STREAM_MAX,
+            max_send_buffer_size: proto::DEFAULT_MAX_SEND_BUFFER_SIZE,
+            pending_accept_reset_stream_max: proto::DEFAULT_REMOTE_RESET_STREAM_MAX,
+            settings: Settings::default(),
+            initial_target_connection_window_size: None,
+            keep_alive_interval: None,
+            keep_alive_timeout: Duration::from_secs(proto::DEFAULT_KEEP_ALIVE_TIMEOUT_SECS),
+            keep_alive_while_idle: false,
+            idle_timeout: None,
+            adaptive_window: false,
+            auto_release_capacity: false,
+            extension_frame_types: Vec::new(),
+            max_queued_extension_frames: proto::DEFAULT_MAX_QUEUED_EXTENSION_FRAMES,
+            max_continuation_frames: None,
+            max_header_block_size: None,
+            max_send_header_table_size: None,
+            max_control_frames_per_second: proto::DEFAULT_MAX_CONTROL_FRAMES_PER_SECOND,
+            max_empty_data_frames_per_second: proto::DEFAULT_MAX_EMPTY_DATA_FRAMES_PER_SECOND,
+        }
+    }
+
+    /// Indicates the initial window size (in octets) for stream-level
+    /// flow control for received data.
+    ///
+    /// The initial window of a stream is used as part of flow control. For more
+    /// details, see [`FlowControl`].
+    ///
+    /// The default value is 65,535.
+    ///
+    /// [`FlowControl`]: ../struct.FlowControl.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::server::*;
+    /// #
+    /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Handshake<T>
+    /// # {
+    /// // `server_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let server_fut = Builder::new()
+    ///     .initial_window_size(1_000_000)
+    ///     .handshake(my_io);
+    /// # server_fut
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn initial_window_size(&mut self, size: u32) -> &mut Self {
+        self.settings.set_initial_window_size(Some(size));
+        self
+    }
+
+    /// Indicates the initial window size (in octets) for connection-level flow control
+    /// for received data.
+    ///
+    /// The initial window of a connection is used as part of flow control. For more details,
+    /// see [`FlowControl`].
+    ///
+    /// The default value is 65,535.
+    ///
+    /// [`FlowControl`]: ../struct.FlowControl.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::server::*;
+    /// #
+    /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Handshake<T>
+    /// # {
+    /// // `server_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let server_fut = Builder::new()
+    ///     .initial_connection_window_size(1_000_000)
+    ///     .handshake(my_io);
+    /// # server_fut
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn initial_connection_window_size(&mut self, size: u32) -> &mut Self {
+        self.initial_target_connection_window_size = Some(size);
+        self
+    }
+
+    /// Indicates the size (in octets) of the largest HTTP/2.0 frame payload that the
+    /// configured server is able to accept.
+    ///
+    /// The sender may send data frames that are **smaller** than this value,
+    /// but any data larger than `max` will be broken up into multiple `DATA`
+    /// frames.
+    ///
+    /// The value **must** be between 16,384 and 16,777,215. The default value is 16,384.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::server::*;
+    /// #
+    /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Handshake<T>
+    /// # {
+    /// // `server_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let server_fut = Builder::new()
+    ///     .max_frame_size(1_000_000)
+    ///     .handshake(my_io);
+    /// # server_fut
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `max` is not within the legal range specified
+    /// above.
+    pub fn max_frame_size(&mut self, max: u32) -> &mut Self {
+        self.settings.set_max_frame_size(Some(max));
+        self
+    }
+
+    /// Sets the max size of received header frames.
+    ///
+    /// This advisory setting informs a peer of the maximum size of header list
+    /// that the sender is prepared to accept, in octets. The value is based on
+    /// the uncompressed size of header fields, including the length of the name
+    /// and value in octets plus an overhead of 32 octets for each header field.
+    ///
+    /// This setting is also used to limit the maximum amount of data that is
+    /// buffered to decode HEADERS frames.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::server::*;
+    /// #
+    /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Handshake<T>
+    /// # {
+    /// // `server_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let server_fut = Builder::new()
+    ///     .max_header_list_size(16 * 1024)
+    ///     .handshake(my_io);
+    /// # server_fut
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn max_header_list_size(&mut self, max: u32) -> &mut Self {
+        self.settings.set_max_header_list_size(Some(max));
+        self
+    }
+
+    /// Sets the header table size.
+    ///
+    /// This setting informs the peer of the maximum size of the header
+    /// compression table used to decode header blocks, in octets. A smaller
+    /// table uses less memory per connection, at the cost of less effective
+    /// header compression.
+    ///
+    /// The new size is enforced once the peer has acknowledged the setting
+    /// and shrunk its table with a dynamic table size update. Header blocks
+    /// that don't do so are treated as a connection error.
+    ///
+    /// The default value is 4,096.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::server::*;
+    /// #
+    /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Handshake<T>
+    /// # {
+    /// // `server_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let server_fut = Builder::new()
+    ///     .header_table_size(1024)
+    ///     .handshake(my_io);
+    /// # server_fut
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn header_table_size(&mut self, size: u32) -> &mut Self {
+        self.settings.set_header_table_size(Some(size));
+        self
+    }
+
+    /// Sets the maximum size of the header compression table used to encode
+    /// header blocks, in octets.
+    ///
+    /// The peer's `SETTINGS_HEADER_TABLE_SIZE` is the largest table it lets
+    /// this endpoint use. This setting caps the table below that, whatever
+    /// the peer advertises. Any change to the table size is signalled to the
+    /// peer with a dynamic table size update at the start of the next header
+    /// block.
+    ///
+    /// By default, the table may grow to the peer's header table size.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::server::*;
+    /// #
+    /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Handshake<T>
+    /// # {
+    /// // `server_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let server_fut = Builder::new()
+    ///     .max_send_header_table_size(0)
+    ///     .handshake(my_io);
+    /// # server_fut
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn max_send_header_table_size(&mut self, max: usize) -> &mut Self {
+        self.max_send_header_table_size = Some(max);
+        self
+    }
+
+    /// Sets the maximum number of CONTINUATION frames in a received header
+    /// block.
+    ///
+    /// A header block is sent as a HEADERS or PUSH_PROMISE frame followed by
+    /// any number of CONTINUATION frames. A peer sending endless tiny
+    /// CONTINUATION frames keeps the connection busy without ever completing
+    /// the block, so once a header block has more CONTINUATION frames than
+    /// this, the connection is closed with the `ENHANCE_YOUR_CALM` error code.
+    ///
+    /// By default, the limit allows enough frames to carry a header list of
+    /// the [`max_header_list_size`] in frames of the max frame size, plus
+    /// 25%%, and at least 5 frames.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::server::*;
+    /// #
+    /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Handshake<T>
+    /// # {
+    /// // `server_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let server_fut = Builder::new()
+    ///     .max_continuation_frames(16)
+    ///     .handshake(my_io);
+    /// # server_fut
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    ///
+    /// [`max_header_list_size`]: #method.max_header_list_size
+    pub fn max_continuation_frames(&mut self, max: usize) -> &mut Self {
+        self.max_continuation_frames = Some(max);
+        self
+    }
+
+    /// Sets the maximum number of encoded bytes in a received header block.
+    ///
+    /// The HPACK encoded header block is counted over the HEADERS or
+    /// PUSH_PROMISE frame and all of its CONTINUATION frames. Once a header
+    /// block is larger than this, the connection is closed with the
+    /// `ENHANCE_YOUR_CALM` error code.
+    ///
+    /// By default, header blocks are only limited by the number of
+    /// CONTINUATION frames they span; see [`max_continuation_frames`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::server::*;
+    /// #
+    /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Handshake<T>
+    /// # {
+    /// // `server_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let server_fut = Builder::new()
+    ///     .max_header_block_size(64 * 1024)
+    ///     .handshake(my_io);
+    /// # server_fut
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    ///
+    /// [`max_continuation_frames`]: #method.max_continuation_frames
+    pub fn max_header_block_size(&mut self, max: usize) -> &mut Self {
+        self.max_header_block_size = Some(max);
+        self
+    }
+
+    /// Sets the maximum number of control frames of each type that the peer
+    /// may send per second.
+    ///
+    /// PING and SETTINGS frames must be acknowledged, and PRIORITY and
+    /// WINDOW_UPDATE frames update connection state, yet they cost the peer
+    /// almost nothing to send. This setting limits, separately for each of
+    /// these frame types, how many may be received in any one second.
+    /// Acknowledgements of frames sent by this endpoint are not counted.
+    ///
+    /// When the limit is exceeded, the connection is closed with a GOAWAY
+    /// frame with the `ENHANCE_YOUR_CALM` error code.
+    ///
+    /// The default value is 10,000.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::server::*;
+    /// #
+    /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Handshake<T>
+    /// # {
+    /// // `server_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let server_fut = Builder::new()
+    ///     .max_control_frames_per_second(1000)
+    ///     .handshake(my_io);
+    /// # server_fut
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn max_control_frames_per_second(&mut self, max: usize) -> &mut Self {
+        self.max_control_frames_per_second = max;
+        self
+    }
+
+    /// Sets the maximum number of empty DATA frames that the peer may send
+    /// per second.
+    ///
+    /// A DATA frame without any payload that doesn't end its stream has no
+    /// effect, but it still has to be processed. When more than this many
+    /// are received in any one second, the connection is closed with a
+    /// GOAWAY frame with the `ENHANCE_YOUR_CALM` error code.
+    ///
+    /// The default value is 100.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::server::*;
+    /// #
+    /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Handshake<T>
+    /// # {
+    /// // `server_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let server_fut = Builder::new()
+    ///     .max_empty_data_frames_per_second(10)
+    ///     .handshake(my_io);
+    /// # server_fut
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn max_empty_data_frames_per_second(&mut self, max: usize) -> &mut Self {
+        self.max_empty_data_frames_per_second = max;
+        self
+    }
+
+    /// Sets the maximum number of concurrent streams.
+    ///
+    /// The maximum concurrent streams setting only controls the maximum number
+    /// of streams that can be initiated by the remote peer. In other words,
+    /// when this setting is set to 100, this does not limit the number of
+    /// concurrent streams that can be created by the caller.
+    ///
+    /// It is recommended that this value be no smaller than 100, so as to not
+    /// unnecessarily limit parallelism. However, any value is legal, including
+    /// 0. If `max` is set to 0, then the remote will not be permitted to
+    /// initiate streams.
+    ///
+    /// Note that streams in the reserved state, i.e., push promises that have
+    /// been reserved but the stream has not started, do not count against this
+    /// setting.
+    ///
+    /// Also note that if the remote *does* exceed the value set here, it is not
+    /// a protocol level error. Instead, the `h2` library will immediately reset
+    /// the stream.
+    ///
+    /// See [Section 5.1.2] in the HTTP/2.0 spec for more details.
+    ///
+    /// [Section 5.1.2]: https://http2.github.io/http2-spec/#rfc.section.5.1.2
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::server::*;
+    /// #
+    /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Handshake<T>
+    /// # {
+    /// // `server_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let server_fut = Builder::new()
+    ///     .max_concurrent_streams(1000)
+    ///     .handshake(my_io);
+    /// # server_fut
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn max_concurrent_streams(&mut self, max: u32) -> &mut Self {
+        self.settings.set_max_concurrent_streams(Some(max));
+        self
+    }
+
+    /// Sets the maximum number of concurrent locally reset streams.
+    ///
+    /// When a stream is explicitly reset by either calling
+    /// [`SendResponse::send_reset`] or by dropping a [`SendResponse`] instance
+    /// before completing the stream, the HTTP/2.0 specification requires that
+    /// any further frames received for that stream must be ignored for "some
+    /// time".
+    ///
+    /// In order to satisfy the specification, internal state must be maintained
+    /// to implement the behavior. This state grows linearly with the number of
+    /// streams that are locally reset.
+    ///
+    /// The `max_concurrent_reset_streams` setting configures sets an upper
+    /// bound on the amount of state that is maintained. When this max value is
+    /// reached, the oldest reset stream is purged from memory.
+    ///
+    /// Once the stream has been fully purged from memory, any additional frames
+    /// received for that stream will result in a connection level protocol
+    /// error, forcing the connection to terminate.
+    ///
+    /// The default value is 10.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::server::*;
+    /// #
+    /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Handshake<T>
+    /// # {
+    /// // `server_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let server_fut = Builder::new()
+    ///     .max_concurrent_reset_streams(1000)
+    ///     .handshake(my_io);
+    /// # server_fut
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn max_concurrent_reset_streams(&mut self, max: usize) -> &mut Self {
+        self.reset_stream_max = max;
+        self
+    }
+
+    /// Sets the maximum send buffer size per stream.
+    ///
+    /// Data passed to [`SendStream::send_data`] is buffered until the peer's
+    /// flow control window allows it to be written. Once a stream has
+    /// buffered this much data, [`SendStream::poll_capacity`] stops reporting
+    /// additional capacity, whatever the size of the window. Capacity is
+    /// reported again as the buffered data is written to the connection.
+    ///
+    /// The default value is currently 400KB, but may change.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `max` is larger than `u32::MAX`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::server::*;
+    /// #
+    /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Handshake<T>
+    /// # {
+    /// // `server_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let server_fut = Builder::new()
+    ///     .max_send_buffer_size(64 * 1024)
+    ///     .handshake(my_io);
+    /// # server_fut
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    ///
+    /// [`SendStream::send_data`]: ../struct.SendStream.html#method.send_data
+    /// [`SendStream::poll_capacity`]: ../struct.SendStream.html#method.poll_capacity
+    pub fn max_send_buffer_size(&mut self, max: usize) -> &mut Self {
+        assert!(max <= u32::MAX as usize);
+        self.max_send_buffer_size = max;
+        self
+    }
+
+    /// Sets the maximum number of pending-accept remotely-reset streams.
+    ///
+    /// A client may open a stream and reset it right away. Such a stream is
+    /// closed before it is returned by [`Connection::accept`], so it does not
+    /// count against `max_concurrent_streams`, yet it still costs the server
+    /// work. Clients doing this over and over are attacking the server.
+    ///
+    /// This setting limits the number of streams that have been reset by the
+    /// client and not accepted yet. When the limit is exceeded, the
+    /// connection is closed with a GOAWAY frame with the
+    /// `ENHANCE_YOUR_CALM` error code. Accepting the streams, even reset
+    /// ones, makes room for more.
+    ///
+    /// The default value is 20.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::server::*;
+    /// #
+    /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Handshake<T>
+    /// # {
+    /// // `server_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let server_fut = Builder::new()
+    ///     .max_pending_accept_reset_streams(100)
+    ///     .handshake(my_io);
+    /// # server_fut
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    ///
+    /// [`Connection::accept`]: struct.Connection.html#method.accept
+    pub fn max_pending_accept_reset_streams(&mut self, max: usize) -> &mut Self {
+        self.pending_accept_reset_stream_max = max;
+        self
+    }
+
+    /// Sets the maximum number of concurrent locally reset streams.
+    ///
+    /// When a stream is explicitly reset by either calling
+    /// [`SendResponse::send_reset`] or by dropping a [`SendResponse`] instance
+    /// before completing the stream, the HTTP/2.0 specification requires that
+    /// any further frames received for that stream must be ignored for "some
+    /// time".
+    ///
+    /// In order to satisfy the specification, internal state must be maintained
+    /// to implement the behavior. This state grows linearly with the number of
+    /// streams that are locally reset.
+    ///
+    /// The `reset_stream_duration` setting configures the max amount of time
+    /// this state will be maintained in memory. Once the duration elapses, the
+    /// stream state is purged from memory.
+    ///
+    /// Once the stream has been fully purged from memory, any additional frames
+    /// received for that stream will result in a connection level protocol
+    /// error, forcing the connection to terminate.
+    ///
+    /// The default value is 30 seconds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::server::*;
+    /// # use std::time::Duration;
+    /// #
+    /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Handshake<T>
+    /// # {
+    /// // `server_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let server_fut = Builder::new()
+    ///     .reset_stream_duration(Duration::from_secs(10))
+    ///     .handshake(my_io);
+    /// # server_fut
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn reset_stream_duration(&mut self, dur: Duration) -> &mut Self {
+        self.reset_stream_duration = dur;
+        self
+    }
+
+    /// Enables the [extended CONNECT protocol].
+    ///
+    /// When enabled, the server advertises `SETTINGS_ENABLE_CONNECT_PROTOCOL`
+    /// and accepts `CONNECT` requests carrying a `:protocol` pseudo-header,
+    /// along with the `:scheme` and `:path` such requests must include. The
+    /// value of the pseudo-header is exposed as a [`Protocol`] in the
+    /// request's extensions.
+    ///
+    /// Requests using `:protocol` are reset with `PROTOCOL_ERROR` unless this
+    /// option is enabled.
+    ///
+    /// [extended CONNECT protocol]: https://datatracker.ietf.org/doc/html/rfc8441#section-4
+    /// [`Protocol`]: ../ext/struct.Protocol.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::server::*;
+    /// #
+    /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Handshake<T>
+    /// # {
+    /// // `server_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let server_fut = Builder::new()
+    ///     .enable_connect_protocol()
+    ///     .handshake(my_io);
+    /// # server_fut
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn enable_connect_protocol(&mut self) -> &mut Self {
+        self.settings.set_enable_connect_protocol(Some(1));
+        self
+    }
 
-impl Builder {
-    /// Returns a new server builder instance initialized with default
-    /// configuration values.
+    /// Sets an interval for HTTP/2.0 PING frames used to keep the connection
+    /// alive.
     ///
-    /// Configuration methods can be chained on the return value.
+    /// When the connection has not received a frame for `interval`, a PING is
+    /// sent to the remote. If the PING is not acknowledged within the
+    /// [`keep_alive_timeout`], the connection is closed and resolves with an
+    /// error for which [`Error::is_keep_alive_timeout`] returns `true`.
+    ///
+    /// Pass `None` to disable keep-alive PINGs, which is the default.
+    ///
+    /// [`keep_alive_timeout`]: #method.keep_alive_timeout
+    /// [`Error::is_keep_alive_timeout`]: ../struct.Error.html#method.is_keep_alive_timeout
     ///
     /// # Examples
     ///
     /// ```
     /// # use tokio::io::{AsyncRead, AsyncWrite};
     /// # use h2::server::*;
+    /// # use std::time::Duration;
     /// #
     /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
     /// # -> Handshake<T>
@@ -606,38 +1502,33 @@ impl Builder {
     /// // `server_fut` is a future representing the completion of the HTTP/2.0
     /// // handshake.
     /// let server_fut = Builder::new()
-    ///     .initial_window_size(1_000_000)
-    ///     .max_concurrent_streams(1000)
+    ///     .keep_alive_interval(Duration::from_secs(30))
     ///     .handshake(my_io);
     /// # server_fut
     /// # }
     /// #
     /// # pub fn main() {}
     /// ```
-    pub fn new() -> Builder {
-        Builder {
-            reset_stream_duration: Duration::from_secs(proto::DEFAULT_RESET_STREAM_SECS),
-            reset_stream_max: proto::DEFAULT_RESET_STREAM_MAX,
-            settings: Settings::default(),
-            initial_target_connection_window_size: None,
-        }
+    pub fn keep_alive_interval<D: Into<Option<Duration>>>(&mut self, interval: D) -> &mut Self {
+        self.keep_alive_interval = interval.into();
+        self
     }
 
-    /// Indicates the initial window size (in octets) for stream-level
-    /// flow control for received data.
+    /// Sets how long to wait for the acknowledgement of a keep-alive PING
+    /// before closing the connection.
     ///
-    /// The initial window of a stream is used as part of flow control. For more
-    /// details, see [`FlowControl`].
+    /// This has no effect unless [`keep_alive_interval`] is set.
     ///
-    /// The default value is 65,535.
+    /// The default value is 20 seconds.
     ///
-    /// [`FlowControl`]: ../struct.FlowControl.html
+    /// [`keep_alive_interval`]: #method.keep_alive_interval
     ///
     /// # Examples
     ///
     /// ```
     /// # use tokio::io::{AsyncRead, AsyncWrite};
     /// # use h2::server::*;
+    /// # use std::time::Duration;
     /// #
     /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
     /// # -> Handshake<T>
@@ -645,33 +1536,37 @@ impl Builder {
     /// // `server_fut` is a future representing the completion of the HTTP/2.0
     /// // handshake.
     /// let server_fut = Builder::new()
-    ///     .initial_window_size(1_000_000)
+    ///     .keep_alive_interval(Duration::from_secs(30))
+    ///     .keep_alive_timeout(Duration::from_secs(10))
     ///     .handshake(my_io);
     /// # server_fut
     /// # }
     /// #
     /// # pub fn main() {}
     /// ```
-    pub fn initial_window_size(&mut self, size: u32) -> &mut Self {
-        self.settings.set_initial_window_size(Some(size));
+    pub fn keep_alive_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.keep_alive_timeout = timeout;
         self
     }
 
-    /// Indicates the initial window size (in octets) for connection-level flow control
-    /// for received data.
+    /// Sets whether keep-alive PINGs are sent while the connection has no
+    /// active streams.
     ///
-    /// The initial window of a connection is used as part of flow control. For more details,
-    /// see [`FlowControl`].
+    /// When disabled, keep-alive PINGs are only sent while there are open
+    /// streams on the connection.
     ///
-    /// The default value is 65,535.
+    /// This has no effect unless [`keep_alive_interval`] is set.
     ///
-    /// [`FlowControl`]: ../struct.FlowControl.html
+    /// The default value is `false`.
+    ///
+    /// [`keep_alive_interval`]: #method.keep_alive_interval
     ///
     /// # Examples
     ///
     /// ```
     /// # use tokio::io::{AsyncRead, AsyncWrite};
     /// # use h2::server::*;
+    /// # use std::time::Duration;
     /// #
     /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
     /// # -> Handshake<T>
@@ -679,32 +1574,35 @@ impl Builder {
     /// // `server_fut` is a future representing the completion of the HTTP/2.0
     /// // handshake.
     /// let server_fut = Builder::new()
-    ///     .initial_connection_window_size(1_000_000)
+    ///     .keep_alive_interval(Duration::from_secs(30))
+    ///     .keep_alive_while_idle(true)
     ///     .handshake(my_io);
     /// # server_fut
     /// # }
     /// #
     /// # pub fn main() {}
     /// ```
-    pub fn initial_connection_window_size(&mut self, size: u32) -> &mut Self {
-        self.initial_target_connection_window_size = Some(size);
+    pub fn keep_alive_while_idle(&mut self, enabled: bool) -> &mut Self {
+        self.keep_alive_while_idle = enabled;
         self
     }
 
-    /// Indicates the size (in octets) of the largest HTTP/2.0 frame payload that the
-    /// configured server is able to accept.
+    /// Sets how long the connection may go without any active streams before
+    /// it is closed.
     ///
-    /// The sender may send data frames that are **smaller** than this value,
-    /// but any data larger than `max` will be broken up into multiple `DATA`
-    /// frames.
+    /// The timer starts whenever the connection has no open streams, which
+    /// includes right after the handshake, and is cancelled when a stream is
+    /// opened. When it expires, a GOAWAY frame with the `NO_ERROR` code is
+    /// sent and the connection is closed cleanly.
     ///
-    /// The value **must** be between 16,384 and 16,777,215. The default value is 16,384.
+    /// Pass `None` to keep idle connections open, which is the default.
     ///
     /// # Examples
     ///
     /// ```
     /// # use tokio::io::{AsyncRead, AsyncWrite};
     /// # use h2::server::*;
+    /// # use std::time::Duration;
     /// #
     /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
     /// # -> Handshake<T>
@@ -712,32 +1610,35 @@ impl Builder {
     /// // `server_fut` is a future representing the completion of the HTTP/2.0
     /// // handshake.
     /// let server_fut = Builder::new()
-    ///     .max_frame_size(1_000_000)
+    ///     .idle_timeout(Duration::from_secs(90))
     ///     .handshake(my_io);
     /// # server_fut
     /// # }
     /// #
     /// # pub fn main() {}
     /// ```
-    ///
-    /// # Panics
-    ///
-    /// This function panics if `max` is not within the legal range specified
-    /// above.
-    pub fn max_frame_size(&mut self, max: u32) -> &mut Self {
-        self.settings.set_max_frame_size(Some(max));
+    pub fn idle_timeout<D: Into<Option<Duration>>>(&mut self, timeout: D) -> &mut Self {
+        self.idle_timeout = timeout.into();
         self
     }
 
-    /// Sets the max size of received header frames.
+    /// Enables adaptive flow control.
     ///
-    /// This advisory setting informs a peer of the maximum size of header list
-    /// that the sender is prepared to accept, in octets. The value is based on
-    /// the uncompressed size of header fields, including the length of the name
-    /// and value in octets plus an overhead of 32 octets for each header field.
+    /// When enabled, the connection periodically sends PING frames while DATA
+    /// is being received in order to estimate the bandwidth-delay product of
+    /// the link. Whenever the peer comes close to filling the receive window
+    /// within a round trip, both the connection window and the initial stream
+    /// window are grown to match the estimate, up to 16MB.
     ///
-    /// This setting is also used to limit the maximum amount of data that is
-    /// buffered to decode HEADERS frames.
+    /// The windows configured with [`initial_window_size`] and
+    /// [`initial_connection_window_size`] are used as the starting point.
+    /// The estimate only ever raises a window: a connection window set with
+    /// `set_target_window_size` that is larger than the estimate is kept.
+    ///
+    /// The default value is `false`.
+    ///
+    /// [`initial_window_size`]: #method.initial_window_size
+    /// [`initial_connection_window_size`]: #method.initial_connection_window_size
     ///
     /// # Examples
     ///
@@ -751,41 +1652,70 @@ impl Builder {
     /// // `server_fut` is a future representing the completion of the HTTP/2.0
     /// // handshake.
     /// let server_fut = Builder::new()
-    ///     .max_header_list_size(16 * 1024)
+    ///     .adaptive_window(true)
     ///     .handshake(my_io);
     /// # server_fut
     /// # }
     /// #
     /// # pub fn main() {}
     /// ```
-    pub fn max_header_list_size(&mut self, max: u32) -> &mut Self {
-        self.settings.set_max_header_list_size(Some(max));
+    pub fn adaptive_window(&mut self, enabled: bool) -> &mut Self {
+        self.adaptive_window = enabled;
         self
     }
 
-    /// Sets the maximum number of concurrent streams.
+    /// Sets whether received data releases its flow control capacity
+    /// automatically.
     ///
-    /// The maximum concurrent streams setting only controls the maximum number
-    /// of streams that can be initiated by the remote peer. In other words,
-    /// when this setting is set to 100, this does not limit the number of
-    /// concurrent streams that can be created by the caller.
+    /// By default, data returned by [`RecvStream::poll_data`] keeps counting
+    /// against the stream and connection receive windows until the caller
+    /// passes its size to [`FlowControl::release_capacity`]. If the caller
+    /// forgets to, the windows are never replenished, and the peer eventually
+    /// stops sending data on the whole connection.
     ///
-    /// It is recommended that this value be no smaller than 100, so as to not
-    /// unnecessarily limit parallelism. However, any value is legal, including
-    /// 0. If `max` is set to 0, then the remote will not be permitted to
-    /// initiate streams.
+    /// When enabled, the capacity of each chunk of data is released as soon
+    /// as it is returned by `poll_data`. The setting can be changed for a
+    /// single stream with [`RecvStream::set_auto_release_capacity`].
     ///
-    /// Note that streams in the reserved state, i.e., push promises that have
-    /// been reserved but the stream has not started, do not count against this
-    /// setting.
+    /// The default value is `false`.
     ///
-    /// Also note that if the remote *does* exceed the value set here, it is not
-    /// a protocol level error. Instead, the `h2` library will immediately reset
-    /// the stream.
+    /// [`RecvStream::poll_data`]: ../struct.RecvStream.html#method.poll_data
+    /// [`FlowControl::release_capacity`]: ../struct.FlowControl.html#method.release_capacity
+    /// [`RecvStream::set_auto_release_capacity`]: ../struct.RecvStream.html#method.set_auto_release_capacity
     ///
-    /// See [Section 5.1.2] in the HTTP/2.0 spec for more details.
+    /// # Examples
     ///
-    /// [Section 5.1.2]: https://http2.github.io/http2-spec/#rfc.section.5.1.2
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::server::*;
+    /// #
+    /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
+    /// # -> Handshake<T>
+    /// # {
+    /// // `server_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let server_fut = Builder::new()
+    ///     .auto_release_capacity(true)
+    ///     .handshake(my_io);
+    /// # server_fut
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn auto_release_capacity(&mut self, enabled: bool) -> &mut Self {
+        self.auto_release_capacity = enabled;
+        self
+    }
+
+    /// Disables RFC 7540 priorities.
+    ///
+    /// This sends `SETTINGS_NO_RFC7540_PRIORITIES`, and the stream
+    /// dependencies and weights sent by the client in PRIORITY and HEADERS
+    /// frames are then ignored. Responses are still scheduled according to
+    /// [Extensible Priorities], which clients signal using the `priority`
+    /// header and PRIORITY_UPDATE frames.
+    ///
+    /// [Extensible Priorities]: https://www.rfc-editor.org/rfc/rfc9218.html
     ///
     /// # Examples
     ///
@@ -799,39 +1729,32 @@ impl Builder {
     /// // `server_fut` is a future representing the completion of the HTTP/2.0
     /// // handshake.
     /// let server_fut = Builder::new()
-    ///     .max_concurrent_streams(1000)
+    ///     .disable_rfc7540_priorities()
     ///     .handshake(my_io);
     /// # server_fut
     /// # }
     /// #
     /// # pub fn main() {}
     /// ```
-    pub fn max_concurrent_streams(&mut self, max: u32) -> &mut Self {
-        self.settings.set_max_concurrent_streams(Some(max));
+    pub fn disable_rfc7540_priorities(&mut self) -> &mut Self {
+        self.settings.set_no_rfc7540_priorities(Some(1));
         self
     }
 
-    /// Sets the maximum number of concurrent locally reset streams.
+    /// Registers an extension frame type to receive.
     ///
-    /// When a stream is explicitly reset by either calling
-    /// [`SendResponse::send_reset`] or by dropping a [`SendResponse`] instance
-    /// before completing the stream, the HTTP/2.0 specification requires that
-    /// any further frames received for that stream must be ignored for "some
-    /// time".
+    /// Frames of this type sent by the peer are handed to the
+    /// [`ExtensionFrames`] handle of the connection. Frames of unknown types
+    /// that were not registered are ignored, as required by RFC 7540.
     ///
-    /// In order to satisfy the specification, internal state must be maintained
-    /// to implement the behavior. This state grows linearly with the number of
-    /// streams that are locally reset.
+    /// Extension frames may be sent without registering their type.
     ///
-    /// The `max_concurrent_reset_streams` setting configures sets an upper
-    /// bound on the amount of state that is maintained. When this max value is
-    /// reached, the oldest reset stream is purged from memory.
+    /// [`ExtensionFrames`]: ../ext/struct.ExtensionFrames.html
     ///
-    /// Once the stream has been fully purged from memory, any additional frames
-    /// received for that stream will result in a connection level protocol
-    /// error, forcing the connection to terminate.
+    /// # Panics
     ///
-    /// The default value is 10.
+    /// Panics if `frame_type` is the type of a frame that is implemented by
+    /// this library.
     ///
     /// # Examples
     ///
@@ -845,46 +1768,47 @@ impl Builder {
     /// // `server_fut` is a future representing the completion of the HTTP/2.0
     /// // handshake.
     /// let server_fut = Builder::new()
-    ///     .max_concurrent_reset_streams(1000)
+    ///     .extension_frame_type(0xb0)
     ///     .handshake(my_io);
     /// # server_fut
     /// # }
     /// #
     /// # pub fn main() {}
     /// ```
-    pub fn max_concurrent_reset_streams(&mut self, max: usize) -> &mut Self {
-        self.reset_stream_max = max;
+    pub fn extension_frame_type(&mut self, frame_type: u8) -> &mut Self {
+        crate::ext::assert_extension_type(frame_type);
+        if !self.extension_frame_types.contains(&frame_type) {
+            self.extension_frame_types.push(frame_type);
+        }
         self
     }
 
-    /// Sets the maximum number of concurrent locally reset streams.
+    /// Sets the maximum number of extension frames queued in each direction.
     ///
-    /// When a stream is explicitly reset by either calling
-    /// [`SendResponse::send_reset`] or by dropping a [`SendResponse`] instance
-    /// before completing the stream, the HTTP/2.0 specification requires that
-    /// any further frames received for that stream must be ignored for "some
-    /// time".
+    /// Frames received from the peer wait in a queue until they are taken
+    /// with [`ExtensionFrames::recv_frame`]. If the peer sends more frames
+    /// than this while the queue is full, it is sending them faster than they
+    /// are handled, and the connection is closed with `ENHANCE_YOUR_CALM`.
     ///
-    /// In order to satisfy the specification, internal state must be maintained
-    /// to implement the behavior. This state grows linearly with the number of
-    /// streams that are locally reset.
+    /// Frames queued with [`ExtensionFrames::send_frame`] wait until the
+    /// connection writes them. Once this many are waiting, `send_frame` fails
+    /// until [`ExtensionFrames::poll_ready`] returns `Ready` again.
     ///
-    /// The `reset_stream_duration` setting configures the max amount of time
-    /// this state will be maintained in memory. Once the duration elapses, the
-    /// stream state is purged from memory.
+    /// The default value is 64.
     ///
-    /// Once the stream has been fully purged from memory, any additional frames
-    /// received for that stream will result in a connection level protocol
-    /// error, forcing the connection to terminate.
+    /// [`ExtensionFrames::recv_frame`]: ../ext/struct.ExtensionFrames.html#method.recv_frame
+    /// [`ExtensionFrames::send_frame`]: ../ext/struct.ExtensionFrames.html#method.send_frame
+    /// [`ExtensionFrames::poll_ready`]: ../ext/struct.ExtensionFrames.html#method.poll_ready
     ///
-    /// The default value is 30 seconds.
+    /// # Panics
+    ///
+    /// Panics if `max` is 0.
     ///
     /// # Examples
     ///
     /// ```
     /// # use tokio::io::{AsyncRead, AsyncWrite};
     /// # use h2::server::*;
-    /// # use std::time::Duration;
     /// #
     /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T)
     /// # -> Handshake<T>
@@ -892,15 +1816,19 @@ impl Builder {
     /// // `server_fut` is a future representing the completion of the HTTP/2.0
     /// // handshake.
     /// let server_fut = Builder::new()
-    ///     .reset_stream_duration(Duration::from_secs(10))
+    ///     .max_queued_extension_frames(16)
     ///     .handshake(my_io);
     /// # server_fut
     /// # }
     /// #
     /// # pub fn main() {}
     /// ```
-    pub fn reset_stream_duration(&mut self, dur: Duration) -> &mut Self {
-        self.reset_stream_duration = dur;
+    pub fn max_queued_extension_frames(&mut self, max: usize) -> &mut Self {
+        assert!(
+            max > 0,
+            "max_queued_extension_frames must be greater than 0"
+        );
+        self.max_queued_extension_frames = max;
         self
     }
 
@@ -965,7 +1893,123 @@ impl Builder {
         T: AsyncRead + AsyncWrite + Unpin,
         B: Buf + 'static,
     {
-        Connection::handshake2(io, self.clone())
+        Connection::handshake2(io, self.clone(), None, Bytes::new())
+    }
+
+    /// Creates a new configured HTTP/2.0 server backed by `io`, continuing
+    /// from bytes that were already read from it.
+    ///
+    /// This is meant for listeners that serve both HTTP/1.1 and HTTP/2.0 on
+    /// the same port: the first bytes of a connection are read to find out
+    /// which protocol the client speaks, for example with
+    /// [`starts_with_preface`]. If it's HTTP/2.0, those bytes are passed as
+    /// `prefix`, and are processed as if they were read from `io`.
+    ///
+    /// `prefix` must start with the client connection preface, or a part of
+    /// it, in which case the rest of the preface is read from `io`. It may
+    /// also contain bytes following the preface, such as the client's
+    /// SETTINGS frame.
+    ///
+    /// [`starts_with_preface`]: fn.starts_with_preface.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+    /// # use h2::server::*;
+    /// # use bytes::Bytes;
+    /// #
+    /// # async fn doc<T: AsyncRead + AsyncWrite + Unpin>(mut my_io: T)
+    /// # {
+    /// let mut buf = vec![0; 24];
+    /// let mut read = 0;
+    /// while read < buf.len() {
+    ///     match my_io.read(&mut buf[read..]).await.unwrap() {
+    ///         0 => return,
+    ///         n => read += n,
+    ///     }
+    /// }
+    ///
+    /// if starts_with_preface(&buf) {
+    ///     let connection = Builder::new()
+    ///         .handshake_with_prefix::<_, Bytes>(my_io, buf.into())
+    ///         .await
+    ///         .unwrap();
+    ///     // The HTTP/2.0 handshake has completed.
+    /// } else {
+    ///     // Serve the connection with HTTP/1.1 instead.
+    /// }
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn handshake_with_prefix<T, B>(&self, io: T, prefix: Bytes) -> Handshake<T, B>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+        B: Buf + 'static,
+    {
+        Connection::handshake2(io, self.clone(), None, prefix)
+    }
+
+    /// Creates a new configured HTTP/2.0 server backed by `io`, continuing an
+    /// HTTP/1.1 connection that was [upgraded][1] with `Upgrade: h2c`.
+    ///
+    /// `request` is the HTTP/1.1 request that asked for the upgrade. Its
+    /// `HTTP2-Settings` header is applied as the client's initial SETTINGS,
+    /// and the request itself becomes stream 1, which is half-closed
+    /// (remote). It is returned by the first call to
+    /// [`Connection::accept`], with an empty body, so that the response can
+    /// be sent over HTTP/2.0.
+    ///
+    /// The `101 Switching Protocols` response must already have been written
+    /// to `io`. Any request body must have been read beforehand, as it is not
+    /// available to the HTTP/2.0 connection.
+    ///
+    /// Since the request was sent over HTTP/1.1, connection-specific headers
+    /// such as `Connection`, `Upgrade` and `HTTP2-Settings` are removed, and
+    /// the `Host` header is used as the URI authority if there is none.
+    ///
+    /// The returned future fails if the `HTTP2-Settings` header is missing or
+    /// invalid.
+    ///
+    /// [1]: https://httpwg.org/specs/rfc7540.html#discover-http
+    /// [`Connection::accept`]: struct.Connection.html#method.accept
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio::io::{AsyncRead, AsyncWrite};
+    /// # use h2::server::*;
+    /// # use http::Request;
+    /// #
+    /// # fn doc<T: AsyncRead + AsyncWrite + Unpin>(my_io: T, upgrade_request: Request<()>)
+    /// # -> Handshake<T>
+    /// # {
+    /// // `server_fut` is a future representing the completion of the HTTP/2.0
+    /// // handshake.
+    /// let server_fut = Builder::new()
+    ///     .handshake_upgrade(my_io, upgrade_request);
+    /// # server_fut
+    /// # }
+    /// #
+    /// # pub fn main() {}
+    /// ```
+    pub fn handshake_upgrade<T, B>(&self, io: T, request: Request<()>) -> Handshake<T, B>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+        B: Buf + 'static,
+    {
+        let settings = match request.headers().get("http2-settings") {
+            Some(value) => Settings::load_http2_settings(value.as_bytes()),
+            None => Err(frame::Error::InvalidHttp2Settings),
+        };
+
+        let upgrade = Upgrade {
+            settings,
+            request: Peer::convert_upgrade_request(request),
+        };
+
+        Connection::handshake2(io, self.clone(), Some(upgrade), Bytes::new())
     }
 }
 
@@ -1005,6 +2049,29 @@ impl<B: Buf> SendResponse<B> {
             .map_err(Into::into)
     }
 
+    /// Sends an [ALTSVC frame] on the request's stream, advertising
+    /// alternative services for the origin of the request.
+    ///
+    /// `field_value` uses the syntax of the `Alt-Svc` header field, such as
+    /// `h3=":443"; ma=3600`. The frame may be sent before or after the
+    /// response headers, but not once the response has ended.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the response has already ended, or if the frame
+    /// would be larger than the smallest allowed frame size.
+    ///
+    /// [ALTSVC frame]: https://www.rfc-editor.org/rfc/rfc7838.html#section-4
+    pub fn send_alt_svc(&mut self, field_value: HeaderValue) -> Result<(), crate::Error> {
+        if 2 + field_value.len() > frame::DEFAULT_MAX_FRAME_SIZE as usize {
+            return Err(UserError::PayloadTooBig.into());
+        }
+
+        self.inner
+            .send_alt_svc(Bytes::copy_from_slice(field_value.as_bytes()))
+            .map_err(Into::into)
+    }
+
     /// Push a request and response to the client
     ///
     /// On success, a [`SendResponse`] instance is returned.
@@ -1057,6 +2124,20 @@ impl<B: Buf> SendResponse<B> {
         self.inner.poll_reset(cx, proto::PollReset::AwaitingHeaders)
     }
 
+    /// Changes the priority of the response stream.
+    ///
+    /// By default, responses are scheduled according to the priority the
+    /// client gave the request, in its `priority` header or in a
+    /// PRIORITY_UPDATE frame. This overrides it, until the client sends a
+    /// PRIORITY_UPDATE frame.
+    ///
+    /// The priority can be changed later using [`SendStream::set_priority`].
+    ///
+    /// [`SendStream::set_priority`]: ../struct.SendStream.html#method.set_priority
+    pub fn set_priority(&mut self, priority: crate::ext::Priority) {
+        self.inner.set_priority(priority)
+    }
+
     /// Returns the stream ID of the response stream.
     ///
     /// # Panics
@@ -1164,10 +2245,11 @@ where
 }
 
 impl<T, B: Buf> ReadPreface<T, B> {
-    fn new(codec: Codec<T, B>) -> Self {
+    fn new(codec: Codec<T, B>, prefix: Bytes) -> Self {
         ReadPreface {
             codec: Some(codec),
             pos: 0,
+            prefix,
         }
     }
 
@@ -1184,6 +2266,24 @@ where
     type Output = Result<Codec<T, B>, crate::Error>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if !self.prefix.is_empty() {
+            let mut prefix = mem::take(&mut self.prefix);
+            let n = cmp::min(PREFACE.len() - self.pos, prefix.len());
+
+            if PREFACE[self.pos..self.pos + n] != prefix[..n] {
+                proto_err!(conn: "read_preface: invalid preface");
+                return Poll::Ready(Err(Reason::PROTOCOL_ERROR.into()));
+            }
+
+            self.pos += n;
+
+            // Whatever follows the preface is the start of the first frames.
+            let rest = prefix.split_off(n);
+            if !rest.is_empty() {
+                self.codec.as_mut().unwrap().extend_read_buffer(&rest);
+            }
+        }
+
         let mut buf = [0; 24];
         let mut rem = PREFACE.len() - self.pos;
 
@@ -1242,7 +2342,8 @@ where
                     flushed
                 }
             };
-            Handshaking::from(ReadPreface::new(codec))
+            let prefix = mem::take(&mut self.prefix);
+            Handshaking::from(ReadPreface::new(codec, prefix))
         } else {
             // Otherwise, we haven't actually advanced the state, but we have
             // to replace it with itself, because we have to return a value.
@@ -1271,12 +2372,41 @@ where
                     initial_max_send_streams: 0,
                     reset_stream_duration: self.builder.reset_stream_duration,
                     reset_stream_max: self.builder.reset_stream_max,
+                    max_send_buffer_size: self.builder.max_send_buffer_size,
+                    remote_reset_stream_max: self.builder.pending_accept_reset_stream_max,
                     settings: self.builder.settings.clone(),
+                    keep_alive_interval: self.builder.keep_alive_interval,
+                    keep_alive_timeout: self.builder.keep_alive_timeout,
+                    keep_alive_while_idle: self.builder.keep_alive_while_idle,
+                    idle_timeout: self.builder.idle_timeout,
+                    adaptive_window: self.builder.adaptive_window,
+                    auto_release_capacity: self.builder.auto_release_capacity,
+                    extension_frame_types: self.builder.extension_frame_types.clone(),
+                    max_queued_extension_frames: self.builder.max_queued_extension_frames,
+                    max_control_frames_per_second: self.builder.max_control_frames_per_second,
+                    max_empty_data_frames_per_second: self.builder.max_empty_data_frames_per_second,
                 },
             );
 
             tracing::trace!("connection established!");
             let mut c = Connection { connection };
+
+            if let Some(upgrade) = self.upgrade.take() {
+                let settings = upgrade.settings.map_err(|e| {
+                    tracing::debug!(error = ?e, "invalid HTTP2-Settings header");
+                    crate::Error::from(Reason::PROTOCOL_ERROR)
+                })?;
+
+                c.connection
+                    .recv_upgrade(&settings, upgrade.request)
+                    .map_err(|e| match e {
+                        RecvError::Connection(reason) | RecvError::Stream { reason, .. } => {
+                            crate::Error::from(reason)
+                        }
+                        RecvError::Io(e) => crate::Error::from_io(e),
+                    })?;
+            }
+
             if let Some(sz) = self.builder.initial_target_connection_window_size {
                 c.set_target_window_size(sz);
             }
@@ -1296,6 +2426,62 @@ where
 }
 
 impl Peer {
+    /// Converts an HTTP/1.1 upgrade request to the HEADERS frame that opens
+    /// stream 1.
+    fn convert_upgrade_request(request: Request<()>) -> frame::Headers {
+        use http::header::{self, HeaderName};
+
+        let (parts, _) = request.into_parts();
+        let mut fields = parts.headers;
+        let mut pseudo = Pseudo::request(parts.method, parts.uri, None);
+
+        // The upgrade happens on a cleartext connection.
+        if pseudo.scheme.is_none() {
+            pseudo.set_scheme(http::uri::Scheme::HTTP);
+        }
+
+        if pseudo.authority.is_none() {
+            if let Some(host) = fields.remove(header::HOST) {
+                if let Ok(host) = host.to_str() {
+                    pseudo.set_authority(crate::hpack::BytesStr::from(host));
+                }
+            }
+        }
+
+        // Connection-specific header fields are not allowed in HTTP/2. This
+        // includes any fields listed in the `Connection` header.
+        let listed = fields
+            .get_all(header::CONNECTION)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+            .collect::<Vec<_>>();
+
+        for name in listed {
+            fields.remove(name);
+        }
+
+        fields.remove(header::CONNECTION);
+        fields.remove(header::UPGRADE);
+        fields.remove(header::TRANSFER_ENCODING);
+        fields.remove("http2-settings");
+        fields.remove("keep-alive");
+        fields.remove("proxy-connection");
+
+        if fields
+            .get(header::TE)
+            .into_iter()
+            .any(|te| te != "trailers")
+        {
+            fields.remove(header::TE);
+        }
+
+        let mut frame = frame::Headers::new(StreamId::from(1), pseudo, fields);
+        frame.set_end_stream();
+        frame
+    }
+
     pub fn convert_send_message(
         id: StreamId,
         response: Response<()>,
@@ -1360,7 +2546,7 @@ impl Peer {
             _,
         ) = request.into_parts();
 
-        let pseudo = Pseudo::request(method, uri);
+        let pseudo = Pseudo::request(method, uri, None);
 
         Ok(frame::PushPromise::new(
             stream_id,
@@ -1413,6 +2599,16 @@ impl proto::Peer for Peer {
             malformed!("malformed headers: missing method");
         }
 
+        // An extended CONNECT request (RFC 8441) carries a :protocol, and
+        // must then include :scheme and :path like any other request.
+        let has_protocol = pseudo.protocol.is_some();
+        if has_protocol && !is_connect {
+            malformed!("malformed headers: :protocol on non-CONNECT request");
+        }
+        if let Some(protocol) = pseudo.protocol {
+            b = b.extension(protocol);
+        }
+
         // Specifying :status for a request is a protocol error
         if pseudo.status.is_some() {
             tracing::trace!("malformed headers: :status field on request; PROTOCOL_ERROR");
@@ -1437,7 +2633,7 @@ impl proto::Peer for Peer {
 
         // A :scheme is required, except CONNECT.
         if let Some(scheme) = pseudo.scheme {
-            if is_connect {
+            if is_connect && !has_protocol {
                 malformed!(":scheme in CONNECT");
             }
             let maybe_scheme = scheme.parse();
@@ -1455,12 +2651,12 @@ impl proto::Peer for Peer {
             if parts.authority.is_some() {
                 parts.scheme = Some(scheme);
             }
-        } else if !is_connect {
+        } else if !is_connect || has_protocol {
             malformed!("malformed headers: missing scheme");
         }
 
         if let Some(path) = pseudo.path {
-            if is_connect {
+            if is_connect && !has_protocol {
                 malformed!(":path in CONNECT");
             }
 
@@ -1473,6 +2669,8 @@ impl proto::Peer for Peer {
             parts.path_and_query = Some(maybe_path.or_else(|why| {
                 malformed!("malformed headers: malformed path ({:?}): {}", path, why,)
             })?);
+        } else if has_protocol {
+            malformed!("malformed headers: missing path in extended CONNECT");
         }
 
         b = b.uri(parts);
@@ -1,15 +1,15 @@
 use crate::codec::UserError;
-use crate::frame::Reason;
+use crate::frame::{Kind, Reason};
 use crate::proto::{self, WindowSize};
 
 use bytes::{Buf, Bytes};
 use http::HeaderMap;
 
 use crate::PollExt;
-use std::fmt;
-#[cfg(feature = "stream")]
 use std::pin::Pin;
 use std::task::{Context, Poll};
+use std::{cmp, fmt, io};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
 /// Sends the body stream and trailers to the remote peer.
 ///
@@ -99,6 +99,49 @@ pub struct SendStream<B: Buf> {
     inner: proto::StreamRef<B>,
 }
 
+/// Writes the body of a message to a [`SendStream`], handling flow control.
+///
+/// `SendStreamWriter` implements [`AsyncWrite`], and [`Sink<Bytes>`] when the
+/// `stream` feature is enabled. Before sending data, it reserves capacity and
+/// waits until capacity is assigned to the stream, so that writes don't
+/// buffer more data than the peer is willing to receive. A write that is
+/// larger than the assigned capacity is only partially sent.
+///
+/// Shutting the writer down sends an empty `DATA` frame with the
+/// `END_STREAM` flag set. If the stream is reset, writes fail with an
+/// `io::Error` wrapping an [`Error`] that carries the reason of the reset.
+///
+/// # Examples
+///
+/// ```
+/// # use h2::{SendStream, SendStreamWriter};
+/// # use bytes::Bytes;
+/// use tokio::io::AsyncWriteExt;
+///
+/// # async fn doc(send_stream: SendStream<Bytes>) -> std::io::Result<()> {
+/// let mut writer = SendStreamWriter::new(send_stream);
+/// writer.write_all(b"hello world").await?;
+/// writer.shutdown().await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`SendStream`]: struct.SendStream.html
+/// [`AsyncWrite`]: https://docs.rs/tokio/1/tokio/io/trait.AsyncWrite.html
+/// [`Sink<Bytes>`]: https://docs.rs/futures/0.3/futures/sink/trait.Sink.html
+/// [`Error`]: struct.Error.html
+#[derive(Debug)]
+pub struct SendStreamWriter {
+    inner: SendStream<Bytes>,
+
+    /// Data accepted by the `Sink` that has not been sent yet.
+    #[cfg(feature = "stream")]
+    pending: Bytes,
+
+    /// Set once `END_STREAM` has been sent.
+    is_shutdown: bool,
+}
+
 /// A stream identifier, as described in [Section 5.1.1] of RFC 7540.
 ///
 /// Streams are identified with an unsigned 31-bit integer. Streams
@@ -108,8 +151,13 @@ pub struct SendStream<B: Buf> {
 /// messages; the stream identifier of zero cannot be used to establish a
 /// new stream.
 ///
+/// Stream identifiers are ordered, so that they can be compared with the
+/// last stream ID of a GOAWAY frame, as returned by
+/// [`Error::go_away_last_stream_id`].
+///
 /// [Section 5.1.1]: https://tools.ietf.org/html/rfc7540#section-5.1.1
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+/// [`Error::go_away_last_stream_id`]: struct.Error.html#method.go_away_last_stream_id
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct StreamId(u32);
 
 /// Receives the body stream and trailers from the remote peer.
@@ -134,6 +182,48 @@ pub struct RecvStream {
     inner: FlowControl,
 }
 
+/// Reads the body of a message from a [`RecvStream`].
+///
+/// `RecvStreamReader` implements [`AsyncRead`]. It releases the capacity of
+/// the data as it is read, so that the caller doesn't need to call
+/// [`FlowControl::release_capacity`]. Data that was received but not read
+/// yet keeps its capacity, so a slow reader still applies backpressure to
+/// the peer. [Automatic capacity release] is disabled on the stream.
+/// Trailers are not read;
+/// they can be received with the `RecvStream` returned by [`into_inner`]
+/// once the body has been read to the end.
+///
+/// If the stream is reset, reads fail with an `io::Error` wrapping the
+/// [`Error`].
+///
+/// # Examples
+///
+/// ```
+/// # use h2::{RecvStream, RecvStreamReader};
+/// use tokio::io::AsyncReadExt;
+///
+/// # async fn doc(recv_stream: RecvStream) -> std::io::Result<()> {
+/// let mut reader = RecvStreamReader::new(recv_stream);
+/// let mut body = String::new();
+/// reader.read_to_string(&mut body).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`RecvStream`]: struct.RecvStream.html
+/// [`AsyncRead`]: https://docs.rs/tokio/1/tokio/io/trait.AsyncRead.html
+/// [`FlowControl::release_capacity`]: struct.FlowControl.html#method.release_capacity
+/// [Automatic capacity release]: struct.RecvStream.html#method.set_auto_release_capacity
+/// [`into_inner`]: #method.into_inner
+/// [`Error`]: struct.Error.html
+#[derive(Debug)]
+pub struct RecvStreamReader {
+    inner: RecvStream,
+
+    /// Data received but not read yet.
+    chunk: Bytes,
+}
+
 /// A handle to release window capacity to a remote stream.
 ///
 /// This type allows the caller to manage inbound data [flow control]. The
@@ -212,6 +302,54 @@ pub struct Pong {
     _p: (),
 }
 
+/// A snapshot of the counters of a connection.
+///
+/// Returned by `client::Connection::stats` and `server::Connection::stats`.
+/// Counters start at zero when the connection is created and only go up,
+/// except for the window and HPACK table sizes, which are current values.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionStats {
+    pub(crate) bytes_sent: u64,
+    pub(crate) bytes_received: u64,
+    pub(crate) frames_sent: FrameStats,
+    pub(crate) frames_received: FrameStats,
+    pub(crate) frame_bytes_sent: FrameStats,
+    pub(crate) frame_bytes_received: FrameStats,
+    pub(crate) local_streams_opened: u64,
+    pub(crate) remote_streams_opened: u64,
+    pub(crate) local_streams_closed: u64,
+    pub(crate) remote_streams_closed: u64,
+    pub(crate) streams_reset_locally: u64,
+    pub(crate) streams_reset_remotely: u64,
+    pub(crate) send_window: WindowSize,
+    pub(crate) recv_window: WindowSize,
+    pub(crate) send_header_table_size: usize,
+    pub(crate) recv_header_table_size: usize,
+}
+
+/// A counter for each type of frame sent or received on a connection.
+///
+/// Depending on the `ConnectionStats` method it was returned by, the
+/// counters are numbers of frames or numbers of bytes, including the 9 byte
+/// frame headers.
+#[derive(Debug, Clone, Default)]
+pub struct FrameStats {
+    data: u64,
+    headers: u64,
+    priority: u64,
+    reset: u64,
+    settings: u64,
+    push_promise: u64,
+    ping: u64,
+    go_away: u64,
+    window_update: u64,
+    continuation: u64,
+    alt_svc: u64,
+    origin: u64,
+    priority_update: u64,
+    unknown: u64,
+}
+
 // ===== impl SendStream =====
 
 impl<B: Buf> SendStream<B> {
@@ -367,6 +505,22 @@ impl<B: Buf> SendStream<B> {
         self.inner.poll_reset(cx, proto::PollReset::Streaming)
     }
 
+    /// Changes the priority of the stream.
+    ///
+    /// Streams are scheduled according to their [`Priority`] when sending
+    /// data. On a client, this also sends a PRIORITY_UPDATE frame so that the
+    /// server can reprioritize the response. The initial priority of a
+    /// request is sent in its `priority` header.
+    ///
+    /// If the request's HEADERS frame hasn't been written yet, the new
+    /// priority only takes effect, and is only sent, once it is. Requests are
+    /// always opened in the order they were sent.
+    ///
+    /// [`Priority`]: ext/struct.Priority.html
+    pub fn set_priority(&mut self, priority: crate::ext::Priority) {
+        self.inner.set_priority(priority)
+    }
+
     /// Returns the stream ID of this `SendStream`.
     ///
     /// # Panics
@@ -377,12 +531,163 @@ impl<B: Buf> SendStream<B> {
     }
 }
 
+// ===== impl SendStreamWriter =====
+
+impl SendStreamWriter {
+    /// Wraps a `SendStream` to write the body of the message.
+    pub fn new(inner: SendStream<Bytes>) -> Self {
+        SendStreamWriter {
+            inner,
+            #[cfg(feature = "stream")]
+            pending: Bytes::new(),
+            is_shutdown: false,
+        }
+    }
+
+    /// Returns a reference to the underlying `SendStream`.
+    pub fn get_ref(&self) -> &SendStream<Bytes> {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying `SendStream`.
+    pub fn get_mut(&mut self) -> &mut SendStream<Bytes> {
+        &mut self.inner
+    }
+
+    /// Consumes the writer, returning the underlying `SendStream`.
+    ///
+    /// Data accepted by the `Sink` implementation that has not been flushed
+    /// yet is discarded.
+    pub fn into_inner(self) -> SendStream<Bytes> {
+        self.inner
+    }
+
+    /// Waits until the stream has capacity to send up to `len` bytes, and
+    /// returns that capacity.
+    fn poll_send_capacity(&mut self, cx: &mut Context, len: usize) -> Poll<io::Result<usize>> {
+        loop {
+            let capacity = self.inner.capacity();
+            if capacity > 0 {
+                return Poll::Ready(Ok(cmp::min(capacity, len)));
+            }
+
+            self.inner.reserve_capacity(len);
+
+            match ready!(self.inner.poll_capacity(cx)) {
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Poll::Ready(Err(into_io(e))),
+                None => return Poll::Ready(Err(self.closed_error(cx))),
+            }
+        }
+    }
+
+    fn send_data(&mut self, cx: &mut Context, data: Bytes, eos: bool) -> io::Result<()> {
+        if self.inner.send_data(data, eos).is_err() {
+            return Err(self.closed_error(cx));
+        }
+        Ok(())
+    }
+
+    /// Returns the error for a stream that can't send data anymore.
+    fn closed_error(&mut self, cx: &mut Context) -> io::Error {
+        match self.inner.poll_reset(cx) {
+            Poll::Ready(Ok(reason)) => into_io(proto::Error::Reset(reason).into()),
+            Poll::Ready(Err(e)) => into_io(e),
+            Poll::Pending => io::Error::new(io::ErrorKind::BrokenPipe, "send stream closed"),
+        }
+    }
+
+    /// Sends the data accepted by the `Sink`, as capacity becomes available.
+    #[cfg(feature = "stream")]
+    fn poll_send_pending(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        while !self.pending.is_empty() {
+            let n = ready!(self.poll_send_capacity(cx, self.pending.len()))?;
+            let data = self.pending.split_to(n);
+            self.send_data(cx, data, false)?;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for SendStreamWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        let n = ready!(self.poll_send_capacity(cx, buf.len()))?;
+        self.send_data(cx, Bytes::copy_from_slice(&buf[..n]), false)?;
+
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Data is written out by the connection task.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.is_shutdown {
+            self.send_data(cx, Bytes::new(), true)?;
+            self.is_shutdown = true;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "stream")]
+impl futures_sink::Sink<Bytes> for SendStreamWriter {
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_send_pending(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Bytes) -> io::Result<()> {
+        debug_assert!(
+            self.pending.is_empty(),
+            "start_send called before poll_ready"
+        );
+        self.pending = item;
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_send_pending(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_send_pending(cx))?;
+        AsyncWrite::poll_shutdown(self, cx)
+    }
+}
+
+// `io::Error::other` is not available on all supported compilers.
+#[allow(clippy::io_other_error)]
+fn into_io(err: crate::Error) -> io::Error {
+    if err.is_io() {
+        err.into_io().unwrap()
+    } else {
+        io::Error::new(io::ErrorKind::Other, err)
+    }
+}
+
 // ===== impl StreamId =====
 
 impl StreamId {
     pub(crate) fn from_internal(id: crate::frame::StreamId) -> Self {
         StreamId(id.into())
     }
+
+    pub(crate) fn into_internal(self) -> crate::frame::StreamId {
+        self.0.into()
+    }
 }
 // ===== impl RecvStream =====
 
@@ -433,6 +738,19 @@ impl RecvStream {
         &mut self.inner
     }
 
+    /// Sets whether the capacity of received data is released automatically.
+    ///
+    /// When enabled, the capacity of each chunk of data is released as soon
+    /// as it is returned by [`poll_data`], so the caller doesn't need to call
+    /// [`FlowControl::release_capacity`]. This overrides the connection's
+    /// `auto_release_capacity` builder setting for this stream.
+    ///
+    /// [`poll_data`]: #method.poll_data
+    /// [`FlowControl::release_capacity`]: struct.FlowControl.html#method.release_capacity
+    pub fn set_auto_release_capacity(&mut self, enabled: bool) {
+        self.inner.inner.set_auto_release_capacity(enabled)
+    }
+
     /// Returns the stream ID of this stream.
     ///
     /// # Panics
@@ -471,6 +789,68 @@ impl Drop for RecvStream {
     }
 }
 
+// ===== impl RecvStreamReader =====
+
+impl RecvStreamReader {
+    /// Wraps a `RecvStream` to read the body of the message.
+    pub fn new(mut inner: RecvStream) -> Self {
+        inner.set_auto_release_capacity(false);
+
+        RecvStreamReader {
+            inner,
+            chunk: Bytes::new(),
+        }
+    }
+
+    /// Returns a reference to the underlying `RecvStream`.
+    pub fn get_ref(&self) -> &RecvStream {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying `RecvStream`.
+    pub fn get_mut(&mut self) -> &mut RecvStream {
+        &mut self.inner
+    }
+
+    /// Consumes the reader, returning the underlying `RecvStream`.
+    ///
+    /// Data that was received but not read yet is discarded, and its capacity
+    /// is released.
+    pub fn into_inner(mut self) -> RecvStream {
+        let unread = self.chunk.len();
+        let _ = self.inner.flow_control().release_capacity(unread);
+        self.inner
+    }
+}
+
+impl AsyncRead for RecvStreamReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        while self.chunk.is_empty() {
+            match ready!(self.inner.poll_data(cx)) {
+                Some(Ok(chunk)) => self.chunk = chunk,
+                Some(Err(e)) => return Poll::Ready(Err(into_io(e))),
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+
+        let n = cmp::min(buf.remaining(), self.chunk.len());
+        buf.put_slice(&self.chunk[..n]);
+        self.chunk.advance(n);
+
+        // Only the capacity of the data handed to the caller is released.
+        self.inner
+            .flow_control()
+            .release_capacity(n)
+            .map_err(into_io)?;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
 // ===== impl FlowControl =====
 
 impl FlowControl {
@@ -522,6 +902,215 @@ impl FlowControl {
     }
 }
 
+// ===== impl ConnectionStats =====
+
+impl ConnectionStats {
+    /// Returns the number of bytes of frames written to the connection.
+    ///
+    /// The connection preface is not included.
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    /// Returns the number of bytes of frames read from the connection.
+    ///
+    /// The connection preface is not included.
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+
+    /// Returns the number of frames sent, by frame type.
+    pub fn frames_sent(&self) -> &FrameStats {
+        &self.frames_sent
+    }
+
+    /// Returns the number of frames received, by frame type.
+    pub fn frames_received(&self) -> &FrameStats {
+        &self.frames_received
+    }
+
+    /// Returns the number of bytes of frames sent, by frame type.
+    ///
+    /// Frames are counted once they are queued to be written, so the total
+    /// may be ahead of `bytes_sent`.
+    pub fn frame_bytes_sent(&self) -> &FrameStats {
+        &self.frame_bytes_sent
+    }
+
+    /// Returns the number of bytes of frames received, by frame type.
+    pub fn frame_bytes_received(&self) -> &FrameStats {
+        &self.frame_bytes_received
+    }
+
+    /// Returns the number of streams opened by this endpoint, including
+    /// pushed streams opened by a server.
+    pub fn local_streams_opened(&self) -> u64 {
+        self.local_streams_opened
+    }
+
+    /// Returns the number of streams opened by the peer.
+    pub fn remote_streams_opened(&self) -> u64 {
+        self.remote_streams_opened
+    }
+
+    /// Returns the number of streams opened by this endpoint that have since
+    /// been closed, whether normally or by a reset.
+    pub fn local_streams_closed(&self) -> u64 {
+        self.local_streams_closed
+    }
+
+    /// Returns the number of streams opened by the peer that have since been
+    /// closed, whether normally or by a reset.
+    pub fn remote_streams_closed(&self) -> u64 {
+        self.remote_streams_closed
+    }
+
+    /// Returns the number of streams reset by this endpoint.
+    pub fn streams_reset_locally(&self) -> u64 {
+        self.streams_reset_locally
+    }
+
+    /// Returns the number of streams reset by the peer.
+    pub fn streams_reset_remotely(&self) -> u64 {
+        self.streams_reset_remotely
+    }
+
+    /// Returns the current connection-level send window, in bytes.
+    pub fn send_window(&self) -> u32 {
+        self.send_window
+    }
+
+    /// Returns the current connection-level receive window, in bytes.
+    pub fn recv_window(&self) -> u32 {
+        self.recv_window
+    }
+
+    /// Returns the size, as defined by HPACK, of the entries in the dynamic
+    /// table used to encode sent headers.
+    pub fn send_header_table_size(&self) -> usize {
+        self.send_header_table_size
+    }
+
+    /// Returns the size, as defined by HPACK, of the entries in the dynamic
+    /// table used to decode received headers.
+    pub fn recv_header_table_size(&self) -> usize {
+        self.recv_header_table_size
+    }
+}
+
+// ===== impl FrameStats =====
+
+impl FrameStats {
+    pub(crate) fn record(&mut self, kind: Kind, n: u64) {
+        let count = match kind {
+            Kind::Data => &mut self.data,
+            Kind::Headers => &mut self.headers,
+            Kind::Priority => &mut self.priority,
+            Kind::Reset => &mut self.reset,
+            Kind::Settings => &mut self.settings,
+            Kind::PushPromise => &mut self.push_promise,
+            Kind::Ping => &mut self.ping,
+            Kind::GoAway => &mut self.go_away,
+            Kind::WindowUpdate => &mut self.window_update,
+            Kind::Continuation => &mut self.continuation,
+            Kind::AltSvc => &mut self.alt_svc,
+            Kind::Origin => &mut self.origin,
+            Kind::PriorityUpdate => &mut self.priority_update,
+            Kind::Unknown => &mut self.unknown,
+        };
+        *count += n;
+    }
+
+    /// Returns the counter for DATA frames.
+    pub fn data(&self) -> u64 {
+        self.data
+    }
+
+    /// Returns the counter for HEADERS frames.
+    pub fn headers(&self) -> u64 {
+        self.headers
+    }
+
+    /// Returns the counter for PRIORITY frames.
+    pub fn priority(&self) -> u64 {
+        self.priority
+    }
+
+    /// Returns the counter for RST_STREAM frames.
+    pub fn reset(&self) -> u64 {
+        self.reset
+    }
+
+    /// Returns the counter for SETTINGS frames, including acknowledgements.
+    pub fn settings(&self) -> u64 {
+        self.settings
+    }
+
+    /// Returns the counter for PUSH_PROMISE frames.
+    pub fn push_promise(&self) -> u64 {
+        self.push_promise
+    }
+
+    /// Returns the counter for PING frames, including acknowledgements.
+    pub fn ping(&self) -> u64 {
+        self.ping
+    }
+
+    /// Returns the counter for GOAWAY frames.
+    pub fn go_away(&self) -> u64 {
+        self.go_away
+    }
+
+    /// Returns the counter for WINDOW_UPDATE frames.
+    pub fn window_update(&self) -> u64 {
+        self.window_update
+    }
+
+    /// Returns the counter for CONTINUATION frames.
+    pub fn continuation(&self) -> u64 {
+        self.continuation
+    }
+
+    /// Returns the counter for ALTSVC frames.
+    pub fn alt_svc(&self) -> u64 {
+        self.alt_svc
+    }
+
+    /// Returns the counter for ORIGIN frames.
+    pub fn origin(&self) -> u64 {
+        self.origin
+    }
+
+    /// Returns the counter for PRIORITY_UPDATE frames.
+    pub fn priority_update(&self) -> u64 {
+        self.priority_update
+    }
+
+    /// Returns the counter for frames of types not implemented by this
+    /// library, such as extension frames.
+    pub fn unknown(&self) -> u64 {
+        self.unknown
+    }
+
+    /// Returns the sum of the counters for all frame types.
+    pub fn total(&self) -> u64 {
+        self.data
+            + self.headers
+            + self.priority
+            + self.reset
+            + self.settings
+            + self.push_promise
+            + self.ping
+            + self.go_away
+            + self.window_update
+            + self.continuation
+            + self.alt_svc
+            + self.origin
+            + self.priority_update
+            + self.unknown
+    }
+}
+
 // ===== impl PingPong =====
 
 impl PingPong {
@@ -51,6 +51,51 @@ where
     frame::WindowUpdate::new(id.into(), sz)
 }
 
+pub fn priority<T, U>(id: T, dependency: U, weight: u8, exclusive: bool) -> frame::Priority
+where
+    T: Into<StreamId>,
+    U: Into<StreamId>,
+{
+    frame::Priority::new(
+        id.into(),
+        frame::StreamDependency::new(dependency.into(), weight, exclusive),
+    )
+}
+
+pub fn priority_update<T>(id: T, value: &str) -> frame::PriorityUpdate
+where
+    T: Into<StreamId>,
+{
+    frame::PriorityUpdate::new(id.into(), Bytes::copy_from_slice(value.as_bytes()))
+}
+
+pub fn extension<T>(kind: u8, id: T, payload: &[u8]) -> frame::Extension
+where
+    T: Into<StreamId>,
+{
+    frame::Extension::new(kind, 0, id.into(), Bytes::copy_from_slice(payload))
+}
+
+pub fn origin(origins: &[&str]) -> frame::Origin {
+    frame::Origin::new(
+        origins
+            .iter()
+            .map(|origin| Bytes::copy_from_slice(origin.as_bytes()))
+            .collect(),
+    )
+}
+
+pub fn alt_svc<T>(id: T, origin: &str, field_value: &str) -> frame::AltSvc
+where
+    T: Into<StreamId>,
+{
+    frame::AltSvc::new(
+        id.into(),
+        Bytes::copy_from_slice(origin.as_bytes()),
+        Bytes::copy_from_slice(field_value.as_bytes()),
+    )
+}
+
 pub fn go_away<T>(id: T) -> Mock<frame::GoAway>
 where
     T: Into<StreamId>,
@@ -109,7 +154,7 @@ impl Mock<frame::Headers> {
         let method = method.try_into().unwrap();
         let uri = uri.try_into().unwrap();
         let (id, _, fields) = self.into_parts();
-        let frame = frame::Headers::new(id, frame::Pseudo::request(method, uri), fields);
+        let frame = frame::Headers::new(id, frame::Pseudo::request(method, uri, None), fields);
         Mock(frame)
     }
 
@@ -171,6 +216,13 @@ impl Mock<frame::Headers> {
         Mock(frame::Headers::new(id, pseudo, fields))
     }
 
+    pub fn protocol(self, value: &str) -> Self {
+        let (id, mut pseudo, fields) = self.into_parts();
+        pseudo.protocol = Some(value.into());
+
+        Mock(frame::Headers::new(id, pseudo, fields))
+    }
+
     pub fn eos(mut self) -> Self {
         self.0.set_end_stream();
         self
@@ -222,8 +274,12 @@ impl Mock<frame::PushPromise> {
         let method = method.try_into().unwrap();
         let uri = uri.try_into().unwrap();
         let (id, promised, _, fields) = self.into_parts();
-        let frame =
-            frame::PushPromise::new(id, promised, frame::Pseudo::request(method, uri), fields);
+        let frame = frame::PushPromise::new(
+            id,
+            promised,
+            frame::Pseudo::request(method, uri, None),
+            fields,
+        );
         Mock(frame)
     }
 
@@ -278,9 +334,24 @@ impl Mock<frame::GoAway> {
         self.reason(frame::Reason::NO_ERROR)
     }
 
+    pub fn calm(self) -> Self {
+        self.reason(frame::Reason::ENHANCE_YOUR_CALM)
+    }
+
     pub fn reason(self, reason: frame::Reason) -> Self {
         Mock(frame::GoAway::new(self.0.last_stream_id(), reason))
     }
+
+    pub fn data<I>(self, debug_data: I) -> Self
+    where
+        I: Into<Bytes>,
+    {
+        Mock(frame::GoAway::with_debug_data(
+            self.0.last_stream_id(),
+            self.0.reason(),
+            debug_data.into(),
+        ))
+    }
 }
 
 // ==== Reset helpers
@@ -340,10 +411,25 @@ impl Mock<frame::Settings> {
         self
     }
 
+    pub fn header_table_size(mut self, val: u32) -> Self {
+        self.0.set_header_table_size(Some(val));
+        self
+    }
+
     pub fn disable_push(mut self) -> Self {
         self.0.set_enable_push(false);
         self
     }
+
+    pub fn enable_connect_protocol(mut self, val: u32) -> Self {
+        self.0.set_enable_connect_protocol(Some(val));
+        self
+    }
+
+    pub fn no_rfc7540_priorities(mut self, val: u32) -> Self {
+        self.0.set_no_rfc7540_priorities(Some(val));
+        self
+    }
 }
 
 impl From<Mock<frame::Settings>> for frame::Settings {
@@ -273,6 +273,12 @@ impl Handle {
         .await;
     }
 
+    /// Stops accepting written bytes, as if the peer stopped reading.
+    pub fn stop_reading(&mut self) {
+        let mut i = self.codec.get_mut().inner.lock().unwrap();
+        i.tx_rem = 0;
+    }
+
     pub async fn unbounded_bytes(&mut self) {
         let mut i = self.codec.get_mut().inner.lock().unwrap();
         i.tx_rem = usize::MAX;
@@ -1,7 +1,8 @@
-use futures::future::{join, ready, select, Either};
+use futures::future::{join, join3, ready, select, Either};
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 use h2_support::prelude::*;
+use std::io;
 use std::pin::Pin;
 use std::task::Context;
 
@@ -1012,6 +1013,258 @@ async fn send_stream_poll_reset() {
     join(srv, client).await;
 }
 
+#[tokio::test]
+async fn recv_go_away_with_debug_data() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv.recv_frame(
+            frames::headers(3)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv.send_frame(frames::go_away(1).internal_error().data("restarting"))
+            .await;
+        srv.send_frame(frames::headers(1).response(200).eos()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, h2) = client::handshake(io).await.unwrap();
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let (response1, _) = client.send_request(request, true).unwrap();
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let (response3, _) = client.send_request(request, true).unwrap();
+        let id1 = response1.stream_id();
+        let id3 = response3.stream_id();
+
+        let (response1, response3, conn) = join3(response1, response3, h2).await;
+        assert_eq!(response1.unwrap().status(), StatusCode::OK);
+
+        // Stream 3 was not processed by the server.
+        let err = response3.unwrap_err();
+        assert_eq!(err.reason(), Some(Reason::INTERNAL_ERROR));
+        assert_eq!(err.go_away_debug_data(), Some(&b"restarting"[..]));
+        let last_stream_id = err.go_away_last_stream_id().unwrap();
+        assert_eq!(last_stream_id, id1);
+        assert!(id3 > last_stream_id);
+        assert!(err.is_go_away());
+        assert!(err.is_remote());
+        assert!(err.is_safe_to_retry());
+
+        // The connection error says nothing about any particular request.
+        let err = conn.unwrap_err();
+        assert_eq!(err.go_away_debug_data(), Some(&b"restarting"[..]));
+        assert!(err.is_go_away());
+        assert!(!err.is_safe_to_retry());
+
+        // Requests started after the GOAWAY are never sent.
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let err = client.send_request(request, true).unwrap_err();
+        assert!(err.is_go_away());
+        assert!(err.is_safe_to_retry());
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn recv_refused_stream_is_safe_to_retry() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv.send_frame(frames::reset(1).refused()).await;
+        srv.recv_frame(
+            frames::headers(3)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv.send_frame(frames::reset(3).internal_error()).await;
+        srv.recv_frame(frames::headers(5).request("GET", "https://example.com/"))
+            .await;
+        srv.recv_frame(frames::reset(5).cancel()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.unwrap();
+        let request = || {
+            Request::builder()
+                .uri("https://example.com/")
+                .body(())
+                .unwrap()
+        };
+
+        let (response, _) = client.send_request(request(), true).unwrap();
+        let err = h2.drive(response).await.unwrap_err();
+        assert_eq!(err.reason(), Some(Reason::REFUSED_STREAM));
+        assert!(err.is_remote());
+        assert!(!err.is_go_away());
+        assert!(err.is_safe_to_retry());
+
+        let (response, _) = client.send_request(request(), true).unwrap();
+        let err = h2.drive(response).await.unwrap_err();
+        assert_eq!(err.reason(), Some(Reason::INTERNAL_ERROR));
+        assert!(err.is_remote());
+        assert!(!err.is_safe_to_retry());
+
+        // Streams reset locally are not remote errors.
+        let (response, mut stream) = client.send_request(request(), false).unwrap();
+        h2.drive(idle_ms(10)).await;
+        stream.send_reset(Reason::CANCEL);
+        let err = h2.drive(response).await.unwrap_err();
+        assert_eq!(err.reason(), Some(Reason::CANCEL));
+        assert!(!err.is_remote());
+        assert!(!err.is_safe_to_retry());
+
+        h2.await.unwrap();
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn recv_origin_frames() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv.send_frame(frames::origin(&[
+            "https://example.com",
+            "not an origin",
+            "https://example.net:8443",
+        ]))
+        .await;
+        srv.send_frame(frames::origin(&[
+            "https://example.org",
+            "https://example.com",
+        ]))
+        .await;
+        // ORIGIN frames on a stream are ignored.
+        srv.send_bytes(&[
+            0, 0, 21, // len
+            12, // type: ORIGIN
+            0,  // flags
+            0, 0, 0, 1, // stream id
+            0, 19, // origin length
+        ])
+        .await;
+        srv.send_bytes(b"https://example.edu").await;
+        srv.send_frame(frames::headers(1).response(200).eos()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.unwrap();
+        assert!(client.origin_set().is_none());
+
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let (response, _) = client.send_request(request, true).unwrap();
+        let response = h2.drive(response).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let origins: Vec<String> = client
+            .origin_set()
+            .unwrap()
+            .iter()
+            .map(|origin| origin.as_str().to_owned())
+            .collect();
+        assert_eq!(
+            origins,
+            [
+                "https://example.com",
+                "https://example.net:8443",
+                "https://example.org"
+            ]
+        );
+        assert_eq!(h2.origin_set().unwrap().len(), 3);
+
+        h2.await.unwrap();
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn origin_set_is_capped() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        let origins: Vec<String> = (0..300)
+            .map(|i| format!("https://{}.example.com", i))
+            .collect();
+        let origins: Vec<&str> = origins.iter().map(String::as_str).collect();
+        srv.send_frame(frames::origin(&origins)).await;
+        srv.send_frame(frames::headers(1).response(200).eos()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.unwrap();
+
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let (response, _) = client.send_request(request, true).unwrap();
+        h2.drive(response).await.unwrap();
+
+        let origins = client.origin_set().unwrap();
+        assert_eq!(origins.len(), 256);
+        assert_eq!(origins[0].as_str(), "https://0.example.com");
+        assert_eq!(origins[255].as_str(), "https://255.example.com");
+
+        h2.await.unwrap();
+    };
+
+    join(srv, h2).await;
+}
+
 #[tokio::test]
 async fn drop_pending_open() {
     // This test checks that a stream queued for pending open behaves correctly when its
@@ -1301,6 +1554,89 @@ async fn informational_while_local_streaming() {
     join(srv, h2).await;
 }
 
+#[tokio::test]
+async fn extended_connect_protocol_enabled_by_server() {
+    use tokio::sync::oneshot;
+
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+    let (settings_tx, settings_rx) = oneshot::channel();
+
+    let srv = async move {
+        let settings = srv
+            .assert_client_handshake_with_settings(frames::settings().enable_connect_protocol(1))
+            .await;
+        assert_default_settings!(settings);
+        settings_tx.send(()).unwrap();
+
+        srv.recv_frame(
+            frames::headers(1)
+                .request("CONNECT", "http://bread/baguette")
+                .protocol("the-bread-protocol"),
+        )
+        .await;
+        srv.send_frame(frames::headers(1).response(200)).await;
+    };
+
+    let h2 = async move {
+        let (mut client, h2) = client::handshake(io).await.unwrap();
+        tokio::spawn(async move {
+            h2.await.expect("connection failed");
+        });
+
+        settings_rx.await.unwrap();
+        assert!(client.is_extended_connect_protocol_enabled());
+
+        let request = Request::builder()
+            .method(Method::CONNECT)
+            .uri("http://bread/baguette")
+            .extension(ext::Protocol::from("the-bread-protocol"))
+            .body(())
+            .unwrap();
+
+        let (response, _) = client.send_request(request, false).unwrap();
+        let response = response.await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn extended_connect_protocol_disabled_by_server() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        idle_ms(10).await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.unwrap();
+
+        assert!(!client.is_extended_connect_protocol_enabled());
+
+        let request = Request::builder()
+            .method(Method::CONNECT)
+            .uri("http://bread/baguette")
+            .extension(ext::Protocol::from("the-bread-protocol"))
+            .body(())
+            .unwrap();
+
+        let err = client.send_request(request, false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "user error: sending extended CONNECT to peer who has not enabled it"
+        );
+
+        h2.drive(idle_ms(5)).await;
+    };
+
+    join(srv, h2).await;
+}
+
 const SETTINGS: &'static [u8] = &[0, 0, 0, 4, 0, 0, 0, 0, 0];
 const SETTINGS_ACK: &'static [u8] = &[0, 0, 0, 4, 1, 0, 0, 0, 0];
 
@@ -1317,3 +1653,751 @@ impl MockH2 for mock_io::Builder {
             .read(SETTINGS_ACK)
     }
 }
+
+#[tokio::test]
+async fn recv_alt_svc_frames() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv.send_frame(frames::alt_svc(0, "https://example.net", "h3=\":443\""))
+            .await;
+        // Ignored: a connection-level frame without a valid origin, and a
+        // stream-level frame with an origin.
+        srv.send_frame(frames::alt_svc(0, "", "h3=\":443\"")).await;
+        srv.send_frame(frames::alt_svc(0, "not an origin", "h3=\":443\""))
+            .await;
+        srv.send_frame(frames::alt_svc(1, "https://example.org", "h3=\":443\""))
+            .await;
+        srv.send_frame(frames::alt_svc(1, "", "h3=\":8443\"; ma=60"))
+            .await;
+        srv.send_frame(frames::headers(1).response(200).eos()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.unwrap();
+        let mut alt_svc = h2.alt_svc().unwrap();
+        assert!(h2.alt_svc().is_none());
+
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let (response, _) = client.send_request(request, true).unwrap();
+        let stream_id = response.stream_id();
+        let response = h2.drive(response).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let conn = async move {
+            h2.await.unwrap();
+        };
+
+        let recv = async move {
+            let adv = alt_svc.alt_svc().await.unwrap();
+            assert_eq!(adv.origin().unwrap().as_str(), "https://example.net");
+            assert!(adv.stream_id().is_none());
+            assert_eq!(adv.field_value(), "h3=\":443\"");
+
+            let adv = alt_svc.alt_svc().await.unwrap();
+            assert!(adv.origin().is_none());
+            assert_eq!(adv.stream_id(), Some(stream_id));
+            assert_eq!(adv.field_value(), "h3=\":8443\"; ma=60");
+
+            // The connection is gone once it is done.
+            assert!(alt_svc.alt_svc().await.is_none());
+        };
+
+        join(conn, recv).await;
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn alt_svc_frames_are_dropped_once_queue_is_full() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        for port in 0..70 {
+            let field_value = format!("h3=\":{}\"", port);
+            srv.send_frame(frames::alt_svc(0, "https://example.net", &field_value))
+                .await;
+        }
+        srv.send_frame(frames::headers(1).response(200).eos()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.unwrap();
+        let mut alt_svc = h2.alt_svc().unwrap();
+
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let (response, _) = client.send_request(request, true).unwrap();
+        h2.drive(response).await.unwrap();
+        h2.await.unwrap();
+
+        // Only the first advertisements were kept.
+        for port in 0..64 {
+            let adv = alt_svc.alt_svc().await.unwrap();
+            assert_eq!(adv.field_value(), &format!("h3=\":{}\"", port));
+        }
+        assert!(alt_svc.alt_svc().await.is_none());
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn max_send_header_table_size_caps_encoder() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv.send_frame(frames::headers(1).response(200).eos()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::Builder::new()
+            .max_send_header_table_size(0)
+            .handshake::<_, Bytes>(io)
+            .await
+            .unwrap();
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let (response, _) = client.send_request(request, true).unwrap();
+        let response = h2.drive(response).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Nothing was added to the encoder's table.
+        assert_eq!(h2.stats().send_header_table_size(), 0);
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn idle_timeout_sends_go_away() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(frames::go_away(0)).await;
+        srv.recv_eof().await;
+    };
+
+    let h2 = async move {
+        let (_client, h2) = client::Builder::new()
+            .idle_timeout(Duration::from_millis(50))
+            .handshake::<_, Bytes>(io)
+            .await
+            .unwrap();
+
+        // The connection closes even though `SendRequest` is still around.
+        h2.await.unwrap();
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn graceful_shutdown_lets_requests_complete() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv.recv_frame(frames::go_away(0)).await;
+        srv.send_frame(frames::headers(1).response(200).eos()).await;
+        srv.recv_eof().await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.expect("handshake");
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let (response, _) = client.send_request(request, true).unwrap();
+
+        // Flush the request before shutting down.
+        h2.drive(idle_ms(10)).await;
+        h2.graceful_shutdown();
+
+        // New requests are refused once the connection is going away.
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let err = client.send_request(request, true).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "user error: opening stream after the connection started shutting down"
+        );
+
+        let response = h2.drive(response).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        h2.await.unwrap();
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn abrupt_shutdown_sends_go_away() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv.recv_frame(frames::go_away(0).reason(Reason::INTERNAL_ERROR))
+            .await;
+        srv.recv_eof().await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.expect("handshake");
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let (response, _) = client.send_request(request, true).unwrap();
+
+        // Flush the request before shutting down.
+        let response = h2.drive(async move {
+            idle_ms(10).await;
+            response
+        });
+        let response = response.await;
+
+        h2.abrupt_shutdown(Reason::INTERNAL_ERROR);
+
+        let err = join(h2, response).await.1.unwrap_err();
+        assert_eq!(err.reason(), Some(Reason::INTERNAL_ERROR));
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn upgrade_response_on_stream_1() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.send_frame(frames::headers(1).response(200).eos()).await;
+        srv.recv_frame(
+            frames::headers(3)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv.send_frame(frames::headers(3).response(204).eos()).await;
+    };
+
+    let h2 = async move {
+        let upgrade_request = Request::builder()
+            .uri("http://example.com/")
+            .body(())
+            .unwrap();
+
+        let (mut client, mut h2, response) = client::Builder::new()
+            .handshake_upgrade::<_, Bytes>(io, &upgrade_request)
+            .await
+            .expect("handshake");
+
+        let response = h2.drive(response).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.body().is_end_stream());
+
+        // Requests sent over HTTP/2.0 start at stream 3.
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let (response, _) = client.send_request(request, true).unwrap();
+        let response = h2.drive(response).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn upgrade_without_send_streams_is_rejected() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        srv.read_preface().await.unwrap();
+        // The connection is dropped before its SETTINGS are ever written.
+        srv.recv_eof().await;
+    };
+
+    let h2 = async move {
+        let upgrade_request = Request::builder()
+            .uri("http://example.com/")
+            .body(())
+            .unwrap();
+
+        let res = client::Builder::new()
+            .initial_max_send_streams(0)
+            .handshake_upgrade::<_, Bytes>(io, &upgrade_request)
+            .await;
+        assert!(res.is_err());
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn pool_opens_connection_when_saturated() {
+    h2_support::trace_init!();
+    let (io1, mut srv1) = mock::new();
+    let (io2, mut srv2) = mock::new();
+    let mut ios = vec![io2, io1];
+
+    let srv1 = async move {
+        let settings = srv1
+            .assert_client_handshake_with_settings(frames::settings().max_concurrent_streams(1))
+            .await;
+        assert_default_settings!(settings);
+        srv1.recv_frame(frames::headers(1).request("POST", "https://example.com/"))
+            .await;
+        srv1.recv_frame(frames::data(1, "hello").eos()).await;
+        srv1.send_frame(frames::headers(1).response(200).eos())
+            .await;
+        srv1.recv_frame(frames::go_away(0)).await;
+    };
+
+    let srv2 = async move {
+        let settings = srv2.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv2.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv2.send_frame(frames::headers(1).response(204).eos())
+            .await;
+        srv2.recv_frame(frames::go_away(0)).await;
+    };
+
+    let (mut pool, connection) =
+        client::Builder::new().pool::<_, _, _, Bytes>(move || ready(Ok(ios.pop().unwrap())));
+
+    let client = async move {
+        pool.ready().await.unwrap();
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let (response1, mut stream1) = pool.send_request(request, false).unwrap();
+
+        // Wait for the server's SETTINGS to limit the first connection.
+        idle_ms(10).await;
+
+        // The first connection is saturated, so a second one is opened.
+        pool.ready().await.unwrap();
+        assert_eq!(pool.num_connections(), 2);
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let (response2, _) = pool.send_request(request, true).unwrap();
+
+        stream1.send_data("hello".into(), true).unwrap();
+        let response1 = response1.await.unwrap();
+        assert_eq!(response1.status(), StatusCode::OK);
+        let response2 = response2.await.unwrap();
+        assert_eq!(response2.status(), StatusCode::NO_CONTENT);
+    };
+
+    join(join3(srv1, srv2, client), connection).await;
+}
+
+#[tokio::test]
+async fn pool_reserves_connection_for_each_handle() {
+    h2_support::trace_init!();
+    let (io1, mut srv1) = mock::new();
+    let (io2, mut srv2) = mock::new();
+    let mut ios = vec![io2, io1];
+
+    let srv1 = async move {
+        let settings = srv1
+            .assert_client_handshake_with_settings(frames::settings().max_concurrent_streams(2))
+            .await;
+        assert_default_settings!(settings);
+        srv1.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv1.recv_frame(
+            frames::headers(3)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv1.send_frame(frames::headers(1).response(200).eos())
+            .await;
+        srv1.send_frame(frames::headers(3).response(200).eos())
+            .await;
+        srv1.recv_frame(frames::go_away(0)).await;
+    };
+
+    let srv2 = async move {
+        let settings = srv2.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv2.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv2.send_frame(frames::headers(1).response(204).eos())
+            .await;
+        srv2.recv_frame(frames::go_away(0)).await;
+    };
+
+    let (mut pool1, connection) =
+        client::Builder::new().pool::<_, _, _, Bytes>(move || ready(Ok(ios.pop().unwrap())));
+    let mut pool2 = pool1.clone();
+    let mut pool3 = pool1.clone();
+
+    let client = async move {
+        pool1.ready().await.unwrap();
+
+        // Wait for the server's SETTINGS to limit the first connection.
+        idle_ms(10).await;
+
+        let request = || {
+            Request::builder()
+                .uri("https://example.com/")
+                .body(())
+                .unwrap()
+        };
+
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        // Nothing is yielded between these calls, so the first connection is
+        // only taken by the reservations of the first two handles.
+        assert!(pool2.poll_ready(&mut cx).is_ready());
+        assert!(!pool3.poll_ready(&mut cx).is_ready());
+        let (response1, _) = pool1.send_request(request(), true).unwrap();
+        let (response2, _) = pool2.send_request(request(), true).unwrap();
+        assert!(!pool3.poll_ready(&mut cx).is_ready());
+
+        // Both streams of the first connection are taken, so the third
+        // request goes to a new connection.
+        pool3.ready().await.unwrap();
+        assert_eq!(pool3.num_connections(), 2);
+        let (response3, _) = pool3.send_request(request(), true).unwrap();
+
+        assert_eq!(response1.await.unwrap().status(), StatusCode::OK);
+        assert_eq!(response2.await.unwrap().status(), StatusCode::OK);
+        assert_eq!(response3.await.unwrap().status(), StatusCode::NO_CONTENT);
+    };
+
+    join(join3(srv1, srv2, client), connection).await;
+}
+
+#[tokio::test]
+async fn pool_retires_connection_on_go_away() {
+    h2_support::trace_init!();
+    let (io1, mut srv1) = mock::new();
+    let (io2, mut srv2) = mock::new();
+    let mut ios = vec![io2, io1];
+
+    let srv1 = async move {
+        let settings = srv1.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv1.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv1.send_frame(frames::go_away(1)).await;
+        srv1.send_frame(frames::headers(1).response(200).eos())
+            .await;
+        srv1.recv_frame(frames::go_away(0)).await;
+        srv1.recv_eof().await;
+    };
+
+    let srv2 = async move {
+        let settings = srv2.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv2.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv2.send_frame(frames::headers(1).response(204).eos())
+            .await;
+        srv2.recv_frame(frames::go_away(0)).await;
+    };
+
+    let (mut pool, connection) =
+        client::Builder::new().pool::<_, _, _, Bytes>(move || ready(Ok(ios.pop().unwrap())));
+
+    let client = async move {
+        pool.ready().await.unwrap();
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let (response, _) = pool.send_request(request, true).unwrap();
+        let response = response.await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // The first connection received a GOAWAY, so the next request goes
+        // to a new connection.
+        pool.ready().await.unwrap();
+        assert_eq!(pool.num_connections(), 1);
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let (response, _) = pool.send_request(request, true).unwrap();
+        let response = response.await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    };
+
+    join(join3(srv1, srv2, client), connection).await;
+}
+
+#[tokio::test]
+async fn pool_returns_connect_error() {
+    h2_support::trace_init!();
+
+    let (mut pool, connection) = client::Builder::new().pool::<_, _, mock::Mock, Bytes>(|| {
+        ready(Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            "connection refused",
+        )))
+    });
+
+    let client = async move {
+        let err = pool.ready().await.unwrap_err();
+        assert_eq!(
+            err.get_io().unwrap().kind(),
+            io::ErrorKind::ConnectionRefused
+        );
+    };
+
+    join(client, connection).await;
+}
+
+#[tokio::test]
+async fn request_timeout_resets_stream_awaiting_response() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv.recv_frame(frames::reset(1).cancel()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.unwrap();
+        let mut request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(h2::ext::RequestTimeout::new(Duration::from_millis(10)));
+
+        let (response, _) = client.send_request(request, true).unwrap();
+        let err = h2.drive(response).await.unwrap_err();
+        assert!(err.is_request_timeout());
+        assert_eq!(err.to_string(), "request timed out");
+        h2.await.unwrap();
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn request_timeout_resets_stream_receiving_body() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv.send_frame(frames::headers(1).response(200)).await;
+        srv.send_frame(frames::data(1, "hello")).await;
+        srv.recv_frame(frames::reset(1).cancel()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.unwrap();
+        let mut request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(h2::ext::RequestTimeout::new(Duration::from_millis(50)));
+
+        let (response, _) = client.send_request(request, true).unwrap();
+        let response = h2.drive(response).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let mut body = response.into_body();
+        let data = h2.drive(body.data()).await.unwrap().unwrap();
+        assert_eq!(data, "hello");
+
+        // The rest of the body doesn't arrive before the deadline.
+        let err = h2.drive(body.data()).await.unwrap().unwrap_err();
+        assert!(err.is_request_timeout());
+        h2.await.unwrap();
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn request_timeout_not_triggered_after_response() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv.send_frame(frames::headers(1).response(200).eos()).await;
+        idle_ms(50).await;
+        srv.recv_frame(frames::go_away(0)).await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.unwrap();
+        let mut request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(h2::ext::RequestTimeout::new(Duration::from_millis(10)));
+
+        let (response, _) = client.send_request(request, true).unwrap();
+        let response = h2.drive(response).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        h2.drive(idle_ms(50)).await;
+
+        drop(client);
+        drop(response);
+        h2.await.unwrap();
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn request_timeout_elapses_when_server_stops_reading() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.stop_reading();
+        // Keep the handle, so writes stay blocked until the client is done.
+        srv
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.unwrap();
+        // Let the handshake complete before the server stops reading.
+        h2.drive(idle_ms(10)).await;
+
+        let mut request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(h2::ext::RequestTimeout::new(Duration::from_millis(10)));
+
+        // The HEADERS frame can't be written, but the request still times
+        // out.
+        let (response, _) = client.send_request(request, true).unwrap();
+        let err = h2.drive(response).await.unwrap_err();
+        assert!(err.is_request_timeout());
+    };
+
+    let (_srv, ()) = join(srv, h2).await;
+}
@@ -240,3 +240,52 @@ async fn read_goaway_with_debug_data() {
 
     assert_closed!(codec);
 }
+
+#[tokio::test]
+async fn read_too_many_continuation_frames() {
+    use futures::StreamExt;
+
+    h2_support::trace_init!();
+    let mut codec = raw_codec! {
+        read => [
+            // HEADERS without END_HEADERS
+            0, 0, 1, 1, 0, 0, 0, 0, 1,
+            0x88,
+            // Empty CONTINUATION frames
+            0, 0, 0, 9, 0, 0, 0, 0, 1,
+            0, 0, 0, 9, 0, 0, 0, 0, 1,
+            0, 0, 0, 9, 0, 0, 0, 0, 1,
+        ];
+    };
+
+    codec.set_max_recv_continuation_frames(2);
+
+    match codec.next().await.unwrap() {
+        Err(RecvError::Connection(reason)) => assert_eq!(reason, Reason::ENHANCE_YOUR_CALM),
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
+
+#[tokio::test]
+async fn read_header_block_over_size_limit() {
+    use futures::StreamExt;
+
+    h2_support::trace_init!();
+    let mut codec = raw_codec! {
+        read => [
+            // HEADERS without END_HEADERS
+            0, 0, 1, 1, 0, 0, 0, 0, 1,
+            0x88,
+            // CONTINUATION with END_HEADERS
+            0, 0, 4, 9, 4, 0, 0, 0, 1,
+            0x88, 0x88, 0x88, 0x88,
+        ];
+    };
+
+    codec.set_max_recv_header_block_size(4);
+
+    match codec.next().await.unwrap() {
+        Err(RecvError::Connection(reason)) => assert_eq!(reason, Reason::ENHANCE_YOUR_CALM),
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
@@ -0,0 +1,270 @@
+use futures::future::join;
+use futures::StreamExt;
+use h2::ext::ExtensionFrame;
+use h2_support::prelude::*;
+
+#[tokio::test]
+async fn server_recv_registered_extension_frames() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        // Not registered, so ignored.
+        client
+            .send_frame(frames::extension(0xb1, 0, b"ignored"))
+            .await;
+        client
+            .send_frame(frames::extension(0xb0, 0, b"hello"))
+            .await;
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        client
+            .send_frame(frames::extension(0xb0, 1, b"world"))
+            .await;
+        client
+            .recv_frame(frames::extension(0xb0, 1, b"reply"))
+            .await;
+        client
+            .recv_frame(frames::headers(1).response(200).eos())
+            .await;
+    };
+
+    let srv = async move {
+        let mut srv = server::Builder::new()
+            .extension_frame_type(0xb0)
+            .handshake::<_, Bytes>(io)
+            .await
+            .expect("handshake");
+        let mut frames = srv.extension_frames().unwrap();
+        assert!(srv.extension_frames().is_none());
+
+        let (req, mut stream) = srv.next().await.unwrap().unwrap();
+
+        let srv = async move {
+            assert!(srv.next().await.is_none());
+        };
+
+        let handler = async move {
+            let frame = frames.recv_frame().await.unwrap();
+            assert_eq!(frame.frame_type(), 0xb0);
+            assert_eq!(frame.stream_id(), None);
+            assert_eq!(&frame.payload()[..], b"hello");
+
+            let frame = frames.recv_frame().await.unwrap();
+            assert_eq!(frame.stream_id(), Some(stream.stream_id()));
+            assert_eq!(&frame.payload()[..], b"world");
+
+            let mut reply = ExtensionFrame::new(0xb0, 0, Bytes::from_static(b"reply"));
+            reply.set_stream_id(stream.stream_id());
+            frames.send_frame(reply).unwrap();
+
+            // Give the connection the chance to write the reply first.
+            idle_ms(10).await;
+
+            drop(req);
+            let rsp = http::Response::builder().status(200).body(()).unwrap();
+            stream.send_response(rsp, true).unwrap();
+
+            // The connection is gone once it is done.
+            assert!(frames.recv_frame().await.is_none());
+            let frame = ExtensionFrame::new(0xb0, 0, Bytes::new());
+            assert!(frames.send_frame(frame).is_err());
+        };
+
+        join(srv, handler).await;
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn client_send_extension_frame() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(frames::extension(0xb0, 0, b"hello")).await;
+    };
+
+    let h2 = async move {
+        let (_client, mut h2) = client::handshake(io).await.unwrap();
+        let mut frames = h2.extension_frames().unwrap();
+        // Let the SETTINGS be acknowledged first.
+        h2.drive(idle_ms(10)).await;
+
+        let frame = ExtensionFrame::new(0xb0, 0, Bytes::from_static(b"hello"));
+        frames.send_frame(frame).unwrap();
+
+        let frame = ExtensionFrame::new(0xb0, 0, vec![0; 16_385].into());
+        let err = frames.send_frame(frame).unwrap_err();
+        assert_eq!(err.to_string(), "user error: payload too big");
+
+        h2.await.unwrap();
+    };
+
+    join(srv, h2).await;
+}
+
+#[test]
+#[should_panic]
+fn extension_frame_with_known_type_panics() {
+    ExtensionFrame::new(0x0, 0, Bytes::new());
+}
+
+#[tokio::test]
+async fn recv_extension_frame_within_header_block_is_connection_error() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv.send_bytes(&[
+            0, 0, 1, // len
+            1, // type: HEADERS
+            0, // flags: no END_HEADERS, a CONTINUATION must follow
+            0, 0, 0, 1,    // stream id
+            0x88, // :status = 200
+        ])
+        .await;
+        srv.send_frame(frames::extension(0xb0, 0, b"")).await;
+        srv.recv_frame(frames::go_away(0).protocol_error()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, h2) = client::Builder::new()
+            .extension_frame_type(0xb0)
+            .handshake::<_, Bytes>(io)
+            .await
+            .unwrap();
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let (response, _) = client.send_request(request, true).unwrap();
+
+        let (response, conn) = join(response, h2).await;
+        assert_eq!(response.unwrap_err().reason(), Some(Reason::PROTOCOL_ERROR));
+        assert_eq!(conn.unwrap_err().reason(), Some(Reason::PROTOCOL_ERROR));
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn recv_extension_frame_flood_is_calm() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        for _ in 0..3 {
+            client
+                .send_frame(frames::extension(0xb0, 0, b"hello"))
+                .await;
+        }
+        client.recv_frame(frames::go_away(0).calm()).await;
+        client.recv_eof().await;
+    };
+
+    let srv = async move {
+        let mut srv = server::Builder::new()
+            .extension_frame_type(0xb0)
+            .max_queued_extension_frames(2)
+            .handshake::<_, Bytes>(io)
+            .await
+            .expect("handshake");
+        // The frames are never received.
+        let _frames = srv.extension_frames().unwrap();
+
+        let err = srv.next().await.unwrap().unwrap_err();
+        assert_eq!(err.reason(), Some(Reason::ENHANCE_YOUR_CALM));
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn recv_extension_frame_on_idle_stream_is_connection_error() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        client
+            .send_frame(frames::extension(0xb0, 1, b"hello"))
+            .await;
+        client.recv_frame(frames::go_away(0).protocol_error()).await;
+        client.recv_eof().await;
+    };
+
+    let srv = async move {
+        let mut srv = server::Builder::new()
+            .extension_frame_type(0xb0)
+            .handshake::<_, Bytes>(io)
+            .await
+            .expect("handshake");
+        let _frames = srv.extension_frames().unwrap();
+
+        let err = srv.next().await.unwrap().unwrap_err();
+        assert_eq!(err.reason(), Some(Reason::PROTOCOL_ERROR));
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn send_extension_frame_waits_for_room() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(frames::extension(0xb0, 0, b"one")).await;
+        srv.recv_frame(frames::extension(0xb0, 0, b"two")).await;
+    };
+
+    let h2 = async move {
+        let (_client, mut h2) = client::Builder::new()
+            .max_queued_extension_frames(1)
+            .handshake::<_, Bytes>(io)
+            .await
+            .unwrap();
+        let mut frames = h2.extension_frames().unwrap();
+        // Let the SETTINGS be acknowledged first.
+        h2.drive(idle_ms(10)).await;
+
+        let frame = ExtensionFrame::new(0xb0, 0, Bytes::from_static(b"one"));
+        frames.send_frame(frame).unwrap();
+
+        // The queue is full until the connection writes the first frame.
+        let frame = ExtensionFrame::new(0xb0, 0, Bytes::from_static(b"two"));
+        let err = frames.send_frame(frame.clone()).unwrap_err();
+        assert_eq!(err.to_string(), "user error: rejected");
+
+        h2.drive(frames.ready()).await.unwrap();
+        frames.send_frame(frame).unwrap();
+
+        h2.await.unwrap();
+    };
+
+    join(srv, h2).await;
+}
@@ -1,3 +1,4 @@
+use futures::channel::oneshot;
 use futures::future::{join, join4};
 use futures::{StreamExt, TryStreamExt};
 use h2_support::prelude::*;
@@ -1561,3 +1562,523 @@ async fn data_padding() {
 
     join(srv, h2).await;
 }
+
+#[tokio::test]
+async fn adaptive_window_grows_with_bdp() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+    let (tx, rx) = oneshot::channel();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://http2.akamai.com/")
+                .eos(),
+        )
+        .await;
+        srv.send_frame(frames::headers(1).response(200)).await;
+
+        // The first DATA frame starts a BDP sample.
+        srv.send_frame(frames::data(1, vec![0; 16_384])).await;
+        srv.recv_frame(frames::ping(frame::Ping::BDP)).await;
+
+        // Fill the rest of the default window before acknowledging.
+        srv.send_frame(frames::data(1, vec![0; 16_384])).await;
+        srv.send_frame(frames::data(1, vec![0; 16_384])).await;
+        srv.send_frame(frames::data(1, vec![0; 16_383]).eos()).await;
+        srv.send_frame(frames::ping(frame::Ping::BDP).pong()).await;
+
+        // Both windows are doubled.
+        srv.recv_frame(frames::settings().initial_window_size(131_070))
+            .await;
+        srv.recv_frame(frames::window_update(0, 65_535)).await;
+        srv.send_frame(frames::settings_ack()).await;
+        tx.send(()).unwrap();
+    };
+
+    let client = async move {
+        let (mut client, conn) = client::Builder::new()
+            .adaptive_window(true)
+            .handshake::<_, Bytes>(io)
+            .await
+            .unwrap();
+        let request = Request::builder()
+            .uri("https://http2.akamai.com/")
+            .body(())
+            .unwrap();
+
+        let res = client.send_request(request, true).unwrap().0;
+        drop(client);
+
+        let res = async move {
+            let res = res.await.unwrap();
+            // Hold on to the received data until the server is done so no
+            // other WINDOW_UPDATE frames are sent.
+            rx.await.unwrap();
+            let body = util::concat(res.into_body()).await.unwrap();
+            assert_eq!(body.len(), 65_535);
+        };
+
+        join(async move { conn.await.expect("client") }, res).await;
+    };
+
+    join(srv, client).await;
+}
+
+#[tokio::test]
+async fn adaptive_window_does_not_shrink_connection_window() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+    let (tx, rx) = oneshot::channel();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(frames::window_update(0, (1 << 20) - 65_535))
+            .await;
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://http2.akamai.com/")
+                .eos(),
+        )
+        .await;
+        srv.send_frame(frames::headers(1).response(200)).await;
+
+        srv.send_frame(frames::data(1, vec![0; 16_384])).await;
+        srv.recv_frame(frames::ping(frame::Ping::BDP)).await;
+        srv.send_frame(frames::data(1, vec![0; 16_384])).await;
+        srv.send_frame(frames::data(1, vec![0; 16_384])).await;
+        srv.send_frame(frames::data(1, vec![0; 16_383]).eos()).await;
+        srv.send_frame(frames::ping(frame::Ping::BDP).pong()).await;
+
+        // The estimate is below the configured connection window, so
+        // neither window is touched.
+        srv.send_frame(frames::ping([1; 8])).await;
+        srv.recv_frame(frames::ping([1; 8]).pong()).await;
+        tx.send(()).unwrap();
+    };
+
+    let client = async move {
+        let (mut client, conn) = client::Builder::new()
+            .adaptive_window(true)
+            .initial_connection_window_size(1 << 20)
+            .handshake::<_, Bytes>(io)
+            .await
+            .unwrap();
+        let request = Request::builder()
+            .uri("https://http2.akamai.com/")
+            .body(())
+            .unwrap();
+
+        let res = client.send_request(request, true).unwrap().0;
+        drop(client);
+
+        let res = async move {
+            let res = res.await.unwrap();
+            rx.await.unwrap();
+            let body = util::concat(res.into_body()).await.unwrap();
+            assert_eq!(body.len(), 65_535);
+        };
+
+        join(async move { conn.await.expect("client") }, res).await;
+    };
+
+    join(srv, client).await;
+}
+
+#[tokio::test]
+async fn empty_data_flood_is_calm() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        client
+            .send_frame(frames::headers(1).request("POST", "https://example.com/"))
+            .await;
+        client.send_frame(frames::data(1, &b""[..])).await;
+        client.send_frame(frames::data(1, &b""[..])).await;
+        client.send_frame(frames::data(1, &b""[..])).await;
+        client.recv_frame(frames::go_away(1).calm()).await;
+    };
+
+    let srv = async move {
+        let mut srv = server::Builder::new()
+            .max_empty_data_frames_per_second(2)
+            .handshake::<_, Bytes>(io)
+            .await
+            .expect("handshake");
+        let err = srv.next().await.unwrap().expect_err("accept");
+        assert_eq!(err.reason(), Some(Reason::ENHANCE_YOUR_CALM));
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn send_buffer_size_caps_capacity() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::Builder::new()
+            .max_send_buffer_size(5)
+            .handshake::<_, Bytes>(io)
+            .await
+            .unwrap();
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("https://http2.akamai.com/")
+            .body(())
+            .unwrap();
+
+        let (response, mut stream) = client.send_request(request, false).unwrap();
+
+        // The window is much larger than the send buffer.
+        stream.reserve_capacity(100);
+        let mut stream = h2.drive(util::wait_for_capacity(stream, 5)).await;
+        assert_eq!(stream.capacity(), 5);
+
+        stream.send_data("hello".into(), false).unwrap();
+        assert_eq!(stream.capacity(), 0);
+
+        // Capacity is reported again once the data is written.
+        let mut stream = h2.drive(util::wait_for_capacity(stream, 5)).await;
+        assert_eq!(stream.capacity(), 5);
+        stream.send_data("world".into(), true).unwrap();
+
+        let response = h2.drive(response).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        h2.await.unwrap();
+    };
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(frames::headers(1).request("POST", "https://http2.akamai.com/"))
+            .await;
+        srv.recv_frame(frames::data(1, "hello")).await;
+        srv.recv_frame(frames::data(1, "world").eos()).await;
+        srv.send_frame(frames::headers(1).response(204).eos()).await;
+    };
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn send_stream_writer_waits_for_capacity() {
+    use tokio::io::AsyncWriteExt;
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv
+            .assert_client_handshake_with_settings(frames::settings().initial_window_size(5))
+            .await;
+        assert_default_settings!(settings);
+        srv.recv_frame(frames::headers(1).request("POST", "https://www.example.com/"))
+            .await;
+        srv.recv_frame(frames::data(1, "hello")).await;
+        srv.send_frame(frames::window_update(1, 10)).await;
+        srv.recv_frame(frames::data(1, " world")).await;
+        srv.recv_frame(frames::data(1, "").eos()).await;
+        srv.send_frame(frames::headers(1).response(200).eos()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.unwrap();
+        // Wait for the server's SETTINGS to shrink the stream window.
+        h2.drive(idle_ms(10)).await;
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("https://www.example.com/")
+            .body(())
+            .unwrap();
+        let (response, stream) = client.send_request(request, false).unwrap();
+
+        let mut writer = h2::SendStreamWriter::new(stream);
+        h2.drive(writer.write_all(b"hello world")).await.unwrap();
+        h2.drive(writer.shutdown()).await.unwrap();
+
+        let response = h2.drive(response).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        h2.await.unwrap();
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn send_stream_writer_reports_reset() {
+    use tokio::io::AsyncWriteExt;
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv
+            .assert_client_handshake_with_settings(frames::settings().initial_window_size(5))
+            .await;
+        assert_default_settings!(settings);
+        srv.recv_frame(frames::headers(1).request("POST", "https://www.example.com/"))
+            .await;
+        srv.recv_frame(frames::data(1, "hello")).await;
+        srv.send_frame(frames::reset(1).cancel()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.unwrap();
+        // Wait for the server's SETTINGS to shrink the stream window.
+        h2.drive(idle_ms(10)).await;
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("https://www.example.com/")
+            .body(())
+            .unwrap();
+        let (_response, stream) = client.send_request(request, false).unwrap();
+
+        let mut writer = h2::SendStreamWriter::new(stream);
+        let err = h2
+            .drive(writer.write_all(b"hello world"))
+            .await
+            .unwrap_err();
+        let err = err
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<h2::Error>())
+            .expect("h2 error");
+        assert_eq!(err.reason(), Some(Reason::CANCEL));
+        drop(client);
+        h2.await.unwrap();
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn send_stream_writer_sink() {
+    use futures::SinkExt;
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv
+            .assert_client_handshake_with_settings(frames::settings().initial_window_size(5))
+            .await;
+        assert_default_settings!(settings);
+        srv.recv_frame(frames::headers(1).request("POST", "https://www.example.com/"))
+            .await;
+        srv.recv_frame(frames::data(1, "hello")).await;
+        srv.send_frame(frames::window_update(1, 10)).await;
+        srv.recv_frame(frames::data(1, " world")).await;
+        srv.recv_frame(frames::data(1, "").eos()).await;
+        srv.send_frame(frames::headers(1).response(200).eos()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.unwrap();
+        // Wait for the server's SETTINGS to shrink the stream window.
+        h2.drive(idle_ms(10)).await;
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("https://www.example.com/")
+            .body(())
+            .unwrap();
+        let (response, stream) = client.send_request(request, false).unwrap();
+
+        let mut writer = h2::SendStreamWriter::new(stream);
+        h2.drive(writer.send(Bytes::from_static(b"hello world")))
+            .await
+            .unwrap();
+        h2.drive(writer.close()).await.unwrap();
+
+        let response = h2.drive(response).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        h2.await.unwrap();
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn auto_release_capacity_sends_window_update() {
+    h2_support::trace_init!();
+
+    let payload = vec![0u8; 16_384];
+    let payload_len = payload.len();
+
+    let (io, mut srv) = mock::new();
+
+    let mock = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://http2.akamai.com/")
+                .eos(),
+        )
+        .await;
+        srv.send_frame(frames::headers(1).response(200)).await;
+        srv.send_frame(frames::data(1, &payload[..])).await;
+        srv.send_frame(frames::data(1, &payload[..])).await;
+        srv.recv_frame(frames::window_update(0, 32_768)).await;
+        srv.recv_frame(frames::window_update(1, 32_768)).await;
+        srv.send_frame(frames::data(1, &payload[..]).eos()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, h2) = client::Builder::new()
+            .auto_release_capacity(true)
+            .handshake::<_, Bytes>(io)
+            .await
+            .unwrap();
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("https://http2.akamai.com/")
+            .body(())
+            .unwrap();
+
+        let req = async move {
+            let resp = client.send_request(request, true).unwrap().0.await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            let mut body = resp.into_parts().1;
+
+            // The capacity is released without calling `release_capacity`.
+            for _ in 0..3 {
+                let buf = body.data().await.unwrap().unwrap();
+                assert_eq!(buf.len(), payload_len);
+            }
+            assert!(body.data().await.is_none());
+        };
+
+        join(
+            async move {
+                h2.await.unwrap();
+            },
+            req,
+        )
+        .await
+    };
+    join(mock, h2).await;
+}
+
+#[tokio::test]
+async fn recv_stream_reader_releases_capacity() {
+    use tokio::io::AsyncReadExt;
+    h2_support::trace_init!();
+
+    let payload = vec![7u8; 16_384];
+
+    let (io, mut srv) = mock::new();
+
+    let mock = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://http2.akamai.com/")
+                .eos(),
+        )
+        .await;
+        srv.send_frame(frames::headers(1).response(200)).await;
+        srv.send_frame(frames::data(1, &payload[..])).await;
+        srv.send_frame(frames::data(1, &payload[..])).await;
+        srv.recv_frame(frames::window_update(0, 32_768)).await;
+        srv.recv_frame(frames::window_update(1, 32_768)).await;
+        srv.send_frame(frames::data(1, "done").eos()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, h2) = client::handshake(io).await.unwrap();
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("https://http2.akamai.com/")
+            .body(())
+            .unwrap();
+
+        let req = async move {
+            let resp = client.send_request(request, true).unwrap().0.await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+
+            let mut reader = h2::RecvStreamReader::new(resp.into_body());
+            let mut body = Vec::new();
+            reader.read_to_end(&mut body).await.unwrap();
+            assert_eq!(body.len(), 32_768 + 4);
+            assert!(body[..32_768].iter().all(|b| *b == 7));
+            assert_eq!(&body[32_768..], b"done");
+        };
+
+        join(
+            async move {
+                h2.await.unwrap();
+            },
+            req,
+        )
+        .await
+    };
+    join(mock, h2).await;
+}
+
+#[tokio::test]
+async fn recv_stream_reader_keeps_capacity_of_unread_data() {
+    use tokio::io::AsyncReadExt;
+    h2_support::trace_init!();
+
+    let payload = vec![7u8; 16_384];
+
+    let (io, mut srv) = mock::new();
+
+    let mock = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://http2.akamai.com/")
+                .eos(),
+        )
+        .await;
+        srv.send_frame(frames::headers(1).response(200)).await;
+        srv.send_frame(frames::data(1, &payload[..]).eos()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, h2) = client::Builder::new()
+            .auto_release_capacity(true)
+            .handshake::<_, Bytes>(io)
+            .await
+            .unwrap();
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("https://http2.akamai.com/")
+            .body(())
+            .unwrap();
+
+        let req = async move {
+            let resp = client.send_request(request, true).unwrap().0.await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+
+            let mut reader = h2::RecvStreamReader::new(resp.into_body());
+            let mut buf = [0; 10_000];
+            reader.read_exact(&mut buf).await.unwrap();
+
+            // Only the capacity of the bytes that were read is released.
+            let flow = reader.get_mut().flow_control();
+            assert_eq!(flow.used_capacity(), 16_384 - 10_000);
+
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest).await.unwrap();
+            assert_eq!(rest.len(), 16_384 - 10_000);
+            assert_eq!(reader.get_mut().flow_control().used_capacity(), 0);
+        };
+
+        join(
+            async move {
+                h2.await.unwrap();
+            },
+            req,
+        )
+        .await
+    };
+    join(mock, h2).await;
+}
@@ -184,3 +184,163 @@ async fn user_notifies_when_connection_closes() {
         "broken pipe",
     );
 }
+
+#[tokio::test]
+async fn keep_alive_ping_sent_while_stream_open() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(frames::headers(1).request("GET", "https://example.com/"))
+            .await;
+        srv.recv_frame(frames::ping(frame::Ping::KEEP_ALIVE)).await;
+        srv.send_frame(frames::ping(frame::Ping::KEEP_ALIVE).pong())
+            .await;
+        srv.send_frame(frames::headers(1).response(200).eos()).await;
+    };
+
+    let client = async move {
+        let (mut client, conn) = client::Builder::new()
+            .keep_alive_interval(Duration::from_millis(20))
+            .handshake::<_, Bytes>(io)
+            .await
+            .expect("handshake");
+        tokio::spawn(async move {
+            conn.await.expect("connection");
+        });
+
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let (response, _stream) = client.send_request(request, false).unwrap();
+        let response = response.await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    };
+
+    join(srv, client).await;
+}
+
+#[tokio::test]
+async fn keep_alive_ping_not_sent_while_idle() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        // Several intervals pass without a PING on an idle connection.
+        idle_ms(100).await;
+        client.send_frame(frames::ping([1; 8])).await;
+        client.recv_frame(frames::ping([1; 8]).pong()).await;
+    };
+
+    let srv = async move {
+        let mut srv = server::Builder::new()
+            .keep_alive_interval(Duration::from_millis(20))
+            .handshake::<_, Bytes>(io)
+            .await
+            .expect("handshake");
+        assert!(srv.next().await.is_none());
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn keep_alive_ping_sent_while_idle() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        client
+            .recv_frame(frames::ping(frame::Ping::KEEP_ALIVE))
+            .await;
+        client
+            .send_frame(frames::ping(frame::Ping::KEEP_ALIVE).pong())
+            .await;
+        // Another PING follows once the interval elapses again.
+        client
+            .recv_frame(frames::ping(frame::Ping::KEEP_ALIVE))
+            .await;
+        client
+            .send_frame(frames::ping(frame::Ping::KEEP_ALIVE).pong())
+            .await;
+    };
+
+    let srv = async move {
+        let mut srv = server::Builder::new()
+            .keep_alive_interval(Duration::from_millis(20))
+            .keep_alive_while_idle(true)
+            .handshake::<_, Bytes>(io)
+            .await
+            .expect("handshake");
+        assert!(srv.next().await.is_none());
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn keep_alive_timeout_closes_connection() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(frames::ping(frame::Ping::KEEP_ALIVE)).await;
+        // Never acknowledge the PING, and keep the connection open long
+        // enough for the timeout to elapse.
+        idle_ms(200).await;
+    };
+
+    let client = async move {
+        let (_client, conn) = client::Builder::new()
+            .keep_alive_interval(Duration::from_millis(20))
+            .keep_alive_timeout(Duration::from_millis(50))
+            .keep_alive_while_idle(true)
+            .handshake::<_, Bytes>(io)
+            .await
+            .expect("handshake");
+
+        let err = conn.await.expect_err("connection");
+        assert!(err.is_keep_alive_timeout());
+        assert_eq!(err.to_string(), "keep-alive timed out");
+    };
+
+    join(srv, client).await;
+}
+
+#[tokio::test]
+async fn ping_flood_is_calm() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        client.send_frame(frames::ping([1; 8])).await;
+        client.send_frame(frames::ping([2; 8])).await;
+        client.send_frame(frames::ping([3; 8])).await;
+        client.recv_frame(frames::ping([1; 8]).pong()).await;
+        client.recv_frame(frames::ping([2; 8]).pong()).await;
+        client.recv_frame(frames::go_away(0).calm()).await;
+    };
+
+    let srv = async move {
+        let mut srv = server::Builder::new()
+            .max_control_frames_per_second(2)
+            .handshake::<_, Bytes>(io)
+            .await
+            .expect("handshake");
+        let err = srv.next().await.unwrap().expect_err("accept");
+        assert_eq!(err.reason(), Some(Reason::ENHANCE_YOUR_CALM));
+    };
+
+    join(client, srv).await;
+}
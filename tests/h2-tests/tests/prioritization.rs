@@ -408,3 +408,530 @@ async fn send_data_receive_window_update() {
 
     join(mock, h2).await;
 }
+
+#[tokio::test]
+async fn exclusive_dependency_is_sent_after_parent() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        // Stream 1 depends exclusively on stream 3.
+        client.send_frame(frames::priority(1, 3, 15, true)).await;
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("GET", "https://example.com/image.png")
+                    .eos(),
+            )
+            .await;
+        client
+            .send_frame(
+                frames::headers(3)
+                    .request("GET", "https://example.com/style.css")
+                    .eos(),
+            )
+            .await;
+        client.recv_frame(frames::headers(3).response(200)).await;
+        client
+            .recv_frame(frames::data(3, vec![0; 16_384]).eos())
+            .await;
+        client.recv_frame(frames::headers(1).response(200)).await;
+        client
+            .recv_frame(frames::data(1, vec![0; 16_384]).eos())
+            .await;
+    };
+
+    let srv = async move {
+        let mut srv = server::handshake(io).await.expect("handshake");
+        let (_, mut stream1) = srv.next().await.unwrap().unwrap();
+        let (_, mut stream3) = srv.next().await.unwrap().unwrap();
+
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        let mut stream1 = stream1.send_response(rsp, false).unwrap();
+        stream1.send_data(vec![0; 16_384].into(), true).unwrap();
+
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        let mut stream3 = stream3.send_response(rsp, false).unwrap();
+        stream3.send_data(vec![0; 16_384].into(), true).unwrap();
+
+        assert!(srv.next().await.is_none());
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn sibling_streams_share_by_weight() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        client
+            .send_frame(
+                frames::headers(3)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        // Stream 1 gets a weight of 1, stream 3 a weight of 256.
+        client.send_frame(frames::priority(1, 0, 0, false)).await;
+        client.send_frame(frames::priority(3, 0, 255, false)).await;
+        client.recv_frame(frames::headers(1).response(200)).await;
+        client.recv_frame(frames::headers(3).response(200)).await;
+        client.recv_frame(frames::data(1, vec![0; 16_384])).await;
+        // Having sent far less than its share, stream 3 sends twice in a row.
+        client.recv_frame(frames::data(3, vec![0; 16_384])).await;
+        client
+            .recv_frame(frames::data(3, vec![0; 16_000]).eos())
+            .await;
+        client
+            .recv_frame(frames::data(1, vec![0; 16_000]).eos())
+            .await;
+    };
+
+    let srv = async move {
+        let mut srv = server::handshake(io).await.expect("handshake");
+        let (_, mut stream1) = srv.next().await.unwrap().unwrap();
+        let (_, mut stream3) = srv.next().await.unwrap().unwrap();
+
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        let mut stream1 = stream1.send_response(rsp, false).unwrap();
+        stream1.send_data(vec![0; 32_384].into(), true).unwrap();
+
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        let mut stream3 = stream3.send_response(rsp, false).unwrap();
+        stream3.send_data(vec![0; 32_384].into(), true).unwrap();
+
+        assert!(srv.next().await.is_none());
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn priority_on_idle_stream_creates_grouping_node() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        // Stream 11 is never opened, but groups stream 3 below it with a
+        // weight of 256. Stream 1 keeps the default weight of 16.
+        client.send_frame(frames::priority(11, 0, 255, false)).await;
+        client.send_frame(frames::priority(3, 11, 15, false)).await;
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        client
+            .send_frame(
+                frames::headers(3)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        client.recv_frame(frames::headers(1).response(200)).await;
+        client.recv_frame(frames::headers(3).response(200)).await;
+        client.recv_frame(frames::data(1, vec![0; 16_384])).await;
+        client.recv_frame(frames::data(3, vec![0; 16_384])).await;
+        client
+            .recv_frame(frames::data(3, vec![0; 16_000]).eos())
+            .await;
+        client
+            .recv_frame(frames::data(1, vec![0; 16_000]).eos())
+            .await;
+    };
+
+    let srv = async move {
+        let mut srv = server::handshake(io).await.expect("handshake");
+        let (_, mut stream1) = srv.next().await.unwrap().unwrap();
+        let (_, mut stream3) = srv.next().await.unwrap().unwrap();
+
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        let mut stream1 = stream1.send_response(rsp, false).unwrap();
+        stream1.send_data(vec![0; 32_384].into(), true).unwrap();
+
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        let mut stream3 = stream3.send_response(rsp, false).unwrap();
+        stream3.send_data(vec![0; 32_384].into(), true).unwrap();
+
+        assert!(srv.next().await.is_none());
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn priority_header_urgency_orders_responses() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("GET", "https://example.com/image.png")
+                    .field("priority", "u=5, i")
+                    .eos(),
+            )
+            .await;
+        client
+            .send_frame(
+                frames::headers(3)
+                    .request("GET", "https://example.com/style.css")
+                    .field("priority", "u=1")
+                    .eos(),
+            )
+            .await;
+        client.recv_frame(frames::headers(3).response(200)).await;
+        client
+            .recv_frame(frames::data(3, vec![0; 16_384]).eos())
+            .await;
+        client.recv_frame(frames::headers(1).response(200)).await;
+        client
+            .recv_frame(frames::data(1, vec![0; 16_384]).eos())
+            .await;
+    };
+
+    let srv = async move {
+        let mut srv = server::handshake(io).await.expect("handshake");
+        let (_, mut stream1) = srv.next().await.unwrap().unwrap();
+        let (_, mut stream3) = srv.next().await.unwrap().unwrap();
+
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        let mut stream1 = stream1.send_response(rsp, false).unwrap();
+        stream1.send_data(vec![0; 16_384].into(), true).unwrap();
+
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        let mut stream3 = stream3.send_response(rsp, false).unwrap();
+        stream3.send_data(vec![0; 16_384].into(), true).unwrap();
+
+        assert!(srv.next().await.is_none());
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn many_streams_are_served_by_urgency() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    const N: u32 = 500;
+    let urgency = |id: u32| (id * 3) % 8;
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        for id in (1..N * 2).step_by(2) {
+            client
+                .send_frame(
+                    frames::headers(id)
+                        .request("GET", "https://example.com/")
+                        .field("priority", format!("u={}", urgency(id)))
+                        .eos(),
+                )
+                .await;
+        }
+
+        let mut ids: Vec<u32> = (1..N * 2).step_by(2).collect();
+        ids.sort_by_key(|&id| (urgency(id), id));
+        for id in ids {
+            client.recv_frame(frames::headers(id).response(200)).await;
+            client.recv_frame(frames::data(id, vec![0; 1]).eos()).await;
+        }
+    };
+
+    let srv = async move {
+        let mut srv = server::handshake(io).await.expect("handshake");
+        let mut streams = Vec::new();
+        for _ in 0..N {
+            let (_, stream) = srv.next().await.unwrap().unwrap();
+            streams.push(stream);
+        }
+
+        for mut stream in streams {
+            let rsp = http::Response::builder().status(200).body(()).unwrap();
+            let mut stream = stream.send_response(rsp, false).unwrap();
+            stream.send_data(vec![0; 1].into(), true).unwrap();
+        }
+
+        assert!(srv.next().await.is_none());
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn priority_update_before_headers_overrides_header() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        client.send_frame(frames::priority_update(1, "u=0")).await;
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("GET", "https://example.com/")
+                    .field("priority", "u=7")
+                    .eos(),
+            )
+            .await;
+        client
+            .send_frame(
+                frames::headers(3)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        client.recv_frame(frames::headers(1).response(200)).await;
+        client
+            .recv_frame(frames::data(1, vec![0; 16_384]).eos())
+            .await;
+        client.recv_frame(frames::headers(3).response(200)).await;
+        client
+            .recv_frame(frames::data(3, vec![0; 16_384]).eos())
+            .await;
+    };
+
+    let srv = async move {
+        let mut srv = server::handshake(io).await.expect("handshake");
+        let (_, mut stream1) = srv.next().await.unwrap().unwrap();
+        let (_, mut stream3) = srv.next().await.unwrap().unwrap();
+
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        let mut stream3 = stream3.send_response(rsp, false).unwrap();
+        stream3.send_data(vec![0; 16_384].into(), true).unwrap();
+
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        let mut stream1 = stream1.send_response(rsp, false).unwrap();
+        stream1.send_data(vec![0; 16_384].into(), true).unwrap();
+
+        assert!(srv.next().await.is_none());
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn disabled_rfc7540_priorities_are_ignored() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_eq!(settings.is_rfc7540_priorities_disabled(), Some(true));
+        // Stream 1 depends exclusively on stream 3, which is ignored.
+        client.send_frame(frames::priority(1, 3, 15, true)).await;
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        client
+            .send_frame(
+                frames::headers(3)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        client.recv_frame(frames::headers(1).response(200)).await;
+        client.recv_frame(frames::headers(3).response(200)).await;
+        client.recv_frame(frames::data(1, vec![0; 8]).eos()).await;
+        client.recv_frame(frames::data(3, vec![0; 8]).eos()).await;
+    };
+
+    let srv = async move {
+        let mut srv = server::Builder::new()
+            .disable_rfc7540_priorities()
+            .handshake::<_, Bytes>(io)
+            .await
+            .expect("handshake");
+        let (_, mut stream1) = srv.next().await.unwrap().unwrap();
+        let (_, mut stream3) = srv.next().await.unwrap().unwrap();
+
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        let mut stream1 = stream1.send_response(rsp, false).unwrap();
+        stream1.send_data(vec![0; 8].into(), true).unwrap();
+
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        let mut stream3 = stream3.send_response(rsp, false).unwrap();
+        stream3.send_data(vec![0; 8].into(), true).unwrap();
+
+        assert!(srv.next().await.is_none());
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn client_set_priority_sends_priority_update() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv.recv_frame(frames::priority_update(1, "u=1, i")).await;
+        srv.send_frame(frames::headers(1).response(200).eos()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.unwrap();
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let (response, mut stream) = client.send_request(request, true).unwrap();
+        stream.set_priority(h2::ext::Priority::new(1, true));
+
+        let response = h2.drive(response).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        h2.await.unwrap();
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn client_headers_are_sent_in_stream_id_order() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv.recv_frame(
+            frames::headers(3)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv.recv_frame(frames::priority_update(3, "u=0")).await;
+        srv.send_frame(frames::headers(1).response(200).eos()).await;
+        srv.send_frame(frames::headers(3).response(200).eos()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.unwrap();
+        let request = || {
+            Request::builder()
+                .uri("https://example.com/")
+                .body(())
+                .unwrap()
+        };
+        let (response1, _) = client.send_request(request(), true).unwrap();
+        let (response3, mut stream3) = client.send_request(request(), true).unwrap();
+
+        // Stream 1 would be implicitly closed if stream 3 was opened first.
+        stream3.set_priority(h2::ext::Priority::new(0, false));
+
+        let response1 = h2.drive(response1).await.unwrap();
+        assert_eq!(response1.status(), StatusCode::OK);
+        let response3 = h2.drive(response3).await.unwrap();
+        assert_eq!(response3.status(), StatusCode::OK);
+        h2.await.unwrap();
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn client_set_priority_applies_after_headers() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(frames::headers(1).request("POST", "https://example.com/"))
+            .await;
+        srv.recv_frame(frames::headers(3).request("POST", "https://example.com/"))
+            .await;
+        srv.recv_frame(frames::priority_update(3, "u=0")).await;
+        srv.recv_frame(frames::data(3, "world").eos()).await;
+        srv.recv_frame(frames::data(1, "hello").eos()).await;
+        srv.send_frame(frames::headers(1).response(200).eos()).await;
+        srv.send_frame(frames::headers(3).response(200).eos()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.unwrap();
+        let request = || {
+            Request::builder()
+                .method(Method::POST)
+                .uri("https://example.com/")
+                .body(())
+                .unwrap()
+        };
+        let (response1, mut stream1) = client.send_request(request(), false).unwrap();
+        let (response3, mut stream3) = client.send_request(request(), false).unwrap();
+        stream1.send_data("hello".into(), true).unwrap();
+        stream3.send_data("world".into(), true).unwrap();
+
+        // Neither HEADERS frame has been written yet.
+        stream3.set_priority(h2::ext::Priority::new(0, false));
+
+        let response1 = h2.drive(response1).await.unwrap();
+        assert_eq!(response1.status(), StatusCode::OK);
+        let response3 = h2.drive(response3).await.unwrap();
+        assert_eq!(response3.status(), StatusCode::OK);
+        h2.await.unwrap();
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn client_recv_priority_update_is_connection_error() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.send_frame(frames::priority_update(1, "u=0")).await;
+        srv.recv_frame(frames::go_away(0).protocol_error()).await;
+    };
+
+    let h2 = async move {
+        let (_client, h2) = client::handshake(io).await.unwrap();
+        let err = h2.await.unwrap_err();
+        assert_eq!(err.reason(), Some(Reason::PROTOCOL_ERROR));
+    };
+
+    join(srv, h2).await;
+}
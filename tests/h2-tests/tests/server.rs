@@ -1,6 +1,6 @@
 #![deny(warnings)]
 
-use futures::future::{join, poll_fn};
+use futures::future::{join, poll_fn, select, Either};
 use futures::StreamExt;
 use h2_support::prelude::*;
 use tokio::io::AsyncWriteExt;
@@ -629,7 +629,7 @@ async fn abrupt_shutdown() {
 }
 
 #[tokio::test]
-async fn graceful_shutdown() {
+async fn abrupt_shutdown_with_debug_data() {
     h2_support::trace_init!();
     let (io, mut client) = mock::new();
 
@@ -637,335 +637,410 @@ async fn graceful_shutdown() {
         let settings = client.assert_server_handshake().await;
         assert_default_settings!(settings);
         client
-            .send_frame(
-                frames::headers(1)
-                    .request("GET", "https://example.com/")
-                    .eos(),
-            )
-            .await;
-        // 2^31 - 1 = 2147483647
-        // Note: not using a constant in the library because library devs
-        // can be unsmart.
-        client.recv_frame(frames::go_away(2147483647)).await;
-        client.recv_frame(frames::ping(frame::Ping::SHUTDOWN)).await;
-        client
-            .recv_frame(frames::headers(1).response(200).eos())
-            .await;
-        // Pretend this stream was sent while the GOAWAY was in flight
-        client
-            .send_frame(frames::headers(3).request("POST", "https://example.com/"))
-            .await;
-        client
-            .send_frame(frames::ping(frame::Ping::SHUTDOWN).pong())
-            .await;
-        client.recv_frame(frames::go_away(3)).await;
-        // streams sent after GOAWAY receive no response
-        client
-            .send_frame(frames::headers(7).request("GET", "https://example.com/"))
+            .send_frame(frames::headers(1).request("POST", "https://example.com/"))
             .await;
-        client.send_frame(frames::data(7, "").eos()).await;
-        client.send_frame(frames::data(3, "").eos()).await;
         client
-            .recv_frame(frames::headers(3).response(200).eos())
+            .recv_frame(
+                frames::go_away(1)
+                    .reason(Reason::ENHANCE_YOUR_CALM)
+                    .data("too many requests"),
+            )
             .await;
         client.recv_eof().await;
     };
 
     let srv = async move {
         let mut srv = server::handshake(io).await.expect("handshake");
-        let (req, mut stream) = srv.next().await.unwrap().unwrap();
-        assert_eq!(req.method(), &http::Method::GET);
+        let (_req, _tx) = srv.next().await.unwrap().expect("server receives request");
 
-        srv.graceful_shutdown();
+        srv.abrupt_shutdown_with_debug_data(
+            Reason::ENHANCE_YOUR_CALM,
+            Bytes::from_static(b"too many requests"),
+        );
 
-        let rsp = http::Response::builder().status(200).body(()).unwrap();
-        stream.send_response(rsp, true).unwrap();
+        poll_fn(move |cx| srv.poll_closed(cx))
+            .await
+            .expect("server");
+    };
 
-        let (req, mut stream) = srv.next().await.unwrap().unwrap();
-        assert_eq!(req.method(), &http::Method::POST);
-        let body = req.into_parts().1;
+    join(client, srv).await;
+}
 
-        let body = async move {
-            let buf = util::concat(body).await.unwrap();
-            assert!(buf.is_empty());
+#[tokio::test]
+async fn abrupt_shutdown_truncates_long_debug_data() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
 
-            let rsp = http::Response::builder().status(200).body(()).unwrap();
-            stream.send_response(rsp, true).unwrap();
-        };
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        client
+            .send_frame(frames::headers(1).request("POST", "https://example.com/"))
+            .await;
+        client
+            .recv_frame(
+                frames::go_away(1)
+                    .reason(Reason::ENHANCE_YOUR_CALM)
+                    .data(vec![b'a'; 16_384 - 8]),
+            )
+            .await;
+        client.recv_eof().await;
+    };
 
-        let mut srv = Box::pin(async move {
-            assert!(srv.next().await.is_none(), "unexpected request");
-        });
-        srv.drive(body).await;
-        srv.await;
+    let srv = async move {
+        let mut srv = server::handshake(io).await.expect("handshake");
+        let (_req, _tx) = srv.next().await.unwrap().expect("server receives request");
+
+        srv.abrupt_shutdown_with_debug_data(Reason::ENHANCE_YOUR_CALM, vec![b'a'; 20_000].into());
+
+        poll_fn(move |cx| srv.poll_closed(cx))
+            .await
+            .expect("server");
     };
 
     join(client, srv).await;
 }
 
 #[tokio::test]
-async fn goaway_even_if_client_sent_goaway() {
+async fn send_origin_frames() {
     h2_support::trace_init!();
     let (io, mut client) = mock::new();
 
+    use std::convert::TryInto;
+
+    let long_a = format!("https://{}.example.com", "a".repeat(10_000));
+    let long_b = format!("https://{}.example.com", "b".repeat(10_000));
+    let (long_a2, long_b2) = (long_a.clone(), long_b.clone());
+
     let client = async move {
         let settings = client.assert_server_handshake().await;
         assert_default_settings!(settings);
         client
             .send_frame(
-                frames::headers(5)
+                frames::headers(1)
                     .request("GET", "https://example.com/")
                     .eos(),
             )
             .await;
-        // Ping-pong so as to wait until server gets req
-        client.ping_pong([0; 8]).await;
-        client.send_frame(frames::go_away(0)).await;
-        // 2^31 - 1 = 2147483647
-        // Note: not using a constant in the library because library devs
-        // can be unsmart.
-        client.recv_frame(frames::go_away(2147483647)).await;
-        client.recv_frame(frames::ping(frame::Ping::SHUTDOWN)).await;
         client
-            .recv_frame(frames::headers(5).response(200).eos())
+            .recv_frame(frames::origin(&[
+                "https://example.com",
+                "https://example.net:8443",
+            ]))
             .await;
+        // Origins that do not fit in one frame are sent in another.
+        client.recv_frame(frames::origin(&[&long_a2])).await;
+        client.recv_frame(frames::origin(&[&long_b2])).await;
         client
-            .send_frame(frames::ping(frame::Ping::SHUTDOWN).pong())
+            .recv_frame(frames::headers(1).response(200).eos())
             .await;
-        client.recv_frame(frames::go_away(5)).await;
-        client.recv_eof().await;
     };
 
     let srv = async move {
         let mut srv = server::handshake(io).await.expect("handshake");
-        let (req, mut stream) = srv.next().await.unwrap().unwrap();
-        assert_eq!(req.method(), &http::Method::GET);
+        let (_req, mut stream) = srv.next().await.unwrap().unwrap();
 
-        srv.graceful_shutdown();
+        srv.send_origin(vec![
+            h2::ext::Origin::from_static("https://example.com"),
+            h2::ext::Origin::from_static("https://example.net:8443"),
+        ]);
+        srv.send_origin(vec![
+            long_a.as_str().try_into().unwrap(),
+            long_b.as_str().try_into().unwrap(),
+        ]);
 
         let rsp = http::Response::builder().status(200).body(()).unwrap();
         stream.send_response(rsp, true).unwrap();
 
-        assert!(srv.next().await.is_none(), "unexpected request");
+        assert!(srv.next().await.is_none());
     };
 
     join(client, srv).await;
 }
 
 #[tokio::test]
-async fn sends_reset_cancel_when_res_body_is_dropped() {
+async fn too_many_pending_accept_reset_streams_is_calm() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        for id in (1..=5).step_by(2) {
+            client
+                .send_frame(
+                    frames::headers(id)
+                        .request("GET", "https://example.com/")
+                        .eos(),
+                )
+                .await;
+            client.send_frame(frames::reset(id).cancel()).await;
+        }
+        client.recv_frame(frames::go_away(5).calm()).await;
+    };
+
+    let srv = async move {
+        let mut srv = server::Builder::new()
+            .max_pending_accept_reset_streams(2)
+            .handshake::<_, Bytes>(io)
+            .await
+            .expect("handshake");
+        let err = srv.accept().await.unwrap().expect_err("accept");
+        assert_eq!(err.reason(), Some(Reason::ENHANCE_YOUR_CALM));
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn accepting_reset_streams_makes_room() {
     h2_support::trace_init!();
     let (io, mut client) = mock::new();
 
     let client = async move {
         let settings = client.assert_server_handshake().await;
         assert_default_settings!(settings);
+        for id in (1..=9).step_by(2) {
+            client
+                .send_frame(
+                    frames::headers(id)
+                        .request("GET", "https://example.com/")
+                        .eos(),
+                )
+                .await;
+            client.send_frame(frames::reset(id).cancel()).await;
+            // Let the server accept the stream.
+            idle_ms(10).await;
+        }
         client
             .send_frame(
-                frames::headers(1)
+                frames::headers(11)
                     .request("GET", "https://example.com/")
                     .eos(),
             )
             .await;
-        client.recv_frame(frames::headers(1).response(200)).await;
-        client.recv_frame(frames::reset(1).cancel()).await;
         client
-            .send_frame(
-                frames::headers(3)
-                    .request("GET", "https://example.com/")
-                    .eos(),
-            )
+            .recv_frame(frames::headers(11).response(200).eos())
             .await;
-        client.recv_frame(frames::headers(3).response(200)).await;
-        client.recv_frame(frames::data(3, vec![0; 10])).await;
-        client.recv_frame(frames::reset(3).cancel()).await;
     };
 
     let srv = async move {
-        let mut srv = server::handshake(io).await.expect("handshake");
-        {
-            let (req, mut stream) = srv.next().await.unwrap().unwrap();
-
-            assert_eq!(req.method(), &http::Method::GET);
+        let mut srv = server::Builder::new()
+            .max_pending_accept_reset_streams(1)
+            .handshake::<_, Bytes>(io)
+            .await
+            .expect("handshake");
 
+        for _ in 0..5 {
+            let (_req, mut stream) = srv.accept().await.unwrap().unwrap();
             let rsp = http::Response::builder().status(200).body(()).unwrap();
-            stream.send_response(rsp, false).unwrap();
-            // SendStream dropped
-        }
-        {
-            let (_req, mut stream) = srv.next().await.unwrap().unwrap();
-            let rsp = http::Response::builder().status(200).body(()).unwrap();
-            let mut tx = stream.send_response(rsp, false).unwrap();
-            tx.send_data(vec![0; 10].into(), false).unwrap();
-            // no send_data with eos
+            // The stream was reset by the client.
+            assert!(stream.send_response(rsp, true).is_err());
         }
 
-        assert!(srv.next().await.is_none());
+        let (_req, mut stream) = srv.accept().await.unwrap().unwrap();
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        stream.send_response(rsp, true).unwrap();
+
+        assert!(srv.accept().await.is_none());
     };
 
     join(client, srv).await;
 }
 
 #[tokio::test]
-async fn too_big_headers_sends_431() {
+async fn reset_of_closed_pending_accept_stream_is_not_counted() {
     h2_support::trace_init!();
     let (io, mut client) = mock::new();
 
     let client = async move {
         let settings = client.assert_server_handshake().await;
-        assert_frame_eq(settings, frames::settings().max_header_list_size(10));
+        assert_default_settings!(settings);
         client
             .send_frame(
                 frames::headers(1)
                     .request("GET", "https://example.com/")
-                    .field("some-header", "some-value")
                     .eos(),
             )
             .await;
+        // A stream can't depend on itself, so the server resets it before it
+        // is accepted.
+        client.send_frame(frames::priority(1, 1, 16, false)).await;
+        client.recv_frame(frames::reset(1).protocol_error()).await;
+        // The stream is already closed, so this doesn't change it.
+        client.send_frame(frames::reset(1).cancel()).await;
+
         client
-            .recv_frame(frames::headers(1).response(431).eos())
+            .send_frame(
+                frames::headers(3)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
             .await;
-        idle_ms(10).await;
+        client.send_frame(frames::reset(3).cancel()).await;
+        idle_ms(100).await;
     };
 
     let srv = async move {
         let mut srv = server::Builder::new()
-            .max_header_list_size(10)
+            .max_pending_accept_reset_streams(1)
             .handshake::<_, Bytes>(io)
             .await
             .expect("handshake");
 
-        let req = srv.next().await;
-        assert!(req.is_none(), "req is {:?}", req);
+        // Process the client's frames without accepting the streams.
+        let closed = poll_fn(|cx| srv.poll_closed(cx));
+        match select(Box::pin(closed), Box::pin(idle_ms(50))).await {
+            Either::Left((res, _)) => panic!("connection closed: {:?}", res),
+            Either::Right(_) => {}
+        }
+
+        // Both streams can still be accepted.
+        let mut accepted = 0;
+        while let Some(res) = srv.accept().await {
+            res.expect("accept");
+            accepted += 1;
+        }
+        assert_eq!(accepted, 2);
     };
 
     join(client, srv).await;
 }
 
 #[tokio::test]
-async fn too_big_headers_sends_reset_after_431_if_not_eos() {
+async fn recv_headers_after_shrinking_header_table() {
     h2_support::trace_init!();
     let (io, mut client) = mock::new();
 
     let client = async move {
         let settings = client.assert_server_handshake().await;
-        assert_frame_eq(settings, frames::settings().max_header_list_size(10));
+        assert_frame_eq(settings, frames::settings().header_table_size(0));
         client
-            .send_frame(
-                frames::headers(1)
-                    .request("GET", "https://example.com/")
-                    .field("some-header", "some-value"),
-            )
+            .send_bytes(&[
+                0, 0, 17, // len
+                1,  // type: HEADERS
+                5,  // flags: END_STREAM | END_HEADERS
+                0, 0, 0, 1,    // stream id
+                0x20, // dynamic table size update: 0
+                0x82, // :method = GET
+                0x87, // :scheme = https
+                0x84, // :path = /
+                0x41, 11, // :authority, with incremental indexing
+                b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'c', b'o', b'm',
+            ])
             .await;
         client
-            .recv_frame(frames::headers(1).response(431).eos())
+            .recv_frame(frames::headers(1).response(200).eos())
             .await;
-        client.recv_frame(frames::reset(1).refused()).await;
     };
 
     let srv = async move {
         let mut srv = server::Builder::new()
-            .max_header_list_size(10)
+            .header_table_size(0)
             .handshake::<_, Bytes>(io)
             .await
             .expect("handshake");
-
-        let req = srv.next().await;
-        assert!(req.is_none(), "req is {:?}", req);
+        let (req, mut stream) = srv.next().await.unwrap().unwrap();
+        assert_eq!(req.uri(), "https://example.com/");
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        stream.send_response(rsp, true).unwrap();
+        assert_eq!(srv.stats().recv_header_table_size(), 0);
+        assert!(srv.next().await.is_none());
     };
 
     join(client, srv).await;
 }
 
 #[tokio::test]
-async fn poll_reset() {
+async fn missing_header_table_size_update_is_error() {
     h2_support::trace_init!();
     let (io, mut client) = mock::new();
 
     let client = async move {
         let settings = client.assert_server_handshake().await;
-        assert_default_settings!(settings);
+        assert_frame_eq(settings, frames::settings().header_table_size(0));
         client
-            .send_frame(
-                frames::headers(1)
-                    .request("GET", "https://example.com/")
-                    .eos(),
-            )
+            .send_bytes(&[
+                0, 0, 3, // len
+                1, // type: HEADERS
+                5, // flags: END_STREAM | END_HEADERS
+                0, 0, 0, 1,    // stream id
+                0x82, // :method = GET
+                0x87, // :scheme = https
+                0x84, // :path = /
+            ])
             .await;
-        idle_ms(10).await;
-        client.send_frame(frames::reset(1).cancel()).await;
+        client.recv_frame(frames::go_away(0).protocol_error()).await;
     };
 
     let srv = async move {
         let mut srv = server::Builder::new()
+            .header_table_size(0)
             .handshake::<_, Bytes>(io)
             .await
             .expect("handshake");
-        let (_req, mut tx) = srv.next().await.expect("server").unwrap();
-        let conn = async move {
-            let req = srv.next().await;
-            assert!(req.is_none(), "no second request");
-        };
-        join(conn, async move {
-            let reason = poll_fn(move |cx| tx.poll_reset(cx))
-                .await
-                .expect("poll_reset");
-            assert_eq!(reason, Reason::CANCEL);
-        })
-        .await;
+        let err = srv.next().await.unwrap().expect_err("accept");
+        assert_eq!(err.reason(), Some(Reason::PROTOCOL_ERROR));
     };
+
     join(client, srv).await;
 }
 
 #[tokio::test]
-async fn poll_reset_io_error() {
+async fn too_many_continuation_frames_is_calm() {
     h2_support::trace_init!();
     let (io, mut client) = mock::new();
 
     let client = async move {
         let settings = client.assert_server_handshake().await;
         assert_default_settings!(settings);
-
         client
-            .send_frame(
-                frames::headers(1)
-                    .request("GET", "https://example.com/")
-                    .eos(),
-            )
+            .send_bytes(&[
+                0, 0, 1, // len
+                1, // type: HEADERS
+                1, // flags: END_STREAM, no END_HEADERS
+                0, 0, 0, 1,    // stream id
+                0x82, // :method = GET
+                0, 0, 1, // len
+                9, // type: CONTINUATION
+                0, // flags
+                0, 0, 0, 1,    // stream id
+                0x87, // :scheme = https
+                0, 0, 1, // len
+                9, // type: CONTINUATION
+                4, // flags: END_HEADERS
+                0, 0, 0, 1,    // stream id
+                0x84, // :path = /
+            ])
             .await;
-        idle_ms(10).await;
+        client.recv_frame(frames::go_away(0).calm()).await;
     };
 
     let srv = async move {
         let mut srv = server::Builder::new()
+            .max_continuation_frames(1)
             .handshake::<_, Bytes>(io)
             .await
             .expect("handshake");
-
-        let (_req, mut tx) = srv.next().await.expect("server").unwrap();
-        let conn = async move {
-            let req = srv.next().await;
-            assert!(req.is_none(), "no second request");
-        };
-        join(conn, async move {
-            poll_fn(move |cx| tx.poll_reset(cx))
-                .await
-                .expect_err("poll_reset should error")
-        })
-        .await;
+        let err = srv.accept().await.unwrap().expect_err("accept");
+        assert_eq!(err.reason(), Some(Reason::ENHANCE_YOUR_CALM));
     };
 
     join(client, srv).await;
 }
 
 #[tokio::test]
-async fn poll_reset_after_send_response_is_user_error() {
+async fn send_alt_svc_frames() {
     h2_support::trace_init!();
     let (io, mut client) = mock::new();
 
     let client = async move {
         let settings = client.assert_server_handshake().await;
         assert_default_settings!(settings);
+        // Servers ignore ALTSVC frames.
+        client
+            .send_frame(frames::alt_svc(0, "https://example.com", "h3=\":443\""))
+            .await;
         client
             .send_frame(
                 frames::headers(1)
@@ -973,26 +1048,433 @@ async fn poll_reset_after_send_response_is_user_error() {
                     .eos(),
             )
             .await;
-        client.recv_frame(frames::headers(1).response(200)).await;
         client
-            .recv_frame(
-                // After the error, our server will drop the handles,
-                // meaning we receive a RST_STREAM here.
-                frames::reset(1).cancel(),
-            )
+            .recv_frame(frames::alt_svc(
+                0,
+                "https://example.net",
+                "h3=\":8443\"; ma=3600",
+            ))
+            .await;
+        client
+            .recv_frame(frames::alt_svc(1, "", "h3=\":443\""))
+            .await;
+        client
+            .recv_frame(frames::headers(1).response(200).eos())
             .await;
-        idle_ms(10).await;
     };
 
     let srv = async move {
-        let mut srv = server::Builder::new()
-            .handshake::<_, Bytes>(io)
-            .await
-            .expect("handshake");
+        let mut srv = server::handshake(io).await.expect("handshake");
+        let (_req, mut stream) = srv.next().await.unwrap().unwrap();
 
-        let (_req, mut tx) = srv.next().await.expect("server").expect("request");
-        let conn = async move {
-            let req = srv.next().await;
+        srv.send_alt_svc(
+            h2::ext::Origin::from_static("https://example.net"),
+            http::HeaderValue::from_static("h3=\":8443\"; ma=3600"),
+        )
+        .unwrap();
+
+        let too_big = http::HeaderValue::from_str(&"a".repeat(16_383)).unwrap();
+        let err = stream.send_alt_svc(too_big).unwrap_err();
+        assert_eq!(err.to_string(), "user error: payload too big");
+
+        stream
+            .send_alt_svc(http::HeaderValue::from_static("h3=\":443\""))
+            .unwrap();
+
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        stream.send_response(rsp, true).unwrap();
+
+        // The response has ended.
+        let err = stream
+            .send_alt_svc(http::HeaderValue::from_static("h3=\":443\""))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "user error: unexpected frame type");
+
+        assert!(srv.next().await.is_none());
+    };
+
+    join(client, srv).await;
+}
+
+#[test]
+fn invalid_origins() {
+    use h2::ext::Origin;
+    use std::convert::TryFrom;
+
+    assert!(Origin::try_from("https://example.com").is_ok());
+    assert!(Origin::try_from("http://127.0.0.1:8080").is_ok());
+    assert!(Origin::try_from("example.com").is_err());
+    assert!(Origin::try_from("https://example.com/").is_err());
+    assert!(Origin::try_from("https://example.com/path").is_err());
+    assert!(Origin::try_from("https://user@example.com").is_err());
+    assert!(Origin::try_from("https://example.com?query").is_err());
+}
+
+#[tokio::test]
+async fn graceful_shutdown() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        // 2^31 - 1 = 2147483647
+        // Note: not using a constant in the library because library devs
+        // can be unsmart.
+        client.recv_frame(frames::go_away(2147483647)).await;
+        client.recv_frame(frames::ping(frame::Ping::SHUTDOWN)).await;
+        client
+            .recv_frame(frames::headers(1).response(200).eos())
+            .await;
+        // Pretend this stream was sent while the GOAWAY was in flight
+        client
+            .send_frame(frames::headers(3).request("POST", "https://example.com/"))
+            .await;
+        client
+            .send_frame(frames::ping(frame::Ping::SHUTDOWN).pong())
+            .await;
+        client.recv_frame(frames::go_away(3)).await;
+        // streams sent after GOAWAY receive no response
+        client
+            .send_frame(frames::headers(7).request("GET", "https://example.com/"))
+            .await;
+        client.send_frame(frames::data(7, "").eos()).await;
+        client.send_frame(frames::data(3, "").eos()).await;
+        client
+            .recv_frame(frames::headers(3).response(200).eos())
+            .await;
+        client.recv_eof().await;
+    };
+
+    let srv = async move {
+        let mut srv = server::handshake(io).await.expect("handshake");
+        let (req, mut stream) = srv.next().await.unwrap().unwrap();
+        assert_eq!(req.method(), &http::Method::GET);
+
+        srv.graceful_shutdown();
+
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        stream.send_response(rsp, true).unwrap();
+
+        let (req, mut stream) = srv.next().await.unwrap().unwrap();
+        assert_eq!(req.method(), &http::Method::POST);
+        let body = req.into_parts().1;
+
+        let body = async move {
+            let buf = util::concat(body).await.unwrap();
+            assert!(buf.is_empty());
+
+            let rsp = http::Response::builder().status(200).body(()).unwrap();
+            stream.send_response(rsp, true).unwrap();
+        };
+
+        let mut srv = Box::pin(async move {
+            assert!(srv.next().await.is_none(), "unexpected request");
+        });
+        srv.drive(body).await;
+        srv.await;
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn goaway_even_if_client_sent_goaway() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        client
+            .send_frame(
+                frames::headers(5)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        // Ping-pong so as to wait until server gets req
+        client.ping_pong([0; 8]).await;
+        client.send_frame(frames::go_away(0)).await;
+        // 2^31 - 1 = 2147483647
+        // Note: not using a constant in the library because library devs
+        // can be unsmart.
+        client.recv_frame(frames::go_away(2147483647)).await;
+        client.recv_frame(frames::ping(frame::Ping::SHUTDOWN)).await;
+        client
+            .recv_frame(frames::headers(5).response(200).eos())
+            .await;
+        client
+            .send_frame(frames::ping(frame::Ping::SHUTDOWN).pong())
+            .await;
+        client.recv_frame(frames::go_away(5)).await;
+        client.recv_eof().await;
+    };
+
+    let srv = async move {
+        let mut srv = server::handshake(io).await.expect("handshake");
+        let (req, mut stream) = srv.next().await.unwrap().unwrap();
+        assert_eq!(req.method(), &http::Method::GET);
+
+        srv.graceful_shutdown();
+
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        stream.send_response(rsp, true).unwrap();
+
+        assert!(srv.next().await.is_none(), "unexpected request");
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn sends_reset_cancel_when_res_body_is_dropped() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        client.recv_frame(frames::headers(1).response(200)).await;
+        client.recv_frame(frames::reset(1).cancel()).await;
+        client
+            .send_frame(
+                frames::headers(3)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        client.recv_frame(frames::headers(3).response(200)).await;
+        client.recv_frame(frames::data(3, vec![0; 10])).await;
+        client.recv_frame(frames::reset(3).cancel()).await;
+    };
+
+    let srv = async move {
+        let mut srv = server::handshake(io).await.expect("handshake");
+        {
+            let (req, mut stream) = srv.next().await.unwrap().unwrap();
+
+            assert_eq!(req.method(), &http::Method::GET);
+
+            let rsp = http::Response::builder().status(200).body(()).unwrap();
+            stream.send_response(rsp, false).unwrap();
+            // SendStream dropped
+        }
+        {
+            let (_req, mut stream) = srv.next().await.unwrap().unwrap();
+            let rsp = http::Response::builder().status(200).body(()).unwrap();
+            let mut tx = stream.send_response(rsp, false).unwrap();
+            tx.send_data(vec![0; 10].into(), false).unwrap();
+            // no send_data with eos
+        }
+
+        assert!(srv.next().await.is_none());
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn too_big_headers_sends_431() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_frame_eq(settings, frames::settings().max_header_list_size(10));
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("GET", "https://example.com/")
+                    .field("some-header", "some-value")
+                    .eos(),
+            )
+            .await;
+        client
+            .recv_frame(frames::headers(1).response(431).eos())
+            .await;
+        idle_ms(10).await;
+    };
+
+    let srv = async move {
+        let mut srv = server::Builder::new()
+            .max_header_list_size(10)
+            .handshake::<_, Bytes>(io)
+            .await
+            .expect("handshake");
+
+        let req = srv.next().await;
+        assert!(req.is_none(), "req is {:?}", req);
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn too_big_headers_sends_reset_after_431_if_not_eos() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_frame_eq(settings, frames::settings().max_header_list_size(10));
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("GET", "https://example.com/")
+                    .field("some-header", "some-value"),
+            )
+            .await;
+        client
+            .recv_frame(frames::headers(1).response(431).eos())
+            .await;
+        client.recv_frame(frames::reset(1).refused()).await;
+    };
+
+    let srv = async move {
+        let mut srv = server::Builder::new()
+            .max_header_list_size(10)
+            .handshake::<_, Bytes>(io)
+            .await
+            .expect("handshake");
+
+        let req = srv.next().await;
+        assert!(req.is_none(), "req is {:?}", req);
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn poll_reset() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        idle_ms(10).await;
+        client.send_frame(frames::reset(1).cancel()).await;
+    };
+
+    let srv = async move {
+        let mut srv = server::Builder::new()
+            .handshake::<_, Bytes>(io)
+            .await
+            .expect("handshake");
+        let (_req, mut tx) = srv.next().await.expect("server").unwrap();
+        let conn = async move {
+            let req = srv.next().await;
+            assert!(req.is_none(), "no second request");
+        };
+        join(conn, async move {
+            let reason = poll_fn(move |cx| tx.poll_reset(cx))
+                .await
+                .expect("poll_reset");
+            assert_eq!(reason, Reason::CANCEL);
+        })
+        .await;
+    };
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn poll_reset_io_error() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        idle_ms(10).await;
+    };
+
+    let srv = async move {
+        let mut srv = server::Builder::new()
+            .handshake::<_, Bytes>(io)
+            .await
+            .expect("handshake");
+
+        let (_req, mut tx) = srv.next().await.expect("server").unwrap();
+        let conn = async move {
+            let req = srv.next().await;
+            assert!(req.is_none(), "no second request");
+        };
+        join(conn, async move {
+            poll_fn(move |cx| tx.poll_reset(cx))
+                .await
+                .expect_err("poll_reset should error")
+        })
+        .await;
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn poll_reset_after_send_response_is_user_error() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        client.recv_frame(frames::headers(1).response(200)).await;
+        client
+            .recv_frame(
+                // After the error, our server will drop the handles,
+                // meaning we receive a RST_STREAM here.
+                frames::reset(1).cancel(),
+            )
+            .await;
+        idle_ms(10).await;
+    };
+
+    let srv = async move {
+        let mut srv = server::Builder::new()
+            .handshake::<_, Bytes>(io)
+            .await
+            .expect("handshake");
+
+        let (_req, mut tx) = srv.next().await.expect("server").expect("request");
+        let conn = async move {
+            let req = srv.next().await;
             assert!(req.is_none(), "no second request");
         };
         tx.send_response(Response::new(()), false)
@@ -1013,45 +1495,379 @@ async fn poll_reset_after_send_response_is_user_error() {
 }
 
 #[tokio::test]
-async fn server_error_on_unclean_shutdown() {
+async fn server_error_on_unclean_shutdown() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let srv = server::Builder::new().handshake::<_, Bytes>(io);
+
+    client.write_all(b"PRI *").await.expect("write");
+    drop(client);
+
+    srv.await.expect_err("should error");
+}
+
+#[tokio::test]
+async fn request_without_authority() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("GET", "/just-a-path")
+                    .scheme("http")
+                    .eos(),
+            )
+            .await;
+        client
+            .recv_frame(frames::headers(1).response(200).eos())
+            .await;
+    };
+
+    let srv = async move {
+        let mut srv = server::handshake(io).await.expect("handshake");
+        let (req, mut stream) = srv.next().await.unwrap().unwrap();
+        assert_eq!(req.uri().path(), "/just-a-path");
+
+        let rsp = Response::new(());
+        stream.send_response(rsp, true).unwrap();
+
+        assert!(srv.next().await.is_none());
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn extended_connect_protocol_enabled() {
     h2_support::trace_init!();
     let (io, mut client) = mock::new();
 
-    let srv = server::Builder::new().handshake::<_, Bytes>(io);
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_eq!(settings.is_extended_connect_protocol_enabled(), Some(true));
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("CONNECT", "http://bread/baguette")
+                    .protocol("the-bread-protocol"),
+            )
+            .await;
+        client.recv_frame(frames::headers(1).response(200)).await;
+    };
 
-    client.write_all(b"PRI *").await.expect("write");
-    drop(client);
+    let srv = async move {
+        let mut builder = server::Builder::new();
+        builder.enable_connect_protocol();
+        let mut srv = builder.handshake::<_, Bytes>(io).await.expect("handshake");
 
-    srv.await.expect_err("should error");
+        let (req, mut stream) = srv.next().await.unwrap().unwrap();
+        assert_eq!(req.method(), Method::CONNECT);
+        assert_eq!(req.uri().scheme_str(), Some("http"));
+        assert_eq!(req.uri().path(), "/baguette");
+        assert_eq!(
+            req.extensions().get::<ext::Protocol>(),
+            Some(&ext::Protocol::from_static("the-bread-protocol"))
+        );
+
+        let rsp = Response::new(());
+        stream.send_response(rsp, false).unwrap();
+
+        poll_fn(move |cx| srv.poll_closed(cx))
+            .await
+            .expect("server");
+    };
+
+    join(client, srv).await;
 }
 
 #[tokio::test]
-async fn request_without_authority() {
+async fn extended_connect_protocol_disabled() {
     h2_support::trace_init!();
     let (io, mut client) = mock::new();
 
     let client = async move {
         let settings = client.assert_server_handshake().await;
-        assert_default_settings!(settings);
+        assert_eq!(settings.is_extended_connect_protocol_enabled(), None);
         client
             .send_frame(
                 frames::headers(1)
-                    .request("GET", "/just-a-path")
+                    .request("CONNECT", "http://bread/baguette")
+                    .protocol("the-bread-protocol"),
+            )
+            .await;
+        client.recv_frame(frames::reset(1).protocol_error()).await;
+    };
+
+    let srv = async move {
+        let mut srv = server::handshake(io).await.expect("handshake");
+
+        assert!(srv.next().await.is_none());
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn extended_connect_protocol_requires_path() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_eq!(settings.is_extended_connect_protocol_enabled(), Some(true));
+        client
+            .send_frame(
+                frames::headers(1)
+                    .method("CONNECT")
                     .scheme("http")
+                    .protocol("the-bread-protocol"),
+            )
+            .await;
+        client.recv_frame(frames::reset(1).protocol_error()).await;
+    };
+
+    let srv = async move {
+        let mut builder = server::Builder::new();
+        builder.enable_connect_protocol();
+        let mut srv = builder.handshake::<_, Bytes>(io).await.expect("handshake");
+
+        assert!(srv.next().await.is_none());
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn idle_timeout_sends_go_away() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("GET", "https://example.com/")
                     .eos(),
             )
             .await;
         client
             .recv_frame(frames::headers(1).response(200).eos())
             .await;
+        client.recv_frame(frames::go_away(1)).await;
+        client.recv_eof().await;
+    };
+
+    let srv = async move {
+        let mut srv = server::Builder::new()
+            .idle_timeout(Duration::from_millis(50))
+            .handshake::<_, Bytes>(io)
+            .await
+            .expect("handshake");
+        let (_req, mut stream) = srv.next().await.unwrap().unwrap();
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        stream.send_response(rsp, true).unwrap();
+        assert!(srv.next().await.is_none());
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn graceful_shutdown_with_timeout_cancels_streams() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        client.recv_frame(frames::go_away(StreamId::MAX)).await;
+        client.recv_frame(frames::ping(frame::Ping::SHUTDOWN)).await;
+        // Never acknowledge the PING, so the deadline has to send the final
+        // GOAWAY.
+        client.recv_frame(frames::go_away(1)).await;
+        client.recv_frame(frames::reset(1).cancel()).await;
+        client.recv_eof().await;
+    };
+
+    let srv = async move {
+        let mut srv = server::handshake(io).await.expect("handshake");
+        let (_req, mut stream) = srv.next().await.unwrap().unwrap();
+
+        srv.graceful_shutdown_with_timeout(Duration::from_millis(50));
+        assert!(srv.next().await.is_none());
+
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        assert!(stream.send_response(rsp, true).is_err());
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn graceful_shutdown_with_timeout_closes_when_client_stops_reading() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        client.stop_reading();
+        // Keep the handle, so writes stay blocked until the server is done.
+        client
     };
 
     let srv = async move {
         let mut srv = server::handshake(io).await.expect("handshake");
+        let (_req, mut stream) = srv.next().await.unwrap().unwrap();
+
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        let mut stream = stream.send_response(rsp, false).unwrap();
+        stream.send_data(vec![0; 1024].into(), false).unwrap();
+
+        srv.graceful_shutdown_with_timeout(Duration::from_millis(50));
+        assert!(srv.next().await.is_none());
+    };
+
+    let (_client, ()) = join(client, srv).await;
+}
+
+#[tokio::test]
+async fn serve_upgrade_request() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        client.recv_frame(frames::headers(1).response(200)).await;
+        // HTTP2-Settings set the stream window to 5 bytes.
+        client.recv_frame(frames::data(1, &b"hello"[..])).await;
+        client.send_frame(frames::window_update(1, 6)).await;
+        client
+            .recv_frame(frames::data(1, &b" world"[..]).eos())
+            .await;
+    };
+
+    let srv = async move {
+        let request = Request::builder()
+            .uri("/")
+            .header("host", "example.com")
+            .header("connection", "Upgrade, HTTP2-Settings")
+            .header("upgrade", "h2c")
+            .header("http2-settings", "AAQAAAAF")
+            .body(())
+            .unwrap();
+
+        let mut srv = server::Builder::new()
+            .handshake_upgrade::<_, Bytes>(io, request)
+            .await
+            .expect("handshake");
         let (req, mut stream) = srv.next().await.unwrap().unwrap();
-        assert_eq!(req.uri().path(), "/just-a-path");
 
-        let rsp = Response::new(());
+        assert_eq!(req.method(), &http::Method::GET);
+        assert_eq!(req.uri(), "http://example.com/");
+        assert!(req.headers().is_empty());
+        assert!(req.body().is_end_stream());
+
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        let mut body = stream.send_response(rsp, false).unwrap();
+        body.send_data("hello world".into(), true).unwrap();
+
+        assert!(srv.next().await.is_none());
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn upgrade_request_with_invalid_http2_settings() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        client.write_preface().await;
+    };
+
+    let srv = async move {
+        let request = Request::builder()
+            .uri("/")
+            .header("host", "example.com")
+            .header("upgrade", "h2c")
+            .header("http2-settings", "not base64!")
+            .body(())
+            .unwrap();
+
+        let err = server::Builder::new()
+            .handshake_upgrade::<_, Bytes>(io, request)
+            .await
+            .unwrap_err();
+        assert_eq!(err.reason(), Some(Reason::PROTOCOL_ERROR));
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn handshake_with_prefix_including_settings() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        // The preface and SETTINGS frame were already read by the server, so
+        // nothing is sent until the server acknowledges them.
+        let settings = client.next().await.unwrap().unwrap();
+        assert!(matches!(settings, frame::Frame::Settings(ref s) if !s.is_ack()));
+        client.send_frame(frames::settings_ack()).await;
+        client.recv_frame(frames::settings_ack()).await;
+
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        client
+            .recv_frame(frames::headers(1).response(200).eos())
+            .await;
+    };
+
+    let srv = async move {
+        let mut prefix = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n".to_vec();
+        // An empty SETTINGS frame.
+        prefix.extend_from_slice(&[0, 0, 0, 4, 0, 0, 0, 0, 0]);
+        assert!(server::starts_with_preface(&prefix));
+
+        let mut srv = server::Builder::new()
+            .handshake_with_prefix::<_, Bytes>(io, prefix.into())
+            .await
+            .expect("handshake");
+        let (req, mut stream) = srv.next().await.unwrap().unwrap();
+
+        assert_eq!(req.method(), &http::Method::GET);
+
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
         stream.send_response(rsp, true).unwrap();
 
         assert!(srv.next().await.is_none());
@@ -1059,3 +1875,55 @@ async fn request_without_authority() {
 
     join(client, srv).await;
 }
+
+#[tokio::test]
+async fn handshake_with_partial_prefix() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let preface = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+    let client = async move {
+        client.send_bytes(&preface[10..]).await;
+        client.send_frame(frames::settings()).await;
+        client.recv_frame(frames::settings()).await;
+        client.recv_frame(frames::settings_ack()).await;
+        client.send_frame(frames::settings_ack()).await;
+    };
+
+    let srv = async move {
+        assert!(!server::starts_with_preface(&preface[..10]));
+
+        let mut srv = server::Builder::new()
+            .handshake_with_prefix::<_, Bytes>(io, Bytes::from_static(&preface[..10]))
+            .await
+            .expect("handshake");
+
+        assert!(srv.next().await.is_none());
+    };
+
+    join(client, srv).await;
+}
+
+#[tokio::test]
+async fn handshake_with_invalid_prefix() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        client.recv_frame(frames::settings()).await;
+    };
+
+    let srv = async move {
+        let prefix = Bytes::from_static(b"GET / HTTP/1.1\r\n");
+        assert!(!server::starts_with_preface(&prefix));
+
+        let err = server::Builder::new()
+            .handshake_with_prefix::<_, Bytes>(io, prefix)
+            .await
+            .unwrap_err();
+        assert_eq!(err.reason(), Some(Reason::PROTOCOL_ERROR));
+    };
+
+    join(client, srv).await;
+}
@@ -0,0 +1,146 @@
+use futures::future::join;
+use futures::StreamExt;
+use h2_support::prelude::*;
+
+#[tokio::test]
+async fn client_connection_stats() {
+    h2_support::trace_init!();
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        srv.recv_frame(
+            frames::headers(1)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv.send_frame(frames::headers(1).response(200)).await;
+        srv.send_frame(frames::data(1, &b"hello"[..]).eos()).await;
+        srv.recv_frame(
+            frames::headers(3)
+                .request("GET", "https://example.com/")
+                .eos(),
+        )
+        .await;
+        srv.send_frame(frames::reset(3).refused()).await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.unwrap();
+
+        let stats = h2.stats();
+        assert_eq!(stats.frames_received().total(), 0);
+        assert_eq!(stats.local_streams_opened(), 0);
+        assert_eq!(stats.send_window(), 65_535);
+        assert_eq!(stats.recv_window(), 65_535);
+
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let (response, _) = client.send_request(request, true).unwrap();
+        let response = h2.drive(response).await.unwrap();
+        let mut body = response.into_body();
+        let chunk = h2.drive(body.data()).await.unwrap().unwrap();
+        assert_eq!(chunk, "hello");
+
+        let request = Request::builder()
+            .uri("https://example.com/")
+            .body(())
+            .unwrap();
+        let (response, _) = client.send_request(request, true).unwrap();
+        let err = h2.drive(response).await.unwrap_err();
+        assert_eq!(err.reason(), Some(Reason::REFUSED_STREAM));
+
+        let stats = h2.stats();
+        // SETTINGS and its acknowledgement in each direction.
+        assert_eq!(stats.frames_sent().settings(), 2);
+        assert_eq!(stats.frames_sent().headers(), 2);
+        assert_eq!(stats.frames_sent().total(), 4);
+        assert_eq!(stats.frames_received().settings(), 2);
+        assert_eq!(stats.frames_received().headers(), 1);
+        assert_eq!(stats.frames_received().data(), 1);
+        assert_eq!(stats.frames_received().reset(), 1);
+        assert_eq!(stats.frames_received().total(), 5);
+        assert!(stats.bytes_sent() > 0);
+        // Every frame has a 9 byte header.
+        assert!(stats.bytes_received() >= 5 * 9 + 5);
+        assert_eq!(stats.frame_bytes_received().data(), 9 + 5);
+        assert_eq!(stats.frame_bytes_received().reset(), 9 + 4);
+        assert_eq!(stats.frame_bytes_received().total(), stats.bytes_received());
+        assert_eq!(stats.frame_bytes_sent().total(), stats.bytes_sent());
+
+        assert_eq!(stats.local_streams_opened(), 2);
+        assert_eq!(stats.local_streams_closed(), 2);
+        assert_eq!(stats.remote_streams_opened(), 0);
+        assert_eq!(stats.streams_reset_locally(), 0);
+        assert_eq!(stats.streams_reset_remotely(), 1);
+
+        // The received data has not been released.
+        assert_eq!(stats.recv_window(), 65_535 - 5);
+        assert_eq!(stats.send_window(), 65_535);
+        // `:authority` and friends are added to the encoder's table.
+        assert!(stats.send_header_table_size() > 0);
+
+        drop(client);
+        h2.await.unwrap();
+    };
+
+    join(srv, h2).await;
+}
+
+#[tokio::test]
+async fn server_connection_stats() {
+    h2_support::trace_init!();
+    let (io, mut client) = mock::new();
+
+    let client = async move {
+        let settings = client.assert_server_handshake().await;
+        assert_default_settings!(settings);
+        client
+            .send_frame(
+                frames::headers(1)
+                    .request("POST", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        client.recv_frame(frames::reset(1).cancel()).await;
+        client
+            .send_frame(
+                frames::headers(3)
+                    .request("GET", "https://example.com/")
+                    .eos(),
+            )
+            .await;
+        client
+            .recv_frame(frames::headers(3).response(200).eos())
+            .await;
+    };
+
+    let srv = async move {
+        let mut srv = server::handshake(io).await.expect("handshake");
+
+        let (_req, mut stream) = srv.next().await.unwrap().unwrap();
+        stream.send_reset(Reason::CANCEL);
+
+        let (_req, mut stream) = srv.next().await.unwrap().unwrap();
+        let rsp = http::Response::builder().status(200).body(()).unwrap();
+        stream.send_response(rsp, true).unwrap();
+
+        assert!(srv.next().await.is_none());
+
+        let stats = srv.stats();
+        assert_eq!(stats.frames_sent().reset(), 1);
+        assert_eq!(stats.frames_sent().headers(), 1);
+        assert_eq!(stats.frames_received().headers(), 2);
+        assert_eq!(stats.remote_streams_opened(), 2);
+        assert_eq!(stats.remote_streams_closed(), 2);
+        assert_eq!(stats.local_streams_opened(), 0);
+        assert_eq!(stats.streams_reset_locally(), 1);
+        assert_eq!(stats.streams_reset_remotely(), 0);
+    };
+
+    join(client, srv).await;
+}